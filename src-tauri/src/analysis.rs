@@ -0,0 +1,262 @@
+// src-tauri/src/analysis.rs - Производные растры рельефа: уклон, экспозиция, кривизна, шероховатость
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::HeightGrid;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisSettings {
+    // Сохранить растры анализа в debug/ как PNG
+    pub export_debug: bool,
+}
+
+// Экспозиция ровной ячейки (направления склона нет)
+pub const FLAT_ASPECT: f32 = -1.0;
+
+// Растры того же размера, что и сетка высот, строка 0 - северная
+pub struct TerrainAnalysis {
+    pub width: usize,
+    pub height: usize,
+    // Уклон (градусы)
+    pub slope_deg: Vec<f32>,
+    // Куда смотрит склон: градусы от севера по часовой, FLAT_ASPECT на ровном месте
+    pub aspect_deg: Vec<f32>,
+    // Кривизна поперёк склона (1/м): < 0 - гребни и отроги, > 0 - лощины
+    pub plan_curvature: Vec<f32>,
+    // Кривизна вдоль склона (1/м): < 0 - перегиб к крутому (бровка), > 0 - к пологому (подножие)
+    pub profile_curvature: Vec<f32>,
+    // Наибольший перепад высот в окне 3x3 (м), как gdaldem roughness
+    pub roughness: Vec<f32>,
+}
+
+// Склоны круче этого считаются крутыми в сводке
+const STEEP_SLOPE_DEG: f32 = 30.0;
+
+// Короткая сводка по рельефу для результата генерации
+#[derive(Debug, Clone, Serialize)]
+pub struct TerrainSummary {
+    pub mean_slope_deg: f32,
+    pub max_slope_deg: f32,
+    // Доля карты круче STEEP_SLOPE_DEG (%)
+    pub steep_percent: f32,
+    pub mean_roughness_m: f32,
+}
+
+impl TerrainAnalysis {
+    pub fn summary(&self) -> TerrainSummary {
+        let cells = self.slope_deg.len().max(1) as f64;
+        let mean = |data: &[f32]| (data.iter().map(|&v| v as f64).sum::<f64>() / cells) as f32;
+        let steep = self.slope_deg.iter().filter(|&&s| s > STEEP_SLOPE_DEG).count();
+        TerrainSummary {
+            mean_slope_deg: mean(&self.slope_deg),
+            max_slope_deg: self.slope_deg.iter().copied().fold(0.0, f32::max),
+            steep_percent: (steep as f64 * 100.0 / cells) as f32,
+            mean_roughness_m: mean(&self.roughness),
+        }
+    }
+}
+
+// Уклон в градусах по центральным разностям (на краях - односторонним)
+pub fn slope_degrees(grid: &HeightGrid, cell_size: f64) -> Vec<f64> {
+    let (w, h) = (grid.width, grid.height);
+    let mut slopes = vec![0.0; w * h];
+    for y in 0..h {
+        let (y0, y1) = (y.saturating_sub(1), (y + 1).min(h - 1));
+        for x in 0..w {
+            let (x0, x1) = (x.saturating_sub(1), (x + 1).min(w - 1));
+            let dzdx = (grid.get(x1, y) - grid.get(x0, y)) as f64 / ((x1 - x0).max(1) as f64 * cell_size);
+            let dzdy = (grid.get(x, y1) - grid.get(x, y0)) as f64 / ((y1 - y0).max(1) as f64 * cell_size);
+            slopes[y * w + x] = (dzdx * dzdx + dzdy * dzdy).sqrt().atan().to_degrees();
+        }
+    }
+    slopes
+}
+
+// Производные по окну 3x3 (Zevenbergen, Thorne 1987). Первые производные на краях
+// односторонние, вторые - по продлённому краю.
+pub fn analyze(grid: &HeightGrid, cell_size: f64) -> TerrainAnalysis {
+    let (w, h) = (grid.width, grid.height);
+    let l = cell_size;
+    let mut analysis = TerrainAnalysis {
+        width: w,
+        height: h,
+        slope_deg: slope_degrees(grid, cell_size).into_iter().map(|s| s as f32).collect(),
+        aspect_deg: vec![FLAT_ASPECT; w * h],
+        plan_curvature: vec![0.0; w * h],
+        profile_curvature: vec![0.0; w * h],
+        roughness: vec![0.0; w * h],
+    };
+
+    for y in 0..h {
+        let (y0, y1) = (y.saturating_sub(1), (y + 1).min(h - 1));
+        for x in 0..w {
+            let (x0, x1) = (x.saturating_sub(1), (x + 1).min(w - 1));
+            let z = |cx: usize, cy: usize| grid.get(cx, cy) as f64;
+            let center = z(x, y);
+
+            // x на восток, y на север (строки сетки идут с севера на юг)
+            let d = ((z(x0, y) + z(x1, y)) / 2.0 - center) / (l * l);
+            let e = ((z(x, y0) + z(x, y1)) / 2.0 - center) / (l * l);
+            let f = (-z(x0, y0) + z(x1, y0) + z(x0, y1) - z(x1, y1)) / (4.0 * l * l);
+            let g = (z(x1, y) - z(x0, y)) / ((x1 - x0).max(1) as f64 * l);
+            let hh = (z(x, y0) - z(x, y1)) / ((y1 - y0).max(1) as f64 * l);
+
+            let i = y * w + x;
+            let gradient2 = g * g + hh * hh;
+            if gradient2 > 1e-12 {
+                // Направление спуска - против градиента
+                analysis.aspect_deg[i] = ((-g).atan2(-hh).to_degrees().rem_euclid(360.0)) as f32;
+                analysis.profile_curvature[i] = (2.0 * (d * g * g + e * hh * hh + f * g * hh) / gradient2) as f32;
+                analysis.plan_curvature[i] = (2.0 * (d * hh * hh + e * g * g - f * g * hh) / gradient2) as f32;
+            }
+
+            let mut min = f32::INFINITY;
+            let mut max = f32::NEG_INFINITY;
+            for cy in y0..=y1 {
+                for cx in x0..=x1 {
+                    min = min.min(grid.get(cx, cy));
+                    max = max.max(grid.get(cx, cy));
+                }
+            }
+            analysis.roughness[i] = max - min;
+        }
+    }
+    analysis
+}
+
+// Отладочные PNG: slope, aspect (цветовой круг, ровное - серое), plan/profile_curvature
+// (128 - ноль, светлее - вогнуто), roughness. Кривизна и шероховатость нормированы
+// по 98-му перцентилю, чтобы единичные выбросы не делали карту чёрной.
+pub fn save_debug_pngs(analysis: &TerrainAnalysis, dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let (w, h) = (analysis.width as u32, analysis.height as u32);
+    let at = |data: &[f32], x: u32, y: u32| data[y as usize * analysis.width + x as usize];
+    let save_gray = |name: &str, data: &[f32], to_byte: &dyn Fn(f32) -> u8| {
+        image::GrayImage::from_fn(w, h, |x, y| image::Luma([to_byte(at(data, x, y))]))
+            .save(dir.join(name))
+            .map_err(|e| e.to_string())
+    };
+
+    save_gray("slope.png", &analysis.slope_deg, &|s| (s / 90.0 * 255.0).round() as u8)?;
+
+    let aspect = image::RgbImage::from_fn(w, h, |x, y| {
+        let a = at(&analysis.aspect_deg, x, y);
+        if a == FLAT_ASPECT {
+            image::Rgb([128, 128, 128])
+        } else {
+            image::Rgb(hue_to_rgb(a))
+        }
+    });
+    aspect.save(dir.join("aspect.png")).map_err(|e| e.to_string())?;
+
+    for (name, data) in [
+        ("plan_curvature.png", &analysis.plan_curvature),
+        ("profile_curvature.png", &analysis.profile_curvature),
+    ] {
+        let scale = percentile_abs(data, 0.98).max(f32::EPSILON);
+        save_gray(name, data, &|c| (128.0 + (c / scale).clamp(-1.0, 1.0) * 127.0).round() as u8)?;
+    }
+
+    let scale = percentile_abs(&analysis.roughness, 0.98).max(f32::EPSILON);
+    save_gray("roughness.png", &analysis.roughness, &|r| ((r / scale).min(1.0) * 255.0).round() as u8)
+}
+
+fn percentile_abs(data: &[f32], q: f64) -> f32 {
+    let mut values: Vec<f32> = data.iter().map(|v| v.abs()).collect();
+    if values.is_empty() {
+        return 0.0;
+    }
+    let k = ((values.len() - 1) as f64 * q) as usize;
+    *values.select_nth_unstable_by(k, f32::total_cmp).1
+}
+
+// Цвет по азимуту: север - красный, восток - жёлто-зелёный, юг - голубой, запад - сине-фиолетовый
+fn hue_to_rgb(degrees: f32) -> [u8; 3] {
+    let h = degrees.rem_euclid(360.0) / 60.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let (r, g, b) = match h as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    [(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: usize = 101;
+
+    // Гауссов холм высотой 50 м в центре сетки
+    fn hill(sign: f32) -> HeightGrid {
+        HeightGrid::from_fn(N, N, |x, y| {
+            let r2 = ((x as f32 - 50.0).powi(2) + (y as f32 - 50.0).powi(2)) / 400.0;
+            100.0 + sign * 50.0 * (-r2).exp()
+        })
+    }
+
+    fn at(data: &[f32], x: usize, y: usize) -> f32 {
+        data[y * N + x]
+    }
+
+    #[test]
+    fn aspect_points_downhill() {
+        let a = analyze(&hill(1.0), 2.0);
+        // Строка 0 - север
+        assert!(at(&a.aspect_deg, 50, 40).min(360.0 - at(&a.aspect_deg, 50, 40)) < 1.0);
+        assert!((at(&a.aspect_deg, 60, 50) - 90.0).abs() < 1.0);
+        assert!((at(&a.aspect_deg, 60, 60) - 135.0).abs() < 1.0);
+        assert!((at(&a.aspect_deg, 50, 60) - 180.0).abs() < 1.0);
+        assert!((at(&a.aspect_deg, 40, 50) - 270.0).abs() < 1.0);
+        assert_eq!(at(&a.aspect_deg, 50, 50), FLAT_ASPECT);
+    }
+
+    #[test]
+    fn curvature_signs() {
+        let a = analyze(&hill(1.0), 2.0);
+        // Верх склона выпуклый, подножие вогнутое, вокруг холма горизонтали расходятся
+        assert!(at(&a.profile_curvature, 58, 50) < 0.0);
+        assert!(at(&a.profile_curvature, 50, 85) > 0.0);
+        assert!(at(&a.plan_curvature, 60, 60) < 0.0);
+
+        let pit = analyze(&hill(-1.0), 2.0);
+        assert!(at(&pit.plan_curvature, 60, 60) > 0.0);
+    }
+
+    #[test]
+    fn slope_and_roughness() {
+        let a = analyze(&hill(1.0), 2.0);
+        assert!(at(&a.slope_deg, 64, 50) > at(&a.slope_deg, 90, 50));
+        assert!(at(&a.roughness, 60, 50) > 1.0);
+        assert!(at(&a.roughness, 0, 0) < 0.01);
+
+        let summary = a.summary();
+        assert_eq!(summary.max_slope_deg, a.slope_deg.iter().copied().fold(0.0, f32::max));
+        assert!(summary.mean_slope_deg > 0.0 && summary.mean_slope_deg < summary.max_slope_deg);
+    }
+
+    #[test]
+    fn debug_pngs_match_the_grid() {
+        let a = analyze(&hill(1.0), 2.0);
+        let dir = tempfile::tempdir().unwrap();
+        save_debug_pngs(&a, dir.path()).unwrap();
+        for name in ["slope.png", "aspect.png", "plan_curvature.png", "profile_curvature.png", "roughness.png"] {
+            let image = image::open(dir.path().join(name)).unwrap();
+            assert_eq!((image.width(), image.height()), (N as u32, N as u32));
+        }
+    }
+
+    #[test]
+    fn hue_wheel() {
+        assert_eq!(hue_to_rgb(0.0), [255, 0, 0]);
+        assert_eq!(hue_to_rgb(120.0), [0, 255, 0]);
+        assert_eq!(hue_to_rgb(240.0), [0, 0, 255]);
+    }
+}
@@ -0,0 +1,208 @@
+// src-tauri/src/ascii_grid.rs - ESRI ASCII grid (.asc)
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+
+use crate::elevation::{ElevationData, ElevationSource, FetchContext};
+use crate::raster::{self, Crs, GeoRaster, GeoTransform};
+use crate::BoundingBox;
+
+// Наибольшее число ячеек растра (1 ГиБ в f32)
+const MAX_CELLS: usize = 1 << 28;
+
+pub struct AsciiGridSource {
+    pub path: PathBuf,
+    // В .asc нет сведений о проекции; по умолчанию WGS84 lat/lon
+    pub epsg: Option<u32>,
+}
+
+#[async_trait]
+impl ElevationSource for AsciiGridSource {
+    fn name(&self) -> String {
+        format!(
+            "ASCII grid {}",
+            self.path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()
+        )
+    }
+
+    async fn fetch(&self, bbox: &BoundingBox, ctx: &FetchContext) -> Result<ElevationData, String> {
+        let crs = Crs::from_epsg(self.epsg.unwrap_or(4326))?;
+        let raster = read_ascii_grid(&self.path, crs)?;
+        println!("Loaded ASCII grid {}x{} ({:?})", raster.width, raster.height, raster.crs);
+
+        raster::rasters_to_height_grid(&[raster], bbox, ctx.meters_per_pixel, &self.name()).map(ElevationData::from)
+    }
+}
+
+// Заголовок (ncols, nrows, xllcorner|xllcenter, yllcorner|yllcenter,
+// cellsize или dx/dy, необязательный NODATA_value), затем строки с севера на юг
+pub fn read_ascii_grid(path: &Path, crs: Crs) -> Result<GeoRaster, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut tokens = text.split_whitespace().peekable();
+
+    let mut header: HashMap<String, f64> = HashMap::new();
+    while let Some(&token) = tokens.peek() {
+        if token.parse::<f64>().is_ok() {
+            break;
+        }
+        let key = token.to_lowercase();
+        tokens.next();
+        let value = tokens
+            .next()
+            .and_then(|v| v.parse::<f64>().ok())
+            .ok_or_else(|| format!("{}: missing value for {}", path.display(), key))?;
+        header.insert(key, value);
+    }
+
+    let get = |key: &str| {
+        header
+            .get(key)
+            .copied()
+            .ok_or_else(|| format!("{}: missing {} in header", path.display(), key))
+    };
+
+    let count = |key: &str| {
+        let value = get(key)?;
+        if value.is_finite() && value > 0.0 && value.fract() == 0.0 {
+            Ok(value as usize)
+        } else {
+            Err(format!("{}: {} must be a positive integer, got {}", path.display(), key, value))
+        }
+    };
+    let step = |key: &str| {
+        let value = get(key)?;
+        if value.is_finite() && value > 0.0 {
+            Ok(value)
+        } else {
+            Err(format!("{}: {} must be positive, got {}", path.display(), key, value))
+        }
+    };
+
+    let width = count("ncols")?;
+    let height = count("nrows")?;
+    let cells = width
+        .checked_mul(height)
+        .filter(|&n| n <= MAX_CELLS)
+        .ok_or_else(|| {
+            format!(
+                "{} is too large ({}x{}), crop it to the map area first",
+                path.display(),
+                width,
+                height
+            )
+        })?;
+    let (dx, dy) = if header.contains_key("cellsize") {
+        (step("cellsize")?, step("cellsize")?)
+    } else {
+        (step("dx")?, step("dy")?)
+    };
+    let nodata = header.get("nodata_value").copied();
+
+    // Левый нижний угол растра
+    let x0 = match header.get("xllcorner") {
+        Some(&x) => x,
+        None => get("xllcenter")? - dx / 2.0,
+    };
+    let y0 = match header.get("yllcorner") {
+        Some(&y) => y,
+        None => get("yllcenter")? - dy / 2.0,
+    };
+
+    let mut data = Vec::with_capacity(cells);
+    for token in tokens {
+        let v: f64 = token
+            .parse()
+            .map_err(|_| format!("{}: invalid value '{}'", path.display(), token))?;
+        data.push(if Some(v) == nodata { f32::NAN } else { v as f32 });
+    }
+
+    if data.len() != cells {
+        return Err(format!(
+            "{}: expected {}x{} values, found {}",
+            path.display(),
+            width,
+            height,
+            data.len()
+        ));
+    }
+
+    Ok(GeoRaster {
+        width,
+        height,
+        data,
+        transform: GeoTransform([x0, dx, 0.0, y0 + height as f64 * dy, 0.0, -dy]),
+        crs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(name: &str, text: &str) -> Result<GeoRaster, String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, text).unwrap();
+        read_ascii_grid(&path, Crs::Geographic)
+    }
+
+    #[test]
+    fn header_and_rows_north_to_south() {
+        let raster = read(
+            "ascii_grid_corner_test.asc",
+            "ncols 3\nnrows 2\nxllcorner 8.0\nyllcorner 47.0\ncellsize 0.5\nNODATA_value -9999\n\
+             1 2 3\n4 -9999 6\n",
+        )
+        .unwrap();
+        assert_eq!((raster.width, raster.height), (3, 2));
+        assert_eq!(raster.transform.0, [8.0, 0.5, 0.0, 48.0, 0.0, -0.5]);
+        assert_eq!(raster.data[0], 1.0);
+        assert!(raster.data[4].is_nan());
+        // Северо-западный пиксель и юго-восточный
+        assert_eq!(raster.sample(8.25, 47.75), 1.0);
+        assert_eq!(raster.sample(9.25, 47.25), 6.0);
+    }
+
+    #[test]
+    fn cell_centre_origin_and_separate_steps() {
+        let raster = read(
+            "ascii_grid_center_test.asc",
+            "NCOLS 2\nNROWS 2\nXLLCENTER 10\nYLLCENTER 20\nDX 2\nDY 4\n1 2\n3 4\n",
+        )
+        .unwrap();
+        assert_eq!(raster.transform.0, [9.0, 2.0, 0.0, 26.0, 0.0, -4.0]);
+    }
+
+    #[test]
+    fn wrong_value_count_is_an_error() {
+        let result = read(
+            "ascii_grid_short_test.asc",
+            "ncols 2\nnrows 2\nxllcorner 0\nyllcorner 0\ncellsize 1\n1 2 3\n",
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn bad_dimensions_are_rejected() {
+        for (ncols, nrows) in [("-3", "2"), ("2.5", "2"), ("0", "2"), ("nan", "2"), ("inf", "2")] {
+            let text = format!("ncols {}\nnrows {}\nxllcorner 0\nyllcorner 0\ncellsize 1\n1 2\n", ncols, nrows);
+            assert!(read("ascii_grid_dims_test.asc", &text).is_err(), "ncols {}", ncols);
+        }
+        // Произведение переполняет usize / превышает предел - ошибка, а не паника
+        let huge = "ncols 1e18\nnrows 1e18\nxllcorner 0\nyllcorner 0\ncellsize 1\n1\n";
+        assert!(read("ascii_grid_huge_test.asc", huge).is_err());
+        let big = "ncols 100000\nnrows 100000\nxllcorner 0\nyllcorner 0\ncellsize 1\n1\n";
+        assert!(read("ascii_grid_big_test.asc", big).is_err());
+    }
+
+    #[test]
+    fn bad_cell_size_is_rejected() {
+        for cell in ["cellsize 0", "cellsize -1", "cellsize nan", "dx 1\ndy 0", "dx -2\ndy 1"] {
+            let text = format!("ncols 1\nnrows 1\nxllcorner 0\nyllcorner 0\n{}\n1\n", cell);
+            assert!(read("ascii_grid_cell_test.asc", &text).is_err(), "{}", cell);
+        }
+    }
+}
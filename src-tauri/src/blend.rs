@@ -0,0 +1,271 @@
+// src-tauri/src/blend.rs - Смешивание нескольких источников высот по приоритету
+
+use std::path::Path;
+
+use crate::raster::MAX_SOURCE_GRID;
+use crate::terrain::{self, HeightGrid, MercatorBounds, ResampleMethod};
+
+// В карте источников: ни у одного источника нет данных
+pub const NO_SOURCE: u8 = u8::MAX;
+
+// Цвета отладочной карты источников по индексу (приоритету)
+const SOURCE_COLORS: [[u8; 3]; 8] = [
+    [230, 25, 75],
+    [60, 180, 75],
+    [0, 130, 200],
+    [255, 225, 25],
+    [145, 30, 180],
+    [245, 130, 48],
+    [70, 240, 240],
+    [240, 50, 230],
+];
+const NO_SOURCE_COLOR: [u8; 3] = [0, 0, 0];
+
+pub struct BlendedGrid {
+    pub grid: HeightGrid,
+    // Индекс источника, который дал основной вклад в ячейку (NO_SOURCE - никто)
+    pub source_map: Vec<u8>,
+}
+
+impl BlendedGrid {
+    // Единственный источник: индекс 0 там, где у него есть данные
+    pub fn single(grid: HeightGrid) -> Self {
+        let source_map = grid.valid.iter().map(|&v| if v { 0 } else { NO_SOURCE }).collect();
+        BlendedGrid { grid, source_map }
+    }
+}
+
+// Сводит сетки источников (первая - самый высокий приоритет) в одну сетку по bounds.
+// Более приоритетный источник побеждает там, где у него есть данные; у края его
+// покрытия вес плавно спадает до нуля на ширине feather_width (метры Web Mercator),
+// чтобы не было ступеньки. Сторона общей сетки не больше max_size.
+pub fn blend_sources(
+    layers: &[HeightGrid],
+    bounds: MercatorBounds,
+    method: ResampleMethod,
+    feather_width: f64,
+    max_size: usize,
+) -> BlendedGrid {
+    // Шаг общей сетки - самый мелкий среди источников
+    let pixel = layers
+        .iter()
+        .map(|l| {
+            let (dx, dy) = l.pixel_size();
+            dx.min(dy)
+        })
+        .fold(f64::INFINITY, f64::min);
+    let max_size = max_size.clamp(2, MAX_SOURCE_GRID);
+    let width = ((bounds.width() / pixel).ceil() as usize).clamp(2, max_size);
+    let height = ((bounds.height() / pixel).ceil() as usize).clamp(2, max_size);
+
+    let feather_px = feather_width / (bounds.width() / width as f64);
+
+    let mut grid = HeightGrid::with_nodata(width, height, bounds);
+    let mut source_map = vec![NO_SOURCE; width * height];
+
+    // От низкого приоритета к высокому, каждый следующий ложится сверху
+    for (index, layer) in layers.iter().enumerate().rev() {
+        let resampled = terrain::resample_valid_to_bounds(layer, bounds, width, height, method);
+        let distance = distance_to_invalid(&resampled.valid, width, height);
+
+        for i in 0..grid.data.len() {
+            if !resampled.valid[i] {
+                continue;
+            }
+            let weight = if !grid.valid[i] || feather_px <= 0.0 {
+                1.0
+            } else {
+                (distance[i] as f64 / feather_px).min(1.0) as f32
+            };
+            grid.data[i] = grid.data[i] * (1.0 - weight) + resampled.data[i] * weight;
+            if !grid.valid[i] || weight >= 0.5 {
+                source_map[i] = index as u8;
+            }
+            grid.valid[i] = true;
+        }
+    }
+
+    BlendedGrid { grid, source_map }
+}
+
+// Расстояние (в пикселях) от каждой ячейки до ближайшей ячейки без данных.
+// Двухпроходная фаска 1/√2; края сетки пустотой не считаются.
+pub fn distance_to_invalid(valid: &[bool], width: usize, height: usize) -> Vec<f32> {
+    let diagonal = std::f32::consts::SQRT_2;
+    let mut dist: Vec<f32> = valid
+        .iter()
+        .map(|&v| if v { f32::INFINITY } else { 0.0 })
+        .collect();
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let mut d = dist[i];
+            if x > 0 {
+                d = d.min(dist[i - 1] + 1.0);
+            }
+            if y > 0 {
+                d = d.min(dist[i - width] + 1.0);
+                if x > 0 {
+                    d = d.min(dist[i - width - 1] + diagonal);
+                }
+                if x + 1 < width {
+                    d = d.min(dist[i - width + 1] + diagonal);
+                }
+            }
+            dist[i] = d;
+        }
+    }
+
+    for y in (0..height).rev() {
+        for x in (0..width).rev() {
+            let i = y * width + x;
+            let mut d = dist[i];
+            if x + 1 < width {
+                d = d.min(dist[i + 1] + 1.0);
+            }
+            if y + 1 < height {
+                d = d.min(dist[i + width] + 1.0);
+                if x + 1 < width {
+                    d = d.min(dist[i + width + 1] + diagonal);
+                }
+                if x > 0 {
+                    d = d.min(dist[i + width - 1] + diagonal);
+                }
+            }
+            dist[i] = d;
+        }
+    }
+
+    dist
+}
+
+// Отладочная карта: PNG с цветом источника в каждой ячейке и JSON с легендой
+pub fn save_source_map(blended: &BlendedGrid, source_names: &[String], dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let (width, height) = (blended.grid.width as u32, blended.grid.height as u32);
+    let mut img = image::RgbImage::new(width, height);
+    let mut counts = vec![0usize; source_names.len()];
+    for (i, &source) in blended.source_map.iter().enumerate() {
+        let color = if source == NO_SOURCE {
+            NO_SOURCE_COLOR
+        } else {
+            counts[source as usize] += 1;
+            SOURCE_COLORS[source as usize % SOURCE_COLORS.len()]
+        };
+        img.put_pixel(i as u32 % width, i as u32 / width, image::Rgb(color));
+    }
+    img.save(dir.join("elevation_sources.png")).map_err(|e| e.to_string())?;
+
+    let total = blended.source_map.len() as f64;
+    let legend: Vec<serde_json::Value> = source_names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            serde_json::json!({
+                "priority": index,
+                "name": name,
+                "color": SOURCE_COLORS[index % SOURCE_COLORS.len()],
+                "coverage_percent": counts[index] as f64 * 100.0 / total,
+            })
+        })
+        .collect();
+    let legend = serde_json::to_string_pretty(&legend).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join("elevation_sources.json"), legend).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(size: f64) -> MercatorBounds {
+        MercatorBounds { min_x: 0.0, min_y: 0.0, max_x: size, max_y: size }
+    }
+
+    #[test]
+    fn distance_counts_steps_and_diagonals() {
+        let mut valid = vec![true; 9];
+        valid[0] = false;
+        let dist = distance_to_invalid(&valid, 3, 3);
+        assert_eq!(dist[0], 0.0);
+        assert_eq!(dist[1], 1.0);
+        assert_eq!(dist[2], 2.0);
+        assert_eq!(dist[4], std::f32::consts::SQRT_2);
+        assert_eq!(dist[8], 2.0 * std::f32::consts::SQRT_2);
+    }
+
+    #[test]
+    fn grid_edge_is_not_a_void() {
+        let dist = distance_to_invalid(&[true; 4], 2, 2);
+        assert!(dist.iter().all(|d| d.is_infinite()));
+    }
+
+    #[test]
+    fn higher_priority_source_wins_where_it_has_data() {
+        let mut detailed = HeightGrid::with_nodata(4, 4, bounds(4.0));
+        for y in 0..4 {
+            for x in 0..2 {
+                detailed.set(x, y, 100.0);
+            }
+        }
+        let coarse = HeightGrid::from_fn(4, 4, |_, _| 10.0);
+
+        let blended = blend_sources(&[detailed, coarse], bounds(4.0), ResampleMethod::Bilinear, 0.0, 16);
+        assert_eq!(blended.grid.get(0, 0), 100.0);
+        assert_eq!(blended.grid.get(3, 3), 10.0);
+        assert_eq!(blended.source_map[0], 0);
+        assert_eq!(blended.source_map[3], 1);
+        assert_eq!(blended.grid.invalid_count(), 0);
+    }
+
+    #[test]
+    fn seam_is_feathered_across_the_coverage_edge() {
+        // Подробный источник покрывает левую половину полосы 20x4, грубый - всю
+        let coarse = HeightGrid::from_fn(20, 4, |_, _| 10.0);
+        let strip = coarse.bounds;
+        let mut detailed = HeightGrid::with_nodata(20, 4, strip);
+        for y in 0..4 {
+            for x in 0..10 {
+                detailed.set(x, y, 100.0);
+            }
+        }
+
+        let blended = blend_sources(&[detailed, coarse], strip, ResampleMethod::Bilinear, 4.0, 64);
+        let grid = &blended.grid;
+        assert_eq!((grid.width, grid.height), (20, 4));
+        for y in 0..4 {
+            // Вес подробного источника растёт на 1/4 за пиксель от края его покрытия
+            for (x, weight) in [(9, 0.25), (8, 0.5), (7, 0.75), (6, 1.0), (0, 1.0)] {
+                let expected = 10.0 + 90.0 * weight;
+                assert!((grid.get(x, y) - expected).abs() < 1e-4, "({}, {}) = {}", x, y, grid.get(x, y));
+            }
+            assert_eq!(grid.get(10, y), 10.0);
+            for x in 0..19 {
+                assert!(grid.get(x, y) >= grid.get(x + 1, y));
+                assert!((10.0..=100.0).contains(&grid.get(x, y)));
+            }
+        }
+        // В карте источников ячейка достаётся тому, чей вес не меньше половины
+        assert_eq!(blended.source_map[8], 0);
+        assert_eq!(blended.source_map[9], 1);
+    }
+
+    #[test]
+    fn single_source_map_follows_its_mask() {
+        let mut grid = HeightGrid::with_nodata(2, 2, bounds(2.0));
+        grid.set(0, 0, 5.0);
+        grid.set(1, 1, 7.0);
+        let blended = BlendedGrid::single(grid);
+        assert_eq!(blended.source_map, [0, NO_SOURCE, NO_SOURCE, 0]);
+
+        let dir = tempfile::tempdir().unwrap();
+        save_source_map(&blended, &["DEM".to_string()], dir.path()).unwrap();
+        let legend: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.path().join("elevation_sources.json")).unwrap()).unwrap();
+        assert_eq!(legend[0]["coverage_percent"], 50.0);
+        assert!(dir.path().join("elevation_sources.png").exists());
+    }
+}
@@ -0,0 +1,284 @@
+// src-tauri/src/border.rs - Обработка краёв карты: спуск к плоскости, зеркало, горный вал
+
+use serde::{Deserialize, Serialize};
+
+use crate::roads::smoothstep;
+use crate::terrain::HeightGrid;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BorderSettings {
+    pub treatment: BorderTreatment,
+    // Полоса у края, где не ставятся объекты и дороги (м); None - ширина обработки края
+    pub object_margin_m: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BorderTreatment {
+    // Рельеф обрывается по краю карты как есть
+    #[default]
+    None,
+    // Плавный спуск к плоскости
+    Falloff(FalloffSettings),
+    // Полоса у края заменяется отражением рельефа за ней
+    Mirror(MirrorSettings),
+    // Подъём к валу выше окружающего рельефа - край карты не виден
+    Rim(RimSettings),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FalloffPlane {
+    // Самая низкая точка карты
+    #[default]
+    Lowest,
+    // Средняя высота карты
+    Mean,
+    // Высота 0 м над уровнем моря
+    SeaLevel,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FalloffSettings {
+    pub width_m: f64,
+    pub plane: FalloffPlane,
+}
+
+impl Default for FalloffSettings {
+    fn default() -> Self {
+        FalloffSettings {
+            width_m: 200.0,
+            plane: FalloffPlane::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MirrorSettings {
+    pub width_m: f64,
+}
+
+impl Default for MirrorSettings {
+    fn default() -> Self {
+        MirrorSettings { width_m: 100.0 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RimSettings {
+    pub width_m: f64,
+    // Насколько гребень выше самой высокой точки в полосе у края (м)
+    pub height_m: f64,
+}
+
+impl Default for RimSettings {
+    fn default() -> Self {
+        RimSettings {
+            width_m: 300.0,
+            height_m: 50.0,
+        }
+    }
+}
+
+impl BorderTreatment {
+    pub fn width_m(&self) -> f64 {
+        match self {
+            BorderTreatment::None => 0.0,
+            BorderTreatment::Falloff(s) => s.width_m,
+            BorderTreatment::Mirror(s) => s.width_m,
+            BorderTreatment::Rim(s) => s.width_m,
+        }
+    }
+}
+
+impl BorderSettings {
+    pub fn object_margin_m(&self) -> f64 {
+        self.object_margin_m.unwrap_or_else(|| self.treatment.width_m())
+    }
+
+    // Проверка до генерации: полоса должна помещаться в карту шириной map_size_m
+    pub fn validate(&self, map_size_m: f64) -> Result<(), String> {
+        let width_m = self.treatment.width_m();
+        if !width_m.is_finite() || width_m < 0.0 {
+            return Err(format!("Border width must be non-negative, got {}", width_m));
+        }
+        if let Some(margin) = self.object_margin_m {
+            if !margin.is_finite() || margin < 0.0 {
+                return Err(format!("Border object margin must be non-negative, got {}", margin));
+            }
+        }
+        if let BorderTreatment::Rim(s) = &self.treatment {
+            if !s.height_m.is_finite() {
+                return Err(format!("Invalid border rim height: {} m", s.height_m));
+            }
+        }
+        if width_m > 0.0 && width_m * 2.0 >= map_size_m {
+            return Err(format!(
+                "Border of {} m does not fit into a {:.0} m wide terrain",
+                width_m, map_size_m
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Обрабатывает полосу шириной width_m вдоль всех четырёх краёв
+pub fn apply_border(grid: &mut HeightGrid, settings: &BorderSettings, square_size: f64) -> Result<(), String> {
+    let (w, h) = (grid.width, grid.height);
+    settings.validate(w.min(h) as f64 * square_size)?;
+    let band = settings.treatment.width_m() / square_size;
+    if band <= 0.0 {
+        return Ok(());
+    }
+
+    // 1 у самого края, 0 на внутренней границе полосы
+    let edge_weight = |x: usize, y: usize| {
+        let d = x.min(y).min(w - 1 - x).min(h - 1 - y) as f64 + 0.5;
+        1.0 - smoothstep((d / band) as f32)
+    };
+
+    match &settings.treatment {
+        BorderTreatment::None => {}
+        BorderTreatment::Falloff(s) => {
+            let plane = match s.plane {
+                FalloffPlane::Lowest => grid.min_max().0,
+                FalloffPlane::Mean => (grid.data.iter().map(|&v| v as f64).sum::<f64>() / grid.data.len() as f64) as f32,
+                FalloffPlane::SeaLevel => 0.0,
+            };
+            blend_towards(grid, plane, edge_weight);
+            println!("Border falloff to {:.1} m over {} m", plane, s.width_m);
+        }
+        BorderTreatment::Mirror(s) => {
+            // Отражение относительно внутренней границы полосы
+            let n = band.round() as isize;
+            let (wi, hi) = (w as isize, h as isize);
+            let reflect = |v: isize, size: isize| {
+                if v < n {
+                    2 * n - v
+                } else if v > size - 1 - n {
+                    2 * (size - 1 - n) - v
+                } else {
+                    v
+                }
+            };
+            let source = grid.data.clone();
+            for y in 0..hi {
+                for x in 0..wi {
+                    let (sx, sy) = (reflect(x, wi), reflect(y, hi));
+                    if sx != x || sy != y {
+                        grid.data[(y * wi + x) as usize] = source[(sy * wi + sx) as usize];
+                    }
+                }
+            }
+            println!("Border mirrored over {} m", s.width_m);
+        }
+        BorderTreatment::Rim(s) => {
+            let mut band_max = f32::NEG_INFINITY;
+            for y in 0..h {
+                for x in 0..w {
+                    if edge_weight(x, y) > 0.0 {
+                        band_max = band_max.max(grid.get(x, y));
+                    }
+                }
+            }
+            let crest = band_max + s.height_m as f32;
+            blend_towards(grid, crest, edge_weight);
+            println!("Border rim at {:.1} m over {} m", crest, s.width_m);
+        }
+    }
+    Ok(())
+}
+
+// true, если точка уровня (x восток, z север, м) ближе margin_m к краю карты
+pub fn in_margin(x: f32, z: f32, map_size_m: f64, margin_m: f64) -> bool {
+    let (x, z) = (x as f64, z as f64);
+    margin_m > 0.0 && (x < margin_m || z < margin_m || x > map_size_m - margin_m || z > map_size_m - margin_m)
+}
+
+fn blend_towards(grid: &mut HeightGrid, target: f32, weight: impl Fn(usize, usize) -> f32) {
+    let w = grid.width;
+    for y in 0..grid.height {
+        for x in 0..w {
+            let k = weight(x, y);
+            if k > 0.0 {
+                let i = y * w + x;
+                grid.data[i] += (target - grid.data[i]) * k;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp(n: usize) -> HeightGrid {
+        HeightGrid::from_fn(n, n, |x, _| 100.0 + x as f32)
+    }
+
+    #[test]
+    fn falloff_reaches_the_plane_and_keeps_the_inside() {
+        let settings = BorderSettings {
+            treatment: BorderTreatment::Falloff(FalloffSettings { width_m: 20.0, plane: FalloffPlane::SeaLevel }),
+            object_margin_m: None,
+        };
+        let mut grid = ramp(100);
+        apply_border(&mut grid, &settings, 2.0).unwrap();
+        assert!(grid.get(0, 50) < 5.0 && grid.get(99, 50) < 5.0);
+        assert_eq!(grid.get(50, 50), 150.0);
+        assert_eq!(settings.object_margin_m(), 20.0);
+    }
+
+    #[test]
+    fn mirror_reflects_the_inner_terrain() {
+        let settings = BorderSettings {
+            treatment: BorderTreatment::Mirror(MirrorSettings { width_m: 20.0 }),
+            object_margin_m: None,
+        };
+        let original = ramp(100);
+        let mut grid = original.clone();
+        apply_border(&mut grid, &settings, 2.0).unwrap();
+        assert_eq!(grid.get(0, 50), original.get(20, 50));
+        assert_eq!(grid.get(99, 50), original.get(79, 50));
+    }
+
+    #[test]
+    fn too_wide_border_is_rejected() {
+        let settings = BorderSettings {
+            treatment: BorderTreatment::Rim(RimSettings { width_m: 150.0, height_m: 10.0 }),
+            object_margin_m: None,
+        };
+        assert!(apply_border(&mut ramp(100), &settings, 2.0).is_err());
+        assert!(settings.validate(200.0).is_err());
+        assert!(settings.validate(400.0).is_ok());
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected_before_generation() {
+        let margin = BorderSettings { treatment: BorderTreatment::None, object_margin_m: Some(-5.0) };
+        assert!(margin.validate(1000.0).is_err());
+        let rim = BorderSettings {
+            treatment: BorderTreatment::Rim(RimSettings { width_m: 50.0, height_m: f64::NAN }),
+            object_margin_m: None,
+        };
+        assert!(rim.validate(1000.0).is_err());
+        let falloff = BorderSettings {
+            treatment: BorderTreatment::Falloff(FalloffSettings { width_m: -1.0, plane: FalloffPlane::Lowest }),
+            object_margin_m: None,
+        };
+        assert!(falloff.validate(1000.0).is_err());
+        assert!(BorderSettings::default().validate(1000.0).is_ok());
+    }
+
+    #[test]
+    fn margin_covers_all_four_edges() {
+        assert!(in_margin(5.0, 100.0, 200.0, 20.0));
+        assert!(in_margin(100.0, 190.0, 200.0, 20.0));
+        assert!(!in_margin(100.0, 100.0, 200.0, 20.0));
+        assert!(!in_margin(5.0, 5.0, 200.0, 0.0));
+    }
+}
@@ -0,0 +1,425 @@
+// src-tauri/src/buildings.rs - Выровненные площадки под зданиями по контурам из OSM
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::border;
+use crate::roads::{check_non_negative, point_segment_distance, smoothstep, Candidate};
+use crate::terrain::HeightGrid;
+use crate::{latlon_to_beamng, OSMElement};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PadBase {
+    // Самая низкая точка контура - здание не висит, но сильнее врезается в склон
+    Min,
+    #[default]
+    Mean,
+    // Высота у входа (узлы entrance=* в контуре), без входов - средняя
+    Entrance,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuildingPadSettings {
+    pub enabled: bool,
+    pub base: PadBase,
+    // Насколько площадка шире контура (м)
+    pub margin_m: f64,
+    // Ширина перехода от площадки к рельефу (м)
+    pub falloff_m: f64,
+    // Круче этого площадку не делаем, здание попадает в отчёт
+    pub max_slope_deg: f64,
+}
+
+impl Default for BuildingPadSettings {
+    fn default() -> Self {
+        BuildingPadSettings {
+            enabled: true,
+            base: PadBase::default(),
+            margin_m: 1.0,
+            falloff_m: 4.0,
+            max_slope_deg: 15.0,
+        }
+    }
+}
+
+// Здание на слишком крутом склоне (не выровнено)
+#[derive(Debug, Clone, Serialize)]
+pub struct SteepBuilding {
+    pub osm_id: i64,
+    pub slope_deg: f64,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct PadReport {
+    pub leveled: usize,
+    pub steep: Vec<SteepBuilding>,
+}
+
+struct Footprint {
+    osm_id: i64,
+    // Вершины контура в координатах сетки (ячейки, дробные)
+    polygon: Vec<(f64, f64)>,
+    entrances: Vec<(f64, f64)>,
+    // Первый узел контура - для отчёта и проверки полосы у края (там же стоит объект здания)
+    lat_lon: (f64, f64),
+}
+
+// Выравнивает рельеф под зданиями. Ячейки с protected = true (дороги) не меняются,
+// ячейки площадок добавляются в protected. Высоты площадок берутся из рельефа до выравнивания.
+// Здания в полосе object_margin_m у края пропускаются - их объекты тоже удалены.
+pub fn level_building_pads(
+    heightmap: &mut HeightGrid,
+    elements: &[OSMElement],
+    square_size: f64,
+    settings: &BuildingPadSettings,
+    object_margin_m: f64,
+    protected: &mut [bool],
+) -> Result<PadReport, String> {
+    let mut report = PadReport::default();
+    if !settings.enabled {
+        return Ok(report);
+    }
+    check_non_negative(
+        "Building pad",
+        &[
+            ("margin_m", settings.margin_m),
+            ("falloff_m", settings.falloff_m),
+            ("max_slope_deg", settings.max_slope_deg),
+        ],
+    )?;
+
+    let (w, h) = (heightmap.width, heightmap.height);
+    let margin = settings.margin_m / square_size;
+    let falloff = settings.falloff_m / square_size;
+    let reach = margin + falloff;
+    let map_size_m = w as f64 * square_size;
+    let mut best: Vec<Option<Candidate>> = vec![None; w * h];
+
+    for footprint in collect_footprints(elements, heightmap) {
+        let (x, _, z) = latlon_to_beamng(footprint.lat_lon.0, footprint.lat_lon.1, heightmap, square_size);
+        if border::in_margin(x, z, map_size_m, object_margin_m) {
+            continue;
+        }
+        let (x0, y0, x1, y1) = match footprint_cells(&footprint.polygon, reach, w, h) {
+            Some(range) => range,
+            None => continue,
+        };
+
+        // Ячейки внутри контура: высоты и точки для плоскости
+        let mut inside = Vec::new();
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                if point_in_polygon((x as f64, y as f64), &footprint.polygon) {
+                    inside.push((x, y, heightmap.get(x, y)));
+                }
+            }
+        }
+        // Здание меньше ячейки - берём ближайшую к первой вершине
+        if inside.is_empty() {
+            let (fx, fy) = footprint.polygon[0];
+            let x = fx.round().clamp(0.0, w as f64 - 1.0) as usize;
+            let y = fy.round().clamp(0.0, h as f64 - 1.0) as usize;
+            inside.push((x, y, heightmap.get(x, y)));
+        }
+
+        let slope_deg = plane_slope_deg(&inside, square_size);
+        if slope_deg > settings.max_slope_deg {
+            report.steep.push(SteepBuilding {
+                osm_id: footprint.osm_id,
+                slope_deg,
+                lat: footprint.lat_lon.0,
+                lon: footprint.lat_lon.1,
+            });
+            continue;
+        }
+
+        let base = pad_base(heightmap, &inside, &footprint.entrances, settings.base);
+        report.leveled += 1;
+
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let p = (x as f64, y as f64);
+                let distance = if point_in_polygon(p, &footprint.polygon) {
+                    0.0
+                } else {
+                    polygon_distance(p, &footprint.polygon)
+                };
+                let weight = if distance <= margin {
+                    1.0
+                } else if distance <= reach {
+                    1.0 - smoothstep(((distance - margin) / falloff.max(f64::EPSILON)) as f32)
+                } else {
+                    continue;
+                };
+                if weight <= 0.0 {
+                    continue;
+                }
+                Candidate::offer(&mut best[y * w + x], Candidate { weight, distance, height: base });
+            }
+        }
+    }
+
+    for (i, candidate) in best.into_iter().enumerate() {
+        if let Some(c) = candidate {
+            if protected[i] {
+                continue;
+            }
+            heightmap.data[i] += (c.height - heightmap.data[i]) * c.weight;
+            if c.weight >= 1.0 {
+                protected[i] = true;
+            }
+        }
+    }
+
+    println!(
+        "Leveled {} building pads, {} buildings on slopes steeper than {}°",
+        report.leveled,
+        report.steep.len(),
+        settings.max_slope_deg
+    );
+    Ok(report)
+}
+
+// Замкнутые линии с building=* (мультиполигоны-отношения не поддерживаются)
+fn collect_footprints(elements: &[OSMElement], heightmap: &HeightGrid) -> Vec<Footprint> {
+    let mut nodes: HashMap<i64, (f64, f64)> = HashMap::new();
+    let mut entrances: HashSet<i64> = HashSet::new();
+    for element in elements {
+        if element.element_type == "node" {
+            if let (Some(lat), Some(lon)) = (element.lat, element.lon) {
+                nodes.insert(element.id, (lat, lon));
+            }
+            if element.tags.contains_key("entrance") {
+                entrances.insert(element.id);
+            }
+        }
+    }
+
+    let to_grid = |&(lat, lon): &(f64, f64)| {
+        let (mx, my) = heightmap.bounds.project(lat, lon);
+        heightmap.mercator_to_pixel(mx, my)
+    };
+
+    let mut footprints = Vec::new();
+    for element in elements {
+        if element.element_type != "way" || !element.tags.contains_key("building") {
+            continue;
+        }
+        let Some(way_nodes) = &element.nodes else {
+            continue;
+        };
+        let coords: Vec<(f64, f64)> = way_nodes.iter().filter_map(|id| nodes.get(id)).copied().collect();
+        if coords.len() < 3 {
+            continue;
+        }
+        footprints.push(Footprint {
+            osm_id: element.id,
+            polygon: coords.iter().map(to_grid).collect(),
+            entrances: way_nodes
+                .iter()
+                .filter(|id| entrances.contains(id))
+                .filter_map(|id| nodes.get(id))
+                .map(to_grid)
+                .collect(),
+            lat_lon: coords[0],
+        });
+    }
+    footprints
+}
+
+// Диапазон ячеек вокруг контура с запасом reach; None, если контур вне карты
+pub fn footprint_cells(polygon: &[(f64, f64)], reach: f64, w: usize, h: usize) -> Option<(usize, usize, usize, usize)> {
+    let min_x = polygon.iter().map(|p| p.0).fold(f64::INFINITY, f64::min) - reach;
+    let max_x = polygon.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max) + reach;
+    let min_y = polygon.iter().map(|p| p.1).fold(f64::INFINITY, f64::min) - reach;
+    let max_y = polygon.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max) + reach;
+    if max_x < 0.0 || max_y < 0.0 || min_x > w as f64 - 1.0 || min_y > h as f64 - 1.0 {
+        return None;
+    }
+    Some((
+        min_x.floor().max(0.0) as usize,
+        min_y.floor().max(0.0) as usize,
+        max_x.ceil().min(w as f64 - 1.0) as usize,
+        max_y.ceil().min(h as f64 - 1.0) as usize,
+    ))
+}
+
+fn pad_base(heightmap: &HeightGrid, inside: &[(usize, usize, f32)], entrances: &[(f64, f64)], base: PadBase) -> f32 {
+    let mean = inside.iter().map(|c| c.2 as f64).sum::<f64>() / inside.len() as f64;
+    match base {
+        PadBase::Min => inside.iter().map(|c| c.2).fold(f32::INFINITY, f32::min),
+        PadBase::Mean => mean as f32,
+        PadBase::Entrance if entrances.is_empty() => mean as f32,
+        PadBase::Entrance => {
+            let sum: f32 = entrances
+                .iter()
+                .map(|&(fx, fy)| heightmap.sample(fx, fy, crate::terrain::ResampleMethod::Bilinear))
+                .sum();
+            sum / entrances.len() as f32
+        }
+    }
+}
+
+// Уклон плоскости z = a*x + b*y + c, подобранной по МНК к высотам внутри контура
+fn plane_slope_deg(cells: &[(usize, usize, f32)], square_size: f64) -> f64 {
+    if cells.len() < 3 {
+        return 0.0;
+    }
+    let n = cells.len() as f64;
+    let (mx, my, mz) = cells.iter().fold((0.0, 0.0, 0.0), |(sx, sy, sz), &(x, y, z)| {
+        (sx + x as f64 / n, sy + y as f64 / n, sz + z as f64 / n)
+    });
+    let (mut sxx, mut syy, mut sxy, mut sxz, mut syz) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for &(x, y, z) in cells {
+        let (dx, dy, dz) = (x as f64 - mx, y as f64 - my, z as f64 - mz);
+        sxx += dx * dx;
+        syy += dy * dy;
+        sxy += dx * dy;
+        sxz += dx * dz;
+        syz += dy * dz;
+    }
+    let det = sxx * syy - sxy * sxy;
+    if det.abs() < 1e-9 {
+        return 0.0;
+    }
+    let a = (sxz * syy - syz * sxy) / det;
+    let b = (syz * sxx - sxz * sxy) / det;
+    ((a * a + b * b).sqrt() / square_size).atan().to_degrees()
+}
+
+// Чётно-нечётное правило для центра ячейки
+fn point_in_polygon(p: (f64, f64), polygon: &[(f64, f64)]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a.1 > p.1) != (b.1 > p.1) && p.0 < (b.0 - a.0) * (p.1 - a.1) / (b.1 - a.1) + a.0 {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn polygon_distance(p: (f64, f64), polygon: &[(f64, f64)]) -> f64 {
+    let mut best = f64::INFINITY;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        best = best.min(point_segment_distance(p, polygon[j], polygon[i]).0);
+        j = i;
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain;
+
+    const SIZE: usize = 20;
+
+    // Сетка 20x20 м у нулевого меридиана, высота растёт на rise метров на ячейку к востоку
+    fn tilted(rise: f32) -> HeightGrid {
+        HeightGrid::from_fn(SIZE, SIZE, |x, _| 100.0 + rise * x as f32)
+    }
+
+    // Узел OSM в точке сетки (ячейки, дробные)
+    fn node(id: i64, fx: f64, fy: f64, tags: serde_json::Value) -> serde_json::Value {
+        let (lat, lon) = terrain::mercator_to_lat_lng(fx + 0.5, SIZE as f64 - fy - 0.5);
+        serde_json::json!({ "id": id, "type": "node", "lat": lat, "lon": lon, "tags": tags })
+    }
+
+    // Квадратное здание на ячейках 6..=10 с входом в северо-восточном углу
+    fn square_building() -> Vec<OSMElement> {
+        let elements = serde_json::json!([
+            node(1, 5.5, 5.5, serde_json::json!({})),
+            node(2, 10.5, 5.5, serde_json::json!({ "entrance": "main" })),
+            node(3, 10.5, 10.5, serde_json::json!({})),
+            node(4, 5.5, 10.5, serde_json::json!({})),
+            { "id": 10, "type": "way", "nodes": [1, 2, 3, 4, 1], "tags": { "building": "yes" } },
+        ]);
+        serde_json::from_value(elements).unwrap()
+    }
+
+    fn level(grid: &mut HeightGrid, settings: &BuildingPadSettings, margin: f64, protected: &mut [bool]) -> PadReport {
+        level_building_pads(grid, &square_building(), 1.0, settings, margin, protected).unwrap()
+    }
+
+    #[test]
+    fn pad_is_levelled_to_the_mean_height() {
+        let mut grid = tilted(0.1);
+        let mut protected = vec![false; SIZE * SIZE];
+        let report = level(&mut grid, &BuildingPadSettings::default(), 0.0, &mut protected);
+
+        assert_eq!(report.leveled, 1);
+        assert!(report.steep.is_empty());
+        // Средняя по ячейкам 6..=10 - высота средней колонки
+        for x in 6..=10 {
+            assert!((grid.get(x, 8) - 100.8).abs() < 1e-3, "{}", grid.get(x, 8));
+            assert!(protected[8 * SIZE + x]);
+        }
+        assert_eq!(grid.get(19, 8), 101.9);
+        assert_eq!(grid.get(8, 19), 100.8);
+    }
+
+    #[test]
+    fn pad_base_can_follow_the_entrance() {
+        let mut grid = tilted(0.1);
+        let mut protected = vec![false; SIZE * SIZE];
+        let settings = BuildingPadSettings { base: PadBase::Entrance, ..BuildingPadSettings::default() };
+        level(&mut grid, &settings, 0.0, &mut protected);
+        assert!((grid.get(8, 8) - 101.05).abs() < 1e-3, "{}", grid.get(8, 8));
+    }
+
+    #[test]
+    fn steep_sites_are_reported_and_left_alone() {
+        let mut grid = tilted(1.0);
+        let mut protected = vec![false; SIZE * SIZE];
+        let report = level(&mut grid, &BuildingPadSettings::default(), 0.0, &mut protected);
+
+        assert_eq!(report.leveled, 0);
+        assert_eq!(report.steep.len(), 1);
+        assert_eq!(report.steep[0].osm_id, 10);
+        assert!((report.steep[0].slope_deg - 45.0).abs() < 1e-6);
+        assert_eq!(grid.data, tilted(1.0).data);
+    }
+
+    #[test]
+    fn protected_cells_are_kept() {
+        let mut grid = tilted(0.1);
+        let mut protected = vec![false; SIZE * SIZE];
+        protected[8 * SIZE + 6] = true;
+        level(&mut grid, &BuildingPadSettings::default(), 0.0, &mut protected);
+        assert_eq!(grid.get(6, 8), 100.6);
+    }
+
+    #[test]
+    fn buildings_in_the_border_margin_are_skipped() {
+        let mut grid = tilted(0.1);
+        let mut protected = vec![false; SIZE * SIZE];
+        // Первая вершина здания в 6 м от западного края
+        let report = level(&mut grid, &BuildingPadSettings::default(), 7.0, &mut protected);
+        assert_eq!(report.leveled, 0);
+        assert_eq!(grid.data, tilted(0.1).data);
+        assert!(!protected.contains(&true));
+
+        let report = level(&mut grid, &BuildingPadSettings::default(), 5.0, &mut protected);
+        assert_eq!(report.leveled, 1);
+    }
+
+    #[test]
+    fn polygon_helpers() {
+        let square = [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)];
+        assert!(point_in_polygon((2.0, 2.0), &square));
+        assert!(!point_in_polygon((5.0, 2.0), &square));
+        assert_eq!(polygon_distance((6.0, 2.0), &square), 2.0);
+        assert_eq!(footprint_cells(&square, 1.5, 10, 3), Some((0, 0, 6, 2)));
+        assert_eq!(footprint_cells(&square, 1.0, 10, 10).map(|r| r.2), Some(5));
+        assert_eq!(footprint_cells(&[(20.0, 20.0); 3], 1.0, 10, 10), None);
+    }
+}
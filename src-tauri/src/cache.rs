@@ -0,0 +1,278 @@
+// src-tauri/src/cache.rs - Дисковый кэш тайлов и ответов Overpass
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::BoundingBox;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheSettings {
+    pub enabled: bool,
+    // None - системная папка кэша приложения
+    pub directory: Option<String>,
+    // Записи старше этого считаются устаревшими; None - не устаревают
+    pub max_age_hours: Option<u64>,
+    pub max_size_mb: u64,
+    // Только кэш: если данных нет, генерация сразу завершается ошибкой
+    pub offline: bool,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        CacheSettings {
+            enabled: true,
+            directory: None,
+            max_age_hours: Some(24 * 30),
+            max_size_mb: 2048,
+            offline: false,
+        }
+    }
+}
+
+// Разделы кэша
+pub const TILES: &str = "tiles";
+pub const OSM: &str = "osm";
+pub const POINTS: &str = "points";
+
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    root: Option<PathBuf>,
+    max_age: Option<Duration>,
+    max_bytes: u64,
+    offline: bool,
+}
+
+impl DiskCache {
+    pub fn open(settings: &CacheSettings) -> Result<Self, String> {
+        if settings.offline && !settings.enabled {
+            return Err("Offline mode requires the cache to be enabled".to_string());
+        }
+
+        let root = if settings.enabled {
+            let dir = match &settings.directory {
+                Some(dir) => PathBuf::from(dir),
+                None => tauri::api::path::cache_dir()
+                    .ok_or("Cannot determine the cache directory")?
+                    .join("beamng-terrain-generator"),
+            };
+            fs::create_dir_all(&dir)
+                .map_err(|e| format!("Failed to create cache directory {}: {}", dir.display(), e))?;
+            Some(dir)
+        } else {
+            None
+        };
+
+        Ok(DiskCache {
+            root,
+            // Огромные значения из настроек означают "без ограничения", а не переполнение
+            max_age: settings.max_age_hours.map(|h| Duration::from_secs(h.saturating_mul(3600))),
+            max_bytes: settings.max_size_mb.saturating_mul(1024 * 1024),
+            offline: settings.offline,
+        })
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    // Ключ тайла: источник (шаблон URL) + z/x/y
+    pub fn tile_key(source: &str, zoom: u32, x: u32, y: u32) -> String {
+        format!("{}|{}/{}/{}", source, zoom, x, y)
+    }
+
+    pub fn osm_key(query: &str, bbox: &BoundingBox) -> String {
+        format!(
+            "{}|{},{},{},{}",
+            query, bbox.min_lat, bbox.min_lng, bbox.max_lat, bbox.max_lng
+        )
+    }
+
+    // Путь по SHA-256 ключа: <root>/<section>/ab/abcdef...
+    fn entry_path(&self, section: &str, key: &str) -> Option<PathBuf> {
+        let root = self.root.as_ref()?;
+        let hash = format!("{:x}", Sha256::digest(key.as_bytes()));
+        Some(root.join(section).join(&hash[..2]).join(hash))
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<Vec<u8>> {
+        let path = self.entry_path(section, key)?;
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+
+        // В офлайн-режиме устаревшие данные лучше, чем никаких
+        if !self.offline && self.is_expired(modified) {
+            return None;
+        }
+
+        fs::read(&path).ok()
+    }
+
+    pub fn put(&self, section: &str, key: &str, data: &[u8]) -> Result<(), String> {
+        let path = match self.entry_path(section, key) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+        // Пишем во временный файл и переименовываем, чтобы не оставить обрезанную запись
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, &path).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn is_expired(&self, modified: SystemTime) -> bool {
+        match self.max_age {
+            Some(max_age) => modified.elapsed().map(|age| age > max_age).unwrap_or(false),
+            None => false,
+        }
+    }
+
+    // Удаляет устаревшие записи и самые старые, пока кэш не уложится в лимит.
+    // В офлайн-режиме устаревшие записи - единственная копия данных, их держит только лимит размера.
+    pub fn prune(&self) {
+        let root = match &self.root {
+            Some(root) => root,
+            None => return,
+        };
+
+        let mut entries: Vec<(PathBuf, SystemTime, u64)> = Vec::new();
+        for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            if let Ok(meta) = entry.metadata() {
+                let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                entries.push((entry.into_path(), modified, meta.len()));
+            }
+        }
+
+        let mut total: u64 = entries.iter().map(|e| e.2).sum();
+        entries.sort_by_key(|e| e.1);
+
+        let mut removed = 0usize;
+        for (path, modified, size) in entries {
+            let expired = !self.offline && self.is_expired(modified);
+            if total <= self.max_bytes && !expired {
+                continue;
+            }
+            if remove_entry(&path) {
+                total = total.saturating_sub(size);
+                removed += 1;
+            }
+        }
+
+        if removed > 0 {
+            println!("Cache: removed {} entries, {:.1} MB left", removed, total as f64 / 1048576.0);
+        }
+    }
+}
+
+fn remove_entry(path: &Path) -> bool {
+    match fs::remove_file(path) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Failed to remove cache entry {}: {}", path.display(), e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_in(dir: &Path, offline: bool) -> DiskCache {
+        DiskCache::open(&CacheSettings {
+            directory: Some(dir.to_string_lossy().into_owned()),
+            max_age_hours: Some(1),
+            offline,
+            ..CacheSettings::default()
+        })
+        .unwrap()
+    }
+
+    fn set_age(cache: &DiskCache, key: &str, seconds: u64) {
+        let path = cache.entry_path(TILES, key).unwrap();
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(seconds)).unwrap();
+    }
+
+    fn make_stale(cache: &DiskCache, key: &str) {
+        set_age(cache, key, 2 * 3600);
+    }
+
+    #[test]
+    fn offline_prune_keeps_stale_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let online = open_in(dir.path(), false);
+        online.put(TILES, "stale", b"tile").unwrap();
+        make_stale(&online, "stale");
+
+        let offline = open_in(dir.path(), true);
+        offline.prune();
+        assert_eq!(offline.get(TILES, "stale").as_deref(), Some(&b"tile"[..]));
+        assert_eq!(online.get(TILES, "stale"), None);
+
+        online.prune();
+        assert_eq!(offline.get(TILES, "stale"), None);
+    }
+
+    #[test]
+    fn online_get_skips_expired_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = open_in(dir.path(), false);
+        cache.put(TILES, "tile", b"data").unwrap();
+        assert_eq!(cache.get(TILES, "tile").as_deref(), Some(&b"data"[..]));
+
+        make_stale(&cache, "tile");
+        assert_eq!(cache.get(TILES, "tile"), None);
+
+        // Без срока годности запись не устаревает
+        let forever = DiskCache::open(&CacheSettings {
+            directory: Some(dir.path().to_string_lossy().into_owned()),
+            max_age_hours: None,
+            ..CacheSettings::default()
+        })
+        .unwrap();
+        assert_eq!(forever.get(TILES, "tile").as_deref(), Some(&b"data"[..]));
+    }
+
+    #[test]
+    fn prune_removes_oldest_entries_over_the_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = open_in(dir.path(), false);
+        cache.max_bytes = 10;
+        for (key, age) in [("old", 1800), ("middle", 1200), ("new", 60)] {
+            cache.put(TILES, key, b"012345").unwrap();
+            set_age(&cache, key, age);
+        }
+
+        cache.prune();
+        assert_eq!(cache.get(TILES, "old"), None);
+        assert_eq!(cache.get(TILES, "middle"), None);
+        assert!(cache.get(TILES, "new").is_some());
+    }
+
+    #[test]
+    fn huge_limits_do_not_overflow() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::open(&CacheSettings {
+            directory: Some(dir.path().to_string_lossy().into_owned()),
+            max_age_hours: Some(u64::MAX),
+            max_size_mb: u64::MAX,
+            ..CacheSettings::default()
+        })
+        .unwrap();
+        assert_eq!(cache.max_bytes, u64::MAX);
+        cache.put(TILES, "tile", b"data").unwrap();
+        cache.prune();
+        assert!(cache.get(TILES, "tile").is_some());
+    }
+}
@@ -0,0 +1,364 @@
+// src-tauri/src/detail.rs - Мелкий рельеф: фрактальный шум там, где данные высот грубее сетки рельефа
+
+use std::collections::HashMap;
+use std::f64::consts::{SQRT_2, TAU};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::slope_degrees;
+use crate::blend::NO_SOURCE;
+use crate::buildings::footprint_cells;
+use crate::erosion::SplitMix64;
+use crate::filters::gaussian_blur;
+use crate::roads::{check_non_negative, smoothstep};
+use crate::terrain::HeightGrid;
+use crate::water::scanline_mask;
+use crate::OSMElement;
+
+// Ширина плавного перехода амплитуды на границе покрытий (м)
+const LANDCOVER_FEATHER_M: f64 = 10.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetailSettings {
+    pub enabled: bool,
+    // Одинаковый seed и настройки - одинаковый рельеф
+    pub seed: u64,
+    // Высота неровностей относительно их размера: 0.02 - около 0.6 м на волне 30 м
+    pub roughness: f64,
+    // Шаг исходного DEM (м); None - шаг сетки источника. Тайлы бывают мельче самого DEM.
+    pub source_resolution_m: Option<f64>,
+    // Доля амплитуды на ровном месте; на склонах круче steep_slope_deg - полная
+    pub flat_factor: f64,
+    pub steep_slope_deg: f64,
+    // Множители амплитуды по покрытию из OSM: "landuse=farmland", "natural=bare_rock"
+    pub landcover: HashMap<String, f64>,
+    // Множитель там, где покрытие не указано
+    pub default_factor: f64,
+}
+
+impl Default for DetailSettings {
+    fn default() -> Self {
+        let landcover = [
+            ("natural=bare_rock", 3.0),
+            ("natural=scree", 2.5),
+            ("natural=rock", 2.5),
+            ("landuse=quarry", 2.0),
+            ("natural=heath", 1.2),
+            ("natural=scrub", 1.2),
+            ("natural=wood", 1.0),
+            ("landuse=forest", 1.0),
+            ("natural=grassland", 0.7),
+            ("landuse=meadow", 0.6),
+            ("natural=sand", 0.5),
+            ("natural=beach", 0.3),
+            ("natural=wetland", 0.2),
+            ("landuse=orchard", 0.4),
+            ("landuse=vineyard", 0.4),
+            ("landuse=farmland", 0.3),
+            ("landuse=grass", 0.3),
+            ("landuse=residential", 0.15),
+            ("landuse=commercial", 0.1),
+            ("landuse=retail", 0.1),
+            ("landuse=industrial", 0.1),
+            ("natural=water", 0.0),
+        ]
+        .into_iter()
+        .map(|(tag, factor)| (tag.to_string(), factor))
+        .collect();
+        DetailSettings {
+            enabled: true,
+            seed: 1,
+            roughness: 0.02,
+            source_resolution_m: None,
+            flat_factor: 0.3,
+            steep_slope_deg: 35.0,
+            landcover,
+            default_factor: 1.0,
+        }
+    }
+}
+
+// Шаг исходных данных на местности (м) по ячейкам. При смешивании источников
+// у каждой ячейки свой шаг - того источника, что дал основной вклад.
+pub struct SourceResolution {
+    width: usize,
+    height: usize,
+    meters: Vec<f64>,
+}
+
+impl SourceResolution {
+    pub fn uniform(meters: f64) -> Self {
+        SourceResolution {
+            width: 1,
+            height: 1,
+            meters: vec![meters],
+        }
+    }
+
+    // Ячейки без источника получают шаг самого грубого источника
+    pub fn from_source_map(source_map: &[u8], width: usize, height: usize, layer_meters: &[f64]) -> Self {
+        let coarsest = layer_meters.iter().copied().fold(0.0, f64::max);
+        SourceResolution {
+            width,
+            height,
+            meters: source_map
+                .iter()
+                .map(|&s| if s == NO_SOURCE { coarsest } else { layer_meters[s as usize] })
+                .collect(),
+        }
+    }
+
+    // Ближайшая ячейка для ячейки (x, y) сетки grid_w x grid_h с теми же границами
+    fn at(&self, x: usize, y: usize, grid_w: usize, grid_h: usize) -> f64 {
+        let sx = (x * self.width / grid_w).min(self.width - 1);
+        let sy = (y * self.height / grid_h).min(self.height - 1);
+        self.meters[sy * self.width + sx]
+    }
+}
+
+// Добавляет к рельефу октавы шума с длиной волны от шага исходных данных до двух ячеек.
+// Длиннее шага DEM октав нет, а у градиентного шума нулевое среднее, так что крупные
+// формы остаются как в источнике.
+pub fn add_detail(
+    grid: &mut HeightGrid,
+    elements: &[OSMElement],
+    square_size: f64,
+    resolution: &SourceResolution,
+    settings: &DetailSettings,
+) -> Result<(), String> {
+    if !settings.enabled {
+        return Ok(());
+    }
+    check_non_negative(
+        "Detail",
+        &[
+            ("roughness", settings.roughness),
+            ("flat_factor", settings.flat_factor),
+            ("steep_slope_deg", settings.steep_slope_deg),
+            ("default_factor", settings.default_factor),
+        ],
+    )?;
+    if let Some(meters) = settings.source_resolution_m {
+        if !meters.is_finite() || meters <= 0.0 {
+            return Err(format!("Invalid source resolution: {} m", meters));
+        }
+    }
+
+    let (w, h) = (grid.width, grid.height);
+    // Короче двух ячеек волну сетка уже не передаст
+    let shortest = 2.0 * square_size;
+    let source_at = |x: usize, y: usize| settings.source_resolution_m.unwrap_or_else(|| resolution.at(x, y, w, h));
+    let coarsest = (0..h)
+        .step_by(h.div_ceil(64))
+        .flat_map(|y| (0..w).step_by(w.div_ceil(64)).map(move |x| (x, y)))
+        .map(|(x, y)| source_at(x, y))
+        .fold(0.0, f64::max);
+    if coarsest <= shortest {
+        println!("Micro-detail skipped: source data ({:.1} m) is as fine as the terrain", coarsest);
+        return Ok(());
+    }
+
+    let factors = landcover_factors(grid, elements, settings, square_size);
+    let slopes = slope_degrees(grid, square_size);
+
+    let mut max_added = 0.0f32;
+    for y in 0..h {
+        for x in 0..w {
+            let i = y * w + x;
+            let source = source_at(x, y);
+            if source <= shortest || factors[i] <= 0.0 {
+                continue;
+            }
+            let steepness = smoothstep((slopes[i] / settings.steep_slope_deg.max(f64::EPSILON)) as f32) as f64;
+            let scale = factors[i] as f64 * (settings.flat_factor + (1.0 - settings.flat_factor) * steepness);
+
+            // Координаты на местности (м), чтобы рисунок не зависел от square_size
+            let (px, py) = (x as f64 * square_size, y as f64 * square_size);
+            let mut noise = 0.0;
+            let mut wavelength = source;
+            let mut octave = 0;
+            while wavelength >= shortest {
+                let seed = settings.seed.wrapping_add(octave);
+                noise += settings.roughness * wavelength * gradient_noise(px / wavelength, py / wavelength, seed);
+                wavelength /= 2.0;
+                octave += 1;
+            }
+
+            let added = (noise * scale) as f32;
+            grid.data[i] += added;
+            max_added = max_added.max(added.abs());
+        }
+    }
+    println!(
+        "Micro-detail added from {:.1} m source data: max {:.2} m (seed {})",
+        coarsest, max_added, settings.seed
+    );
+    Ok(())
+}
+
+// Множитель амплитуды по ячейкам из замкнутых линий landuse/natural. Мелкие контуры
+// ложатся поверх крупных, границы размыты на LANDCOVER_FEATHER_M.
+fn landcover_factors(grid: &HeightGrid, elements: &[OSMElement], settings: &DetailSettings, square_size: f64) -> Vec<f32> {
+    let (w, h) = (grid.width, grid.height);
+    let nodes: HashMap<i64, (f64, f64)> = elements
+        .iter()
+        .filter(|e| e.element_type == "node")
+        .filter_map(|e| Some((e.id, (e.lat?, e.lon?))))
+        .collect();
+
+    let mut areas = Vec::new();
+    for element in elements {
+        if element.element_type != "way" {
+            continue;
+        }
+        let factor = ["natural", "landuse"].iter().find_map(|key| {
+            let value = element.tags.get(*key)?;
+            settings.landcover.get(&format!("{}={}", key, value))
+        });
+        let (Some(&factor), Some(way_nodes)) = (factor, &element.nodes) else {
+            continue;
+        };
+        if way_nodes.len() < 4 || way_nodes.first() != way_nodes.last() {
+            continue;
+        }
+        let polygon: Vec<(f64, f64)> = way_nodes
+            .iter()
+            .filter_map(|id| nodes.get(id))
+            .map(|&(lat, lon)| {
+                let (mx, my) = grid.bounds.project(lat, lon);
+                grid.mercator_to_pixel(mx, my)
+            })
+            .collect();
+        if polygon.len() < 4 {
+            continue;
+        }
+        if let Some(cells) = footprint_cells(&polygon, 0.0, w, h) {
+            areas.push((polygon, cells, factor as f32));
+        }
+    }
+    areas.sort_by_key(|(_, (x0, y0, x1, y1), _)| std::cmp::Reverse((x1 - x0 + 1) * (y1 - y0 + 1)));
+
+    let mut factors = vec![settings.default_factor as f32; w * h];
+    for (polygon, (x0, y0, x1, y1), factor) in &areas {
+        let (sw, sh) = (x1 - x0 + 1, y1 - y0 + 1);
+        let inside = scanline_mask(polygon, *x0, *y0, sw, sh);
+        for j in (0..sw * sh).filter(|&j| inside[j]) {
+            factors[(y0 + j / sw) * w + x0 + j % sw] = *factor;
+        }
+    }
+    if areas.is_empty() {
+        return factors;
+    }
+    gaussian_blur(&factors, w, h, LANDCOVER_FEATHER_M / 2.0 / square_size)
+}
+
+// Градиентный шум Перлина, значения примерно в [-1, 1]
+fn gradient_noise(x: f64, y: f64, seed: u64) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (ix, iy) = (x0 as i64, y0 as i64);
+    let corner = |dx: i64, dy: i64| {
+        let hash = SplitMix64(
+            seed ^ ((ix + dx) as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+                ^ ((iy + dy) as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F),
+        )
+        .next_u64();
+        let angle = (hash >> 11) as f64 / (1u64 << 53) as f64 * TAU;
+        angle.cos() * (fx - dx as f64) + angle.sin() * (fy - dy as f64)
+    };
+    let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let (u, v) = (fade(fx), fade(fy));
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * u;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * u;
+    (top + (bottom - top) * v) * SQRT_2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain;
+
+    const SIZE: usize = 64;
+
+    fn flat() -> HeightGrid {
+        HeightGrid::from_fn(SIZE, SIZE, |_, _| 0.0)
+    }
+
+    fn settings(source_m: f64) -> DetailSettings {
+        DetailSettings {
+            source_resolution_m: Some(source_m),
+            flat_factor: 1.0,
+            ..DetailSettings::default()
+        }
+    }
+
+    #[test]
+    fn noise_is_zero_on_the_lattice_and_bounded() {
+        assert_eq!(gradient_noise(3.0, -2.0, 5), 0.0);
+        let mut max = 0.0f64;
+        for i in 0..400 {
+            let (x, y) = (i as f64 * 0.173, i as f64 * 0.291);
+            assert_eq!(gradient_noise(x, y, 5), gradient_noise(x, y, 5));
+            max = max.max(gradient_noise(x, y, 5).abs());
+        }
+        assert!(max > 0.1 && max <= 1.0 + 1e-9, "{}", max);
+    }
+
+    #[test]
+    fn detail_follows_seed_and_keeps_large_forms() {
+        let mut a = flat();
+        let mut b = flat();
+        let mut c = flat();
+        add_detail(&mut a, &[], 1.0, &SourceResolution::uniform(1.0), &settings(16.0)).unwrap();
+        add_detail(&mut b, &[], 1.0, &SourceResolution::uniform(1.0), &settings(16.0)).unwrap();
+        let other_seed = DetailSettings { seed: 2, ..settings(16.0) };
+        add_detail(&mut c, &[], 1.0, &SourceResolution::uniform(1.0), &other_seed).unwrap();
+        assert_eq!(a.data, b.data);
+        assert_ne!(a.data, c.data);
+
+        // Волны от 16 до 2 м при roughness 0.02 - не выше полуметра, в среднем около нуля
+        let (min, max) = a.min_max();
+        assert!(max - min > 0.05 && max.abs() < 0.5 && min.abs() < 0.5, "{} {}", min, max);
+        let mean = a.data.iter().sum::<f32>() / a.data.len() as f32;
+        assert!(mean.abs() < 0.05, "{}", mean);
+    }
+
+    #[test]
+    fn fine_source_data_gets_no_detail() {
+        let mut grid = flat();
+        add_detail(&mut grid, &[], 1.0, &SourceResolution::uniform(2.0), &DetailSettings::default()).unwrap();
+        assert!(grid.data.iter().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn water_gets_no_detail() {
+        // Озеро на всю карту с запасом
+        let corner = |id: i64, x: f64, y: f64| {
+            let (lat, lon) = terrain::mercator_to_lat_lng(x, y);
+            serde_json::json!({ "id": id, "type": "node", "lat": lat, "lon": lon })
+        };
+        let elements: Vec<OSMElement> = serde_json::from_value(serde_json::json!([
+            corner(1, -20.0, -20.0),
+            corner(2, 84.0, -20.0),
+            corner(3, 84.0, 84.0),
+            corner(4, -20.0, 84.0),
+            { "id": 10, "type": "way", "nodes": [1, 2, 3, 4, 1], "tags": { "natural": "water" } },
+        ]))
+        .unwrap();
+
+        let mut grid = flat();
+        add_detail(&mut grid, &elements, 1.0, &SourceResolution::uniform(1.0), &settings(16.0)).unwrap();
+        assert!(grid.data.iter().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn source_map_resolution_per_cell() {
+        let map = [0, 1, NO_SOURCE, 0];
+        let resolution = SourceResolution::from_source_map(&map, 2, 2, &[1.0, 30.0]);
+        assert_eq!(resolution.at(0, 0, 4, 4), 1.0);
+        assert_eq!(resolution.at(3, 0, 4, 4), 30.0);
+        assert_eq!(resolution.at(0, 3, 4, 4), 30.0);
+        assert_eq!(resolution.at(3, 3, 4, 4), 1.0);
+    }
+}
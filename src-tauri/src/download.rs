@@ -0,0 +1,224 @@
+// src-tauri/src/download.rs - HTTP загрузка с повторами и экспоненциальной задержкой
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadSettings {
+    // Сколько тайлов качается одновременно
+    pub concurrency: usize,
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub timeout_secs: u64,
+}
+
+impl Default for DownloadSettings {
+    fn default() -> Self {
+        DownloadSettings {
+            concurrency: 8,
+            max_retries: 4,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            timeout_secs: 30,
+        }
+    }
+}
+
+impl DownloadSettings {
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt);
+        Duration::from_millis(self.initial_backoff_ms.saturating_mul(factor).min(self.max_backoff_ms))
+    }
+}
+
+// Выполняет запрос, повторяя его при таймаутах, ошибках соединения, 5xx и 429.
+// Остальные ответы (например 404) сразу возвращаются как ошибка.
+pub async fn fetch_with_retry<F>(build_request: F, settings: &DownloadSettings) -> Result<Vec<u8>, String>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    let mut attempt = 0;
+    loop {
+        let (error, retry_after) = match build_request()
+            .timeout(Duration::from_secs(settings.timeout_secs))
+            .send()
+            .await
+        {
+            Ok(response) if response.status().is_success() => match response.bytes().await {
+                Ok(bytes) => return Ok(bytes.to_vec()),
+                Err(e) => (format!("failed to read response: {}", e), None),
+            },
+            Ok(response) => {
+                let status = response.status();
+                if !status.is_server_error() && status != StatusCode::TOO_MANY_REQUESTS {
+                    return Err(format!("HTTP {}", status));
+                }
+                (format!("HTTP {}", status), retry_after(&response))
+            }
+            Err(e) if e.is_timeout() || e.is_connect() || e.is_request() || e.is_body() => {
+                (e.to_string(), None)
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+
+        if attempt >= settings.max_retries {
+            return Err(format!("{} (gave up after {} retries)", error, attempt));
+        }
+
+        // Retry-After от сервера тоже ограничен max_backoff_ms
+        let delay = retry_after
+            .map(|d| d.min(Duration::from_millis(settings.max_backoff_ms)))
+            .unwrap_or_else(|| settings.backoff(attempt));
+        eprintln!("Request failed: {}, retrying in {:.1}s", error, delay.as_secs_f64());
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+// Пробует зеркала по порядку, пока одно из них не ответит
+pub async fn fetch_from_mirrors<F>(urls: &[String], build_request: F, settings: &DownloadSettings) -> Result<Vec<u8>, String>
+where
+    F: Fn(&str) -> reqwest::RequestBuilder,
+{
+    let mut errors = Vec::new();
+    for url in urls {
+        match fetch_with_retry(|| build_request(url), settings).await {
+            Ok(bytes) => return Ok(bytes),
+            Err(e) => {
+                if urls.len() > 1 {
+                    eprintln!("Mirror {} failed: {}", url, e);
+                }
+                errors.push(format!("{}: {}", url, e));
+            }
+        }
+    }
+    if errors.is_empty() {
+        return Err("No URLs configured".to_string());
+    }
+    Err(errors.join("; "))
+}
+
+// Пользовательские заголовки из настроек (например Authorization для своего сервера)
+pub fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap, String> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("Invalid header name '{}'", name))?;
+        let value = HeaderValue::from_str(value).map_err(|_| format!("Invalid value for header '{}'", name))?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+// Retry-After в секундах (формат с датой не поддерживаем)
+pub fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn quick() -> DownloadSettings {
+        DownloadSettings {
+            max_retries: 2,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 5,
+            timeout_secs: 5,
+            ..DownloadSettings::default()
+        }
+    }
+
+    // Локальный сервер: на каждое соединение отдаёт следующий ответ из списка
+    async fn serve(responses: Vec<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{}/tile.png", address)
+    }
+
+    const UNAVAILABLE: &str = "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const NOT_FOUND: &str = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const RATE_LIMITED: &str =
+        "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 86400\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\ntile";
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let settings = DownloadSettings::default();
+        assert_eq!(settings.backoff(0), Duration::from_millis(500));
+        assert_eq!(settings.backoff(2), Duration::from_millis(2000));
+        assert_eq!(settings.backoff(10), Duration::from_millis(30_000));
+        assert_eq!(settings.backoff(100), Duration::from_millis(30_000));
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried() {
+        let url = serve(vec![UNAVAILABLE, UNAVAILABLE, OK]).await;
+        let client = reqwest::Client::new();
+        let bytes = fetch_with_retry(|| client.get(&url), &quick()).await.unwrap();
+        assert_eq!(bytes, b"tile");
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let url = serve(vec![NOT_FOUND, OK]).await;
+        let client = reqwest::Client::new();
+        let error = fetch_with_retry(|| client.get(&url), &quick()).await.unwrap_err();
+        assert!(error.contains("404"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_capped_by_max_backoff() {
+        let url = serve(vec![RATE_LIMITED, OK]).await;
+        let client = reqwest::Client::new();
+        let settings = quick();
+        let fetch = fetch_with_retry(|| client.get(&url), &settings);
+        let bytes = tokio::time::timeout(Duration::from_secs(5), fetch).await.expect("waited for Retry-After");
+        assert_eq!(bytes.unwrap(), b"tile");
+    }
+
+    #[tokio::test]
+    async fn next_mirror_is_tried() {
+        let broken = serve(vec![NOT_FOUND]).await;
+        let working = serve(vec![OK]).await;
+        let client = reqwest::Client::new();
+        let bytes = fetch_from_mirrors(&[broken, working], |url| client.get(url), &quick()).await.unwrap();
+        assert_eq!(bytes, b"tile");
+
+        assert!(fetch_from_mirrors(&[], |url| client.get(url), &quick()).await.is_err());
+    }
+
+    #[test]
+    fn invalid_headers_are_rejected() {
+        let headers = HashMap::from([("Authorization".to_string(), "Bearer abc".to_string())]);
+        assert_eq!(header_map(&headers).unwrap()["authorization"], "Bearer abc");
+
+        let headers = HashMap::from([("Bad Header".to_string(), "x".to_string())]);
+        assert!(header_map(&headers).is_err());
+        let headers = HashMap::from([("X-Token".to_string(), "line\nbreak".to_string())]);
+        assert!(header_map(&headers).is_err());
+    }
+}
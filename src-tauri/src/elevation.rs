@@ -0,0 +1,376 @@
+// src-tauri/src/elevation.rs - Источники данных о высотах
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::ascii_grid::AsciiGridSource;
+use crate::cache::{self, DiskCache};
+use crate::download::{self, DownloadSettings};
+use crate::geotiff::GeoTiffSource;
+use crate::hgt::HgtSource;
+use crate::lidar::{LidarSettings, LidarSource};
+use crate::opentopodata::{OpenTopoDataSettings, OpenTopoDataSource};
+use crate::terrain::{self, HeightGrid, TerrainTile, TileEncoding, TileRange};
+use crate::BoundingBox;
+
+// Общие параметры загрузки для всех источников
+pub struct FetchContext {
+    pub client: reqwest::Client,
+    // Zoom для тайловых источников (см. select_terrain_zoom)
+    pub zoom: u32,
+    // Нужное разрешение на местности; источникам мельче брать нет смысла
+    pub meters_per_pixel: f64,
+    pub cache: DiskCache,
+    pub download: DownloadSettings,
+}
+
+// Результат источника: сетка высот и то, чего получить не удалось
+pub struct ElevationData {
+    pub grid: HeightGrid,
+    // Тайлы (z/x/y или имена файлов), которых нет в сетке
+    pub missing_tiles: Vec<String>,
+}
+
+impl From<HeightGrid> for ElevationData {
+    fn from(grid: HeightGrid) -> Self {
+        ElevationData {
+            grid,
+            missing_tiles: Vec::new(),
+        }
+    }
+}
+
+// Любой источник высот возвращает сетку с границами в Web Mercator,
+// дальше она идёт в общий пайплайн process_terrain_data
+#[async_trait]
+pub trait ElevationSource: Send + Sync {
+    fn name(&self) -> String;
+
+    fn is_tiled(&self) -> bool {
+        false
+    }
+
+    async fn fetch(&self, bbox: &BoundingBox, ctx: &FetchContext) -> Result<ElevationData, String>;
+}
+
+// Выбор источника из настроек генерации
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ElevationSourceConfig {
+    #[default]
+    Terrarium,
+    TerrainRgb { access_token: String },
+    // Свой тайловый сервер: шаблоны {z}/{x}/{y} по порядку (основной и зеркала)
+    Tiles {
+        url_templates: Vec<String>,
+        encoding: TileEncoding,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    OpenTopoData(OpenTopoDataSettings),
+    GeoTiff { path: String, epsg: Option<u32> },
+    // Папка с тайлами SRTM (.hgt), нужные тайлы подбираются по bbox
+    Hgt { directory: String },
+    AsciiGrid { path: String, epsg: Option<u32> },
+    // Облака точек LAS/LAZ, DEM строится по точкам земли
+    Lidar(LidarSettings),
+}
+
+impl ElevationSourceConfig {
+    pub fn build(&self) -> Result<Box<dyn ElevationSource>, String> {
+        Ok(match self {
+            ElevationSourceConfig::Terrarium => Box::new(TileSource::terrarium()),
+            ElevationSourceConfig::TerrainRgb { access_token } => {
+                Box::new(TileSource::terrain_rgb(access_token))
+            }
+            ElevationSourceConfig::Tiles { url_templates, encoding, headers } => {
+                Box::new(TileSource::custom(url_templates, *encoding, headers)?)
+            }
+            ElevationSourceConfig::OpenTopoData(settings) => Box::new(OpenTopoDataSource {
+                settings: settings.clone(),
+            }),
+            ElevationSourceConfig::GeoTiff { path, epsg } => Box::new(GeoTiffSource {
+                path: PathBuf::from(path),
+                epsg: *epsg,
+            }),
+            ElevationSourceConfig::Hgt { directory } => Box::new(HgtSource {
+                directory: PathBuf::from(directory),
+            }),
+            ElevationSourceConfig::AsciiGrid { path, epsg } => Box::new(AsciiGridSource {
+                path: PathBuf::from(path),
+                epsg: *epsg,
+            }),
+            ElevationSourceConfig::Lidar(settings) => Box::new(LidarSource {
+                settings: settings.clone(),
+            }),
+        })
+    }
+}
+
+// Terrarium format: https://registry.opendata.aws/terrain-tiles/
+const TERRARIUM_URL: &str = "https://s3.amazonaws.com/elevation-tiles-prod/terrarium/{z}/{x}/{y}.png";
+const TERRAIN_RGB_URL: &str =
+    "https://api.mapbox.com/v4/mapbox.terrain-rgb/{z}/{x}/{y}.pngraw?access_token={token}";
+
+pub struct TileSource {
+    name: String,
+    // Первый шаблон основной, остальные - зеркала на случай его отказа
+    url_templates: Vec<String>,
+    headers: HeaderMap,
+    encoding: TileEncoding,
+}
+
+impl TileSource {
+    pub fn terrarium() -> Self {
+        TileSource {
+            name: "AWS Terrarium".to_string(),
+            url_templates: vec![TERRARIUM_URL.to_string()],
+            headers: HeaderMap::new(),
+            encoding: TileEncoding::Terrarium,
+        }
+    }
+
+    pub fn terrain_rgb(access_token: &str) -> Self {
+        TileSource {
+            name: "Mapbox Terrain-RGB".to_string(),
+            url_templates: vec![TERRAIN_RGB_URL.replace("{token}", access_token)],
+            headers: HeaderMap::new(),
+            encoding: TileEncoding::TerrainRgb,
+        }
+    }
+
+    pub fn custom(
+        url_templates: &[String],
+        encoding: TileEncoding,
+        headers: &HashMap<String, String>,
+    ) -> Result<Self, String> {
+        let primary = url_templates.first().ok_or("No tile URL templates configured")?;
+        for template in url_templates {
+            if !["{z}", "{x}", "{y}"].iter().all(|p| template.contains(p)) {
+                return Err(format!("Tile URL template must contain {{z}}, {{x}} and {{y}}: {}", template));
+            }
+        }
+
+        // Имя источника - хост основного сервера
+        let host = primary
+            .split("://")
+            .nth(1)
+            .and_then(|rest| rest.split('/').next())
+            .unwrap_or(primary);
+
+        Ok(TileSource {
+            name: format!("Tiles ({})", host),
+            url_templates: url_templates.to_vec(),
+            headers: download::header_map(headers)?,
+            encoding,
+        })
+    }
+
+    // Ключ кэша не зависит от токена доступа в query-строке и от зеркала,
+    // с которого тайл в итоге скачан
+    fn cache_key(&self, zoom: u32, x: u32, y: u32) -> String {
+        let x = wrap_tile_x(zoom, x);
+        let source = self.url_templates[0].split('?').next().unwrap_or_default();
+        DiskCache::tile_key(source, zoom, x, y)
+    }
+
+    fn tile_urls(&self, zoom: u32, x: u32, y: u32) -> Vec<String> {
+        let x = wrap_tile_x(zoom, x);
+        self.url_templates
+            .iter()
+            .map(|template| {
+                template
+                    .replace("{z}", &zoom.to_string())
+                    .replace("{x}", &x.to_string())
+                    .replace("{y}", &y.to_string())
+            })
+            .collect()
+    }
+
+    // Качает тайлы параллельно (не больше download.concurrency одновременно).
+    // Тайлы, которые не удалось скачать после всех повторов, в результат не попадают.
+    async fn download_tiles(
+        &self,
+        tiles: &[(u32, u32)],
+        ctx: &FetchContext,
+    ) -> Result<(Vec<TerrainTile>, Vec<(u32, u32)>), String> {
+        let zoom = ctx.zoom;
+        let mut terrain_tiles = Vec::new();
+        let mut to_download = Vec::new();
+
+        for &(tile_x, tile_y) in tiles {
+            let key = self.cache_key(zoom, tile_x, tile_y);
+            match ctx.cache.get(cache::TILES, &key) {
+                Some(data) => terrain_tiles.push(TerrainTile { zoom, x: tile_x, y: tile_y, data }),
+                None => to_download.push((tile_x, tile_y, key)),
+            }
+        }
+
+        if !to_download.is_empty() && ctx.cache.is_offline() {
+            let (x, y, _) = &to_download[0];
+            return Err(format!(
+                "{} of {} tiles are not in the cache (offline mode), e.g. {}/{}/{}",
+                to_download.len(),
+                tiles.len(),
+                zoom,
+                x,
+                y
+            ));
+        }
+
+        println!(
+            "Downloading {} terrain tiles from {} ({} cached)...",
+            to_download.len(),
+            self.name,
+            terrain_tiles.len()
+        );
+
+        let semaphore = Arc::new(Semaphore::new(ctx.download.concurrency.max(1)));
+        let mut tasks = JoinSet::new();
+        for (tile_x, tile_y, key) in to_download {
+            let semaphore = semaphore.clone();
+            let client = ctx.client.clone();
+            let cache = ctx.cache.clone();
+            let settings = ctx.download.clone();
+            let headers = self.headers.clone();
+            let urls = self.tile_urls(zoom, tile_x, tile_y);
+
+            tasks.spawn(async move {
+                // Семафор не закрывается, так что acquire не падает
+                let _permit = semaphore.acquire_owned().await;
+                let result = download::fetch_from_mirrors(&urls, |url| client.get(url).headers(headers.clone()), &settings).await;
+                if let Ok(bytes) = &result {
+                    if let Err(e) = cache.put(cache::TILES, &key, bytes) {
+                        eprintln!("Failed to cache tile {}/{}/{}: {}", zoom, tile_x, tile_y, e);
+                    }
+                }
+                (tile_x, tile_y, result)
+            });
+        }
+
+        let mut missing = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            let (tile_x, tile_y, result) = joined.map_err(|e| format!("Download task failed: {}", e))?;
+            match result {
+                Ok(data) => {
+                    println!("✓ Downloaded tile {}/{}/{}", zoom, tile_x, tile_y);
+                    terrain_tiles.push(TerrainTile { zoom, x: tile_x, y: tile_y, data });
+                }
+                Err(e) => {
+                    eprintln!("Failed to download tile {}/{}/{}: {}", zoom, tile_x, tile_y, e);
+                    missing.push((tile_x, tile_y));
+                }
+            }
+        }
+
+        Ok((terrain_tiles, missing))
+    }
+}
+
+// Столбцы за антимеридианом (x >= 2^zoom) - это те же тайлы с начала строки
+fn wrap_tile_x(zoom: u32, x: u32) -> u32 {
+    x % (1 << zoom)
+}
+
+#[async_trait]
+impl ElevationSource for TileSource {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn is_tiled(&self) -> bool {
+        true
+    }
+
+    async fn fetch(&self, bbox: &BoundingBox, ctx: &FetchContext) -> Result<ElevationData, String> {
+        let tiles = crate::calculate_tiles(bbox, ctx.zoom);
+        let range = TileRange::from_tiles(ctx.zoom, &tiles).ok_or("No terrain tiles cover the selected area")?;
+
+        let (downloaded, mut missing) = self
+            .download_tiles(&tiles, ctx)
+            .await
+            .map_err(|e| format!("Failed to fetch {} tiles: {}", self.name, e))?;
+
+        if downloaded.is_empty() {
+            return Err(format!("Failed to fetch {} tiles: none of {} tiles could be downloaded", self.name, tiles.len()));
+        }
+        if !missing.is_empty() {
+            eprintln!(
+                "Warning: {} of {} tiles from {} are missing",
+                missing.len(),
+                tiles.len(),
+                self.name
+            );
+        }
+
+        let grid = terrain::mosaic_tiles(&downloaded, range, self.encoding)?;
+        missing.sort_unstable();
+        Ok(ElevationData {
+            grid,
+            missing_tiles: missing
+                .into_iter()
+                .map(|(x, y)| format!("{}/{}/{}", ctx.zoom, wrap_tile_x(ctx.zoom, x), y))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mirrors() -> TileSource {
+        let templates = [
+            "https://tiles.example.com/dem/{z}/{x}/{y}.png?key=secret".to_string(),
+            "https://mirror.example.org/{z}/{y}/{x}.png".to_string(),
+        ];
+        TileSource::custom(&templates, TileEncoding::TerrainRgb, &HashMap::new()).unwrap()
+    }
+
+    #[test]
+    fn urls_are_built_for_every_mirror() {
+        let source = mirrors();
+        assert_eq!(source.name(), "Tiles (tiles.example.com)");
+        assert_eq!(
+            source.tile_urls(3, 9, 2),
+            [
+                "https://tiles.example.com/dem/3/1/2.png?key=secret",
+                "https://mirror.example.org/3/2/1.png",
+            ]
+        );
+    }
+
+    #[test]
+    fn cache_key_ignores_query_and_wraps_columns() {
+        let source = mirrors();
+        assert_eq!(source.cache_key(3, 9, 2), "https://tiles.example.com/dem/{z}/{x}/{y}.png|3/1/2");
+        assert_eq!(source.cache_key(3, 1, 2), source.cache_key(3, 9, 2));
+    }
+
+    #[test]
+    fn templates_need_all_placeholders() {
+        let templates = ["https://tiles.example.com/{z}/{x}.png".to_string()];
+        assert!(TileSource::custom(&templates, TileEncoding::Terrarium, &HashMap::new()).is_err());
+        assert!(TileSource::custom(&[], TileEncoding::Terrarium, &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn config_is_read_from_settings_json() {
+        let config: ElevationSourceConfig = serde_json::from_str(
+            r#"{ "type": "tiles", "url_templates": ["https://a/{z}/{x}/{y}.png"], "encoding": "terrarium" }"#,
+        )
+        .unwrap();
+        let source = config.build().unwrap();
+        assert!(source.is_tiled());
+
+        let config: ElevationSourceConfig = serde_json::from_str(r#"{ "type": "hgt", "directory": "/srtm" }"#).unwrap();
+        assert!(!config.build().unwrap().is_tiled());
+    }
+}
@@ -0,0 +1,400 @@
+// src-tauri/src/erosion.rs - Эрозия рельефа: капли воды (гидравлическая) и осыпание склонов (термическая)
+
+use serde::{Deserialize, Serialize};
+
+use crate::roads::check_non_negative;
+use crate::terrain::HeightGrid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErosionSettings {
+    pub enabled: bool,
+    // Одинаковый seed и настройки - одинаковый результат
+    pub seed: u64,
+    // Бюджет гидравлической эрозии: число капель на всю карту
+    pub droplets: usize,
+    // Максимум шагов одной капли
+    pub droplet_lifetime: usize,
+    // Доля прежнего направления капли (0 - строго по уклону)
+    pub inertia: f32,
+    // Сколько осадка несёт капля относительно перепада, скорости и объёма воды
+    pub sediment_capacity: f32,
+    pub min_sediment_capacity: f32,
+    pub erode_speed: f32,
+    pub deposit_speed: f32,
+    pub evaporate_speed: f32,
+    pub gravity: f32,
+    // Радиус, с которого капля забирает грунт (м)
+    pub erosion_radius_m: f64,
+    // Бюджет термической эрозии: проходов по всей карте
+    pub thermal_iterations: usize,
+    // Склоны круче этого осыпаются
+    pub talus_angle_deg: f64,
+    // Доля избытка над углом откоса, переносимая за проход
+    pub thermal_rate: f32,
+}
+
+impl Default for ErosionSettings {
+    fn default() -> Self {
+        ErosionSettings {
+            enabled: false,
+            seed: 1,
+            droplets: 200_000,
+            droplet_lifetime: 30,
+            inertia: 0.05,
+            sediment_capacity: 4.0,
+            min_sediment_capacity: 0.01,
+            erode_speed: 0.3,
+            deposit_speed: 0.3,
+            evaporate_speed: 0.01,
+            gravity: 4.0,
+            erosion_radius_m: 6.0,
+            thermal_iterations: 20,
+            talus_angle_deg: 35.0,
+            thermal_rate: 0.5,
+        }
+    }
+}
+
+// SplitMix64: свой генератор, чтобы результат не зависел от версий внешних крейтов
+pub struct SplitMix64(pub u64);
+
+impl SplitMix64 {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Равномерно в [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Эрозия всей карты. Ячейки с locked = true (полотно дорог, площадки, вода) не меняются,
+// капли через них проходят, но грунт там не берут и не оставляют.
+pub fn erode(grid: &mut HeightGrid, settings: &ErosionSettings, square_size: f64, locked: &[bool]) -> Result<(), String> {
+    if !settings.enabled {
+        return Ok(());
+    }
+    check_non_negative(
+        "Erosion",
+        &[
+            ("sediment_capacity", settings.sediment_capacity as f64),
+            ("min_sediment_capacity", settings.min_sediment_capacity as f64),
+            ("gravity", settings.gravity as f64),
+            ("erosion_radius_m", settings.erosion_radius_m),
+            ("thermal_rate", settings.thermal_rate as f64),
+        ],
+    )?;
+    for (name, value) in [
+        ("inertia", settings.inertia),
+        ("erode_speed", settings.erode_speed),
+        ("deposit_speed", settings.deposit_speed),
+        ("evaporate_speed", settings.evaporate_speed),
+    ] {
+        if !(0.0..=1.0).contains(&value) {
+            return Err(format!("Erosion setting {} must be between 0 and 1, got {}", name, value));
+        }
+    }
+    if !(0.0..90.0).contains(&settings.talus_angle_deg) {
+        return Err(format!("Talus angle must be from 0 to 90 degrees, got {}", settings.talus_angle_deg));
+    }
+
+    // Моделируем в высотах, выраженных в ячейках: тогда уклоны - настоящие уклоны
+    // и параметры не зависят от square_size
+    let scale = square_size as f32;
+    let mut heights: Vec<f32> = grid.data.iter().map(|&v| v / scale).collect();
+    let (w, h) = (grid.width, grid.height);
+
+    hydraulic(&mut heights, w, h, settings, settings.erosion_radius_m / square_size, locked);
+    thermal(&mut heights, w, h, settings, locked);
+
+    let mut max_change = 0.0f32;
+    for (i, value) in grid.data.iter_mut().enumerate() {
+        // Закрытые ячейки не пересчитываем обратно, чтобы не набежала ошибка округления
+        if locked[i] {
+            continue;
+        }
+        let eroded = heights[i] * scale;
+        max_change = max_change.max((eroded - *value).abs());
+        *value = eroded;
+    }
+    println!(
+        "Erosion: {} droplets, {} thermal passes, max change {:.2} m (seed {})",
+        settings.droplets, settings.thermal_iterations, max_change, settings.seed
+    );
+    Ok(())
+}
+
+// Кисть эрозии: смещения в пределах радиуса и нормированные веса (больше у центра).
+// Кисть в одну ячейку роет колодцы, поэтому радиус не меньше двух ячеек.
+fn erosion_brush(radius: f64) -> Vec<(isize, isize, f32)> {
+    let r = radius.max(2.0);
+    let reach = r.ceil() as isize;
+    let mut brush = Vec::new();
+    for dy in -reach..=reach {
+        for dx in -reach..=reach {
+            let d = ((dx * dx + dy * dy) as f64).sqrt();
+            if d < r {
+                brush.push((dx, dy, (r - d) as f32));
+            }
+        }
+    }
+    let total: f32 = brush.iter().map(|b| b.2).sum();
+    brush.iter_mut().for_each(|b| b.2 /= total);
+    brush
+}
+
+// Высота и градиент в дробной точке по четырём соседним ячейкам
+fn height_and_gradient(heights: &[f32], w: usize, x: f32, y: f32) -> (f32, f32, f32) {
+    let (cx, cy) = (x as usize, y as usize);
+    let (u, v) = (x - cx as f32, y - cy as f32);
+    let i = cy * w + cx;
+    let (nw, ne, sw, se) = (heights[i], heights[i + 1], heights[i + w], heights[i + w + 1]);
+    let gx = (ne - nw) * (1.0 - v) + (se - sw) * v;
+    let gy = (sw - nw) * (1.0 - u) + (se - ne) * u;
+    let height = nw * (1.0 - u) * (1.0 - v) + ne * u * (1.0 - v) + sw * (1.0 - u) * v + se * u * v;
+    (height, gx, gy)
+}
+
+fn hydraulic(heights: &mut [f32], w: usize, h: usize, s: &ErosionSettings, radius: f64, locked: &[bool]) {
+    let brush = erosion_brush(radius);
+    let mut rng = SplitMix64(s.seed);
+    let (max_x, max_y) = ((w - 1) as f32, (h - 1) as f32);
+
+    for _ in 0..s.droplets {
+        let mut x = (rng.next_f64() * (w - 1) as f64) as f32;
+        let mut y = (rng.next_f64() * (h - 1) as f64) as f32;
+        let (mut dir_x, mut dir_y) = (0.0f32, 0.0f32);
+        let (mut speed, mut water, mut sediment) = (1.0f32, 1.0f32, 0.0f32);
+
+        for _ in 0..s.droplet_lifetime {
+            let (cx, cy) = (x as usize, y as usize);
+            let (u, v) = (x - cx as f32, y - cy as f32);
+            let (height, gx, gy) = height_and_gradient(heights, w, x, y);
+
+            dir_x = dir_x * s.inertia - gx * (1.0 - s.inertia);
+            dir_y = dir_y * s.inertia - gy * (1.0 - s.inertia);
+            let length = (dir_x * dir_x + dir_y * dir_y).sqrt();
+            if length <= f32::EPSILON {
+                break;
+            }
+            dir_x /= length;
+            dir_y /= length;
+            x += dir_x;
+            y += dir_y;
+            if x < 0.0 || y < 0.0 || x >= max_x || y >= max_y {
+                break;
+            }
+
+            let delta = height_and_gradient(heights, w, x, y).0 - height;
+            let capacity = (-delta * speed * water * s.sediment_capacity).max(s.min_sediment_capacity);
+
+            if sediment > capacity || delta > 0.0 {
+                // В гору - заполняем ямку позади, иначе сбрасываем избыток
+                let amount = if delta > 0.0 {
+                    delta.min(sediment)
+                } else {
+                    (sediment - capacity) * s.deposit_speed
+                };
+                let corners = [
+                    (cy * w + cx, (1.0 - u) * (1.0 - v)),
+                    (cy * w + cx + 1, u * (1.0 - v)),
+                    (cy * w + w + cx, (1.0 - u) * v),
+                    (cy * w + w + cx + 1, u * v),
+                ];
+                for (i, weight) in corners {
+                    if !locked[i] {
+                        heights[i] += amount * weight;
+                        sediment -= amount * weight;
+                    }
+                }
+            } else {
+                // Не больше перепада, чтобы не прорыть яму глубже следующей точки
+                let amount = ((capacity - sediment) * s.erode_speed).min(-delta);
+                for &(dx, dy, weight) in &brush {
+                    let (bx, by) = (cx as isize + dx, cy as isize + dy);
+                    if bx < 0 || by < 0 || bx >= w as isize || by >= h as isize {
+                        continue;
+                    }
+                    let i = by as usize * w + bx as usize;
+                    if !locked[i] {
+                        heights[i] -= amount * weight;
+                        sediment += amount * weight;
+                    }
+                }
+            }
+
+            // Под уклон капля разгоняется, в гору - тормозит
+            speed = (speed * speed - delta * s.gravity).max(0.0).sqrt();
+            water *= 1.0 - s.evaporate_speed;
+        }
+    }
+}
+
+// Материал со склонов круче угла откоса сползает к нижним соседям пропорционально избытку
+fn thermal(heights: &mut [f32], w: usize, h: usize, s: &ErosionSettings, locked: &[bool]) {
+    let talus = s.talus_angle_deg.to_radians().tan() as f32;
+    let neighbours: [(isize, isize, f32); 8] = [
+        (-1, -1, std::f32::consts::SQRT_2),
+        (0, -1, 1.0),
+        (1, -1, std::f32::consts::SQRT_2),
+        (-1, 0, 1.0),
+        (1, 0, 1.0),
+        (-1, 1, std::f32::consts::SQRT_2),
+        (0, 1, 1.0),
+        (1, 1, std::f32::consts::SQRT_2),
+    ];
+    let mut delta = vec![0.0f32; w * h];
+    let mut excess = [0.0f32; 8];
+
+    for _ in 0..s.thermal_iterations {
+        delta.iter_mut().for_each(|d| *d = 0.0);
+        let mut moved = false;
+        for y in 0..h {
+            for x in 0..w {
+                let i = y * w + x;
+                if locked[i] {
+                    continue;
+                }
+                let mut total = 0.0;
+                let mut max_excess = 0.0f32;
+                for (k, &(dx, dy, distance)) in neighbours.iter().enumerate() {
+                    excess[k] = 0.0;
+                    let (nx, ny) = (x as isize + dx, y as isize + dy);
+                    if nx < 0 || ny < 0 || nx >= w as isize || ny >= h as isize {
+                        continue;
+                    }
+                    let j = ny as usize * w + nx as usize;
+                    if locked[j] {
+                        continue;
+                    }
+                    let e = heights[i] - heights[j] - talus * distance;
+                    if e > 0.0 {
+                        excess[k] = e;
+                        total += e;
+                        max_excess = max_excess.max(e);
+                    }
+                }
+                if total <= 0.0 {
+                    continue;
+                }
+                // Половина наибольшего избытка выравнивает пару ячеек ровно по углу откоса
+                let amount = s.thermal_rate * max_excess / 2.0;
+                delta[i] -= amount;
+                for (k, &(dx, dy, _)) in neighbours.iter().enumerate() {
+                    if excess[k] > 0.0 {
+                        let j = (y as isize + dy) as usize * w + (x as isize + dx) as usize;
+                        delta[j] += amount * excess[k] / total;
+                    }
+                }
+                moved = true;
+            }
+        }
+        if !moved {
+            break;
+        }
+        for (value, d) in heights.iter_mut().zip(&delta) {
+            *value += d;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Склон с волнами, чтобы капли не шли строго параллельно
+    fn hill(size: usize) -> HeightGrid {
+        HeightGrid::from_fn(size, size, |x, y| 0.5 * x as f32 + 2.0 * (y as f32 * 0.7).sin())
+    }
+
+    fn settings(seed: u64) -> ErosionSettings {
+        ErosionSettings {
+            enabled: true,
+            seed,
+            droplets: 2000,
+            erosion_radius_m: 2.0,
+            ..ErosionSettings::default()
+        }
+    }
+
+    #[test]
+    fn splitmix_matches_reference() {
+        let mut rng = SplitMix64(0);
+        assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+        let f = rng.next_f64();
+        assert!((0.0..1.0).contains(&f));
+    }
+
+    #[test]
+    fn same_seed_same_result() {
+        let locked = vec![false; 32 * 32];
+        let mut a = hill(32);
+        let mut b = hill(32);
+        let mut c = hill(32);
+        erode(&mut a, &settings(7), 1.0, &locked).unwrap();
+        erode(&mut b, &settings(7), 1.0, &locked).unwrap();
+        erode(&mut c, &settings(8), 1.0, &locked).unwrap();
+        assert_eq!(a.data, b.data);
+        assert_ne!(a.data, c.data);
+        assert_ne!(a.data, hill(32).data);
+    }
+
+    #[test]
+    fn locked_cells_are_untouched() {
+        let mut locked = vec![false; 32 * 32];
+        for i in (10 * 32..12 * 32).chain((0..32).map(|y| y * 32 + 16)) {
+            locked[i] = true;
+        }
+        let mut grid = hill(32);
+        erode(&mut grid, &settings(3), 1.0, &locked).unwrap();
+        let original = hill(32);
+        for (i, _) in locked.iter().enumerate().filter(|(_, &l)| l) {
+            assert_eq!(grid.data[i], original.data[i]);
+        }
+    }
+
+    #[test]
+    fn thermal_keeps_mass_and_flattens_cliffs() {
+        // Столб 10 м посреди плоскости
+        let (w, h) = (9, 9);
+        let mut heights = vec![0.0f32; w * h];
+        heights[4 * w + 4] = 10.0;
+        let s = ErosionSettings { thermal_iterations: 500, ..ErosionSettings::default() };
+        thermal(&mut heights, w, h, &s, &vec![false; w * h]);
+
+        let total: f32 = heights.iter().sum();
+        assert!((total - 10.0).abs() < 1e-3);
+        assert!(heights[4 * w + 4] < 3.0);
+        let talus = s.talus_angle_deg.to_radians().tan() as f32;
+        assert!(heights[4 * w + 4] - heights[4 * w + 5] < talus + 0.1);
+    }
+
+    #[test]
+    fn brush_weights_sum_to_one() {
+        for radius in [0.5, 2.0, 3.7] {
+            let brush = erosion_brush(radius);
+            let total: f32 = brush.iter().map(|b| b.2).sum();
+            assert!((total - 1.0).abs() < 1e-5);
+            assert!(brush.len() > 1);
+        }
+    }
+
+    #[test]
+    fn disabled_or_invalid_settings() {
+        let locked = vec![false; 16];
+        let mut grid = hill(4);
+        erode(&mut grid, &ErosionSettings::default(), 1.0, &locked).unwrap();
+        assert_eq!(grid.data, hill(4).data);
+
+        let bad = ErosionSettings { inertia: 1.5, ..settings(1) };
+        assert!(erode(&mut grid, &bad, 1.0, &locked).is_err());
+        let bad = ErosionSettings { talus_angle_deg: 90.0, ..settings(1) };
+        assert!(erode(&mut grid, &bad, 1.0, &locked).is_err());
+    }
+}
@@ -0,0 +1,468 @@
+// src-tauri/src/filters.rs - Цепочка фильтров карты высот: сглаживание, выбросы, ступени
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::slope_degrees;
+use crate::terrain::HeightGrid;
+
+// Один шаг цепочки: фильтр и маска, где он действует
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterStep {
+    #[serde(flatten)]
+    pub filter: HeightFilter,
+    #[serde(default)]
+    pub mask: FilterMask,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HeightFilter {
+    Gaussian(GaussianSettings),
+    Median(MedianSettings),
+    // Сглаживание с сохранением перепадов высот (обрывы, насыпи)
+    Bilateral(BilateralSettings),
+    // Ячейки, сильно отличающиеся от соседей, заменяются медианой соседей
+    Despike(DespikeSettings),
+    // Ступени от квантования высот: сглаживание, не выходящее за полшага от исходной высоты
+    Deterrace(DeterraceSettings),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GaussianSettings {
+    pub sigma_m: f64,
+}
+
+impl Default for GaussianSettings {
+    fn default() -> Self {
+        GaussianSettings { sigma_m: 2.0 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MedianSettings {
+    pub radius_m: f64,
+}
+
+impl Default for MedianSettings {
+    fn default() -> Self {
+        MedianSettings { radius_m: 2.0 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BilateralSettings {
+    pub sigma_m: f64,
+    // Перепад высот, после которого соседи почти не учитываются
+    pub sigma_height_m: f64,
+}
+
+impl Default for BilateralSettings {
+    fn default() -> Self {
+        BilateralSettings {
+            sigma_m: 3.0,
+            sigma_height_m: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DespikeSettings {
+    pub threshold_m: f64,
+    pub passes: usize,
+}
+
+impl Default for DespikeSettings {
+    fn default() -> Self {
+        DespikeSettings {
+            threshold_m: 5.0,
+            passes: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeterraceSettings {
+    // Шаг квантования исходных высот (м)
+    pub step_m: f64,
+    pub iterations: usize,
+}
+
+impl Default for DeterraceSettings {
+    fn default() -> Self {
+        DeterraceSettings {
+            step_m: 1.0,
+            iterations: 100,
+        }
+    }
+}
+
+// Где фильтр действует. Без ограничений - везде. За пределами диапазона уклонов
+// действие спадает до нуля на feather_deg градусах, по высоте граница резкая.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterMask {
+    pub min_slope_deg: Option<f64>,
+    pub max_slope_deg: Option<f64>,
+    pub min_height_m: Option<f32>,
+    pub max_height_m: Option<f32>,
+    pub feather_deg: f64,
+}
+
+impl Default for FilterMask {
+    fn default() -> Self {
+        FilterMask {
+            min_slope_deg: None,
+            max_slope_deg: None,
+            min_height_m: None,
+            max_height_m: None,
+            feather_deg: 5.0,
+        }
+    }
+}
+
+impl FilterMask {
+    fn is_unbounded(&self) -> bool {
+        self.min_slope_deg.is_none()
+            && self.max_slope_deg.is_none()
+            && self.min_height_m.is_none()
+            && self.max_height_m.is_none()
+    }
+
+    // Вес фильтра 0..1 для ячейки с данным уклоном и высотой
+    fn weight(&self, slope_deg: f64, height: f32) -> f32 {
+        if self.min_height_m.is_some_and(|min| height < min) || self.max_height_m.is_some_and(|max| height > max) {
+            return 0.0;
+        }
+
+        let outside = match (self.min_slope_deg, self.max_slope_deg) {
+            (Some(min), _) if slope_deg < min => min - slope_deg,
+            (_, Some(max)) if slope_deg > max => slope_deg - max,
+            _ => 0.0,
+        };
+        if outside <= 0.0 {
+            1.0
+        } else if self.feather_deg <= 0.0 {
+            0.0
+        } else {
+            (1.0 - outside / self.feather_deg).max(0.0) as f32
+        }
+    }
+}
+
+// Применяет фильтры по порядку. cell_size - размер ячейки на местности (м).
+pub fn apply_filters(grid: &mut HeightGrid, steps: &[FilterStep], cell_size: f64) -> Result<(), String> {
+    for step in steps {
+        validate(&step.filter)?;
+
+        // Маска считается по рельефу до этого шага
+        let weights = if step.mask.is_unbounded() {
+            None
+        } else {
+            let slopes = slope_degrees(grid, cell_size);
+            Some(
+                slopes
+                    .iter()
+                    .zip(&grid.data)
+                    .map(|(&slope, &height)| step.mask.weight(slope, height))
+                    .collect::<Vec<f32>>(),
+            )
+        };
+
+        let filtered = run_filter(grid, &step.filter, cell_size);
+
+        let mut max_change = 0.0f32;
+        for (i, value) in filtered.into_iter().enumerate() {
+            let weight = weights.as_ref().map_or(1.0, |w| w[i]);
+            let new_value = grid.data[i] + (value - grid.data[i]) * weight;
+            max_change = max_change.max((new_value - grid.data[i]).abs());
+            grid.data[i] = new_value;
+        }
+        println!("Height filter {:?}: max change {:.2} m", step.filter, max_change);
+    }
+    Ok(())
+}
+
+fn validate(filter: &HeightFilter) -> Result<(), String> {
+    let positive = |name: &str, value: f64| {
+        if value.is_finite() && value > 0.0 {
+            Ok(())
+        } else {
+            Err(format!("Height filter {} must be positive, got {}", name, value))
+        }
+    };
+    match filter {
+        HeightFilter::Gaussian(s) => positive("sigma_m", s.sigma_m),
+        HeightFilter::Median(s) => positive("radius_m", s.radius_m),
+        HeightFilter::Bilateral(s) => {
+            positive("sigma_m", s.sigma_m)?;
+            positive("sigma_height_m", s.sigma_height_m)
+        }
+        HeightFilter::Despike(s) => positive("threshold_m", s.threshold_m),
+        HeightFilter::Deterrace(s) => positive("step_m", s.step_m),
+    }
+}
+
+fn run_filter(grid: &HeightGrid, filter: &HeightFilter, cell_size: f64) -> Vec<f32> {
+    let (w, h) = (grid.width, grid.height);
+    match filter {
+        HeightFilter::Gaussian(s) => gaussian_blur(&grid.data, w, h, s.sigma_m / cell_size),
+        HeightFilter::Median(s) => median(&grid.data, w, h, window_radius(s.radius_m / cell_size)),
+        HeightFilter::Bilateral(s) => bilateral(&grid.data, w, h, s.sigma_m / cell_size, s.sigma_height_m),
+        HeightFilter::Despike(s) => {
+            let mut data = grid.data.clone();
+            for _ in 0..s.passes {
+                if despike(&mut data, w, h, s.threshold_m as f32) == 0 {
+                    break;
+                }
+            }
+            data
+        }
+        HeightFilter::Deterrace(s) => deterrace(&grid.data, w, h, s.step_m as f32, s.iterations),
+    }
+}
+
+// Радиус окна в ячейках, не меньше одной
+fn window_radius(radius_px: f64) -> usize {
+    (radius_px.round() as usize).max(1)
+}
+
+// Раздельное гауссово размытие, края продлеваются
+pub fn gaussian_blur(data: &[f32], w: usize, h: usize, sigma_px: f64) -> Vec<f32> {
+    let radius = (3.0 * sigma_px).ceil().max(1.0) as isize;
+    let mut kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-((i * i) as f64) / (2.0 * sigma_px * sigma_px)).exp() as f32)
+        .collect();
+    let total: f32 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|k| *k /= total);
+
+    let mut horizontal = vec![0.0f32; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            horizontal[y * w + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, &wk)| {
+                    let sx = (x as isize + k as isize - radius).clamp(0, w as isize - 1) as usize;
+                    wk * data[y * w + sx]
+                })
+                .sum();
+        }
+    }
+
+    let mut out = vec![0.0f32; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            out[y * w + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, &wk)| {
+                    let sy = (y as isize + k as isize - radius).clamp(0, h as isize - 1) as usize;
+                    wk * horizontal[sy * w + x]
+                })
+                .sum();
+        }
+    }
+    out
+}
+
+fn median(data: &[f32], w: usize, h: usize, radius: usize) -> Vec<f32> {
+    let mut out = vec![0.0f32; w * h];
+    let mut window = Vec::with_capacity((2 * radius + 1) * (2 * radius + 1));
+    for y in 0..h {
+        for x in 0..w {
+            window.clear();
+            for sy in y.saturating_sub(radius)..(y + radius + 1).min(h) {
+                window.extend_from_slice(&data[sy * w + x.saturating_sub(radius)..sy * w + (x + radius + 1).min(w)]);
+            }
+            let mid = window.len() / 2;
+            let (_, value, _) = window.select_nth_unstable_by(mid, f32::total_cmp);
+            out[y * w + x] = *value;
+        }
+    }
+    out
+}
+
+fn bilateral(data: &[f32], w: usize, h: usize, sigma_px: f64, sigma_height: f64) -> Vec<f32> {
+    let radius = (2.0 * sigma_px).ceil().max(1.0) as usize;
+    let spatial = 1.0 / (2.0 * sigma_px * sigma_px);
+    let range = 1.0 / (2.0 * sigma_height * sigma_height);
+
+    let mut out = vec![0.0f32; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            let center = data[y * w + x] as f64;
+            let mut sum = 0.0;
+            let mut weight = 0.0;
+            for sy in y.saturating_sub(radius)..(y + radius + 1).min(h) {
+                for sx in x.saturating_sub(radius)..(x + radius + 1).min(w) {
+                    let value = data[sy * w + sx] as f64;
+                    let d2 = ((sx as f64 - x as f64).powi(2) + (sy as f64 - y as f64).powi(2)) * spatial;
+                    let wgt = (-d2 - (value - center).powi(2) * range).exp();
+                    sum += value * wgt;
+                    weight += wgt;
+                }
+            }
+            out[y * w + x] = (sum / weight) as f32;
+        }
+    }
+    out
+}
+
+// Один проход: выбросы сравниваются с медианой 8 соседей. Возвращает число исправленных ячеек.
+fn despike(data: &mut [f32], w: usize, h: usize, threshold: f32) -> usize {
+    let source = data.to_vec();
+    let mut fixed = 0;
+    let mut neighbours = Vec::with_capacity(8);
+    for y in 0..h {
+        for x in 0..w {
+            neighbours.clear();
+            for sy in y.saturating_sub(1)..(y + 2).min(h) {
+                for sx in x.saturating_sub(1)..(x + 2).min(w) {
+                    if sx != x || sy != y {
+                        neighbours.push(source[sy * w + sx]);
+                    }
+                }
+            }
+            neighbours.sort_unstable_by(f32::total_cmp);
+            let n = neighbours.len();
+            let median = if n % 2 == 0 {
+                (neighbours[n / 2 - 1] + neighbours[n / 2]) / 2.0
+            } else {
+                neighbours[n / 2]
+            };
+            if (source[y * w + x] - median).abs() > threshold {
+                data[y * w + x] = median;
+                fixed += 1;
+            }
+        }
+    }
+    fixed
+}
+
+// Итерации Якоби к гладкой поверхности, но каждая ячейка остаётся в пределах
+// ±step/2 от исходной высоты - истинная высота до квантования была где-то там
+fn deterrace(data: &[f32], w: usize, h: usize, step: f32, iterations: usize) -> Vec<f32> {
+    let half = step / 2.0;
+    let mut current = data.to_vec();
+    let mut next = current.clone();
+    for _ in 0..iterations {
+        for y in 0..h {
+            for x in 0..w {
+                let mut sum = 0.0;
+                let mut count = 0.0;
+                if x > 0 {
+                    sum += current[y * w + x - 1];
+                    count += 1.0;
+                }
+                if x + 1 < w {
+                    sum += current[y * w + x + 1];
+                    count += 1.0;
+                }
+                if y > 0 {
+                    sum += current[(y - 1) * w + x];
+                    count += 1.0;
+                }
+                if y + 1 < h {
+                    sum += current[(y + 1) * w + x];
+                    count += 1.0;
+                }
+                let original = data[y * w + x];
+                next[y * w + x] = (sum / count).clamp(original - half, original + half);
+            }
+        }
+        std::mem::swap(&mut current, &mut next);
+    }
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spike(x: usize, y: usize) -> f32 {
+        if (x, y) == (4, 4) { 50.0 } else { 10.0 }
+    }
+
+    #[test]
+    fn gaussian_keeps_flat_ground_and_spreads_a_spike() {
+        let flat = gaussian_blur(&[7.0; 25], 5, 5, 1.5);
+        assert!(flat.iter().all(|&v| (v - 7.0).abs() < 1e-5));
+
+        let g = HeightGrid::from_fn(9, 9, spike);
+        let blurred = gaussian_blur(&g.data, 9, 9, 1.0);
+        assert!(blurred[4 * 9 + 4] < 20.0);
+        assert!(blurred[4 * 9 + 5] > 10.0);
+    }
+
+    #[test]
+    fn median_and_despike_remove_a_spike() {
+        let g = HeightGrid::from_fn(9, 9, spike);
+        assert!(median(&g.data, 9, 9, 1).iter().all(|&v| v == 10.0));
+
+        let mut data = g.data.clone();
+        assert_eq!(despike(&mut data, 9, 9, 5.0), 1);
+        assert!(data.iter().all(|&v| v == 10.0));
+        assert_eq!(despike(&mut data, 9, 9, 5.0), 0);
+    }
+
+    #[test]
+    fn bilateral_keeps_a_cliff() {
+        let g = HeightGrid::from_fn(10, 10, |x, _| if x < 5 { 0.0 } else { 20.0 });
+        let smoothed = bilateral(&g.data, 10, 10, 2.0, 1.0);
+        assert!(smoothed[5 * 10 + 4] < 0.01);
+        assert!(smoothed[5 * 10 + 5] > 19.99);
+        // Гаусс той же ширины обрыв размывает
+        assert!(gaussian_blur(&g.data, 10, 10, 2.0)[5 * 10 + 4] > 5.0);
+    }
+
+    #[test]
+    fn deterrace_stays_within_half_a_step() {
+        // Пандус, квантованный с шагом 1 м
+        let g = HeightGrid::from_fn(12, 12, |x, _| (x as f32 * 0.3).floor());
+        let smoothed = deterrace(&g.data, 12, 12, 1.0, 200);
+        for (&s, &o) in smoothed.iter().zip(&g.data) {
+            assert!((s - o).abs() <= 0.5 + 1e-6);
+        }
+        // Ступени сглажены: соседние ячейки отличаются меньше, чем на шаг
+        let row = &smoothed[6 * 12..7 * 12];
+        assert!(row.windows(2).all(|p| (p[1] - p[0]).abs() < 0.9));
+    }
+
+    #[test]
+    fn mask_feathers_outside_slope_range() {
+        let mask = FilterMask { max_slope_deg: Some(10.0), feather_deg: 5.0, ..FilterMask::default() };
+        assert_eq!(mask.weight(8.0, 0.0), 1.0);
+        assert_eq!(mask.weight(12.5, 0.0), 0.5);
+        assert_eq!(mask.weight(20.0, 0.0), 0.0);
+
+        let mask = FilterMask { min_height_m: Some(100.0), ..FilterMask::default() };
+        assert_eq!(mask.weight(0.0, 99.0), 0.0);
+        assert_eq!(mask.weight(0.0, 101.0), 1.0);
+    }
+
+    #[test]
+    fn steps_are_parsed_and_applied_in_order() {
+        let steps: Vec<FilterStep> = serde_json::from_str(
+            r#"[
+                { "type": "despike", "threshold_m": 5.0 },
+                { "type": "gaussian", "sigma_m": 2.0, "mask": { "min_height_m": 1000.0 } }
+            ]"#,
+        )
+        .unwrap();
+        let mut g = HeightGrid::from_fn(9, 9, spike);
+        apply_filters(&mut g, &steps, 1.0).unwrap();
+        // Выброс убран, а размытие по маске высот не действует
+        assert!(g.data.iter().all(|&v| v == 10.0));
+
+        let bad = [FilterStep {
+            filter: HeightFilter::Median(MedianSettings { radius_m: 0.0 }),
+            mask: FilterMask::default(),
+        }];
+        assert!(apply_filters(&mut g, &bad, 1.0).is_err());
+    }
+}
@@ -0,0 +1,322 @@
+// src-tauri/src/geotiff.rs - Чтение одноканальных GeoTIFF (DEM, LiDAR)
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use tiff::decoder::{Decoder, DecodingResult, Limits};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+use crate::elevation::{ElevationData, ElevationSource, FetchContext};
+use crate::raster::{self, Crs, GeoRaster, GeoTransform};
+use crate::BoundingBox;
+
+// Ключи GeoKeyDirectory (GeoTIFF 1.0, раздел 6.2)
+const GT_RASTER_TYPE_KEY: u16 = 1025;
+const GEOGRAPHIC_TYPE_KEY: u16 = 2048;
+const PROJECTED_CS_TYPE_KEY: u16 = 3072;
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+const USER_DEFINED: u16 = 32767;
+// Наибольший размер раскодированного растра (1 ГиБ)
+const MAX_DECODED_BYTES: usize = 1 << 30;
+
+pub struct GeoTiffSource {
+    pub path: PathBuf,
+    // Переопределяет CRS из файла (если GeoKeys нет или они пользовательские)
+    pub epsg: Option<u32>,
+}
+
+#[async_trait]
+impl ElevationSource for GeoTiffSource {
+    fn name(&self) -> String {
+        format!(
+            "GeoTIFF {}",
+            self.path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()
+        )
+    }
+
+    async fn fetch(&self, bbox: &BoundingBox, ctx: &FetchContext) -> Result<ElevationData, String> {
+        let raster = read_geotiff(&self.path, self.epsg)?;
+        println!(
+            "Loaded GeoTIFF {}x{} ({:?}), {:.2} m/px",
+            raster.width,
+            raster.height,
+            raster.crs,
+            raster.ground_resolution((bbox.min_lat + bbox.max_lat) / 2.0)
+        );
+
+        raster::rasters_to_height_grid(&[raster], bbox, ctx.meters_per_pixel, &self.name()).map(ElevationData::from)
+    }
+}
+
+pub fn read_geotiff(path: &Path, epsg_override: Option<u32>) -> Result<GeoRaster, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut decoder = Decoder::new(BufReader::new(file))
+        .map_err(|e| format!("Failed to read GeoTIFF {}: {}", path.display(), e))?
+        .with_limits(decode_limits());
+
+    let bits = match decoder.colortype().map_err(|e| e.to_string())? {
+        ColorType::Gray(bits) => bits,
+        other => return Err(format!("Only single-band GeoTIFF DEMs are supported, got {:?}", other)),
+    };
+
+    let (width, height) = decoder.dimensions().map_err(|e| e.to_string())?;
+    let decoded_bytes = (width as usize).saturating_mul(height as usize).saturating_mul(bits.div_ceil(8) as usize);
+    if decoded_bytes > MAX_DECODED_BYTES {
+        return Err(format!(
+            "GeoTIFF {} is too large ({}x{}, {} MiB), crop it to the map area first",
+            path.display(),
+            width,
+            height,
+            decoded_bytes >> 20
+        ));
+    }
+
+    let geo_keys = read_geo_keys(&mut decoder);
+    let crs = match epsg_override {
+        Some(code) => Crs::from_epsg(code)?,
+        None => crs_from_geo_keys(&geo_keys)?,
+    };
+
+    let mut transform = read_transform(&mut decoder)?;
+    if geo_keys.get(&GT_RASTER_TYPE_KEY) == Some(&RASTER_PIXEL_IS_POINT) {
+        // Привязка к центру пикселя - сдвигаем на половину пикселя к углу
+        let t = &mut transform.0;
+        t[0] -= 0.5 * (t[1] + t[2]);
+        t[3] -= 0.5 * (t[4] + t[5]);
+    }
+
+    let nodata = decoder
+        .get_tag_ascii_string(Tag::GdalNodata)
+        .ok()
+        .and_then(|s| s.trim().trim_end_matches('\0').parse::<f64>().ok());
+
+    let image = decoder
+        .read_image()
+        .map_err(|e| format!("Failed to decode GeoTIFF {}: {}", path.display(), e))?;
+
+    let data = match image {
+        DecodingResult::I16(v) => to_heights(v.into_iter().map(|x| x as f64), nodata),
+        // Nodata сравниваем в типе отсчётов: -9999.9 в f32 не равно -9999.9 в f64
+        DecodingResult::F32(v) => to_heights(v.into_iter().map(|x| x as f64), nodata.map(|n| n as f32 as f64)),
+        DecodingResult::U8(v) => to_heights(v.into_iter().map(|x| x as f64), nodata),
+        DecodingResult::U16(v) => to_heights(v.into_iter().map(|x| x as f64), nodata),
+        DecodingResult::U32(v) => to_heights(v.into_iter().map(|x| x as f64), nodata),
+        DecodingResult::U64(v) => to_heights(v.into_iter().map(|x| x as f64), nodata),
+        DecodingResult::F64(v) => to_heights(v.into_iter(), nodata),
+        DecodingResult::I8(v) => to_heights(v.into_iter().map(|x| x as f64), nodata),
+        DecodingResult::I32(v) => to_heights(v.into_iter().map(|x| x as f64), nodata),
+        DecodingResult::I64(v) => to_heights(v.into_iter().map(|x| x as f64), nodata),
+    };
+
+    if data.len() != width as usize * height as usize {
+        return Err(format!(
+            "GeoTIFF {} has {} samples, expected {}x{}",
+            path.display(),
+            data.len(),
+            width,
+            height
+        ));
+    }
+
+    Ok(GeoRaster {
+        width: width as usize,
+        height: height as usize,
+        data,
+        transform,
+        crs,
+    })
+}
+
+fn decode_limits() -> Limits {
+    let mut limits = Limits::default();
+    limits.decoding_buffer_size = MAX_DECODED_BYTES;
+    limits
+}
+
+fn to_heights(values: impl Iterator<Item = f64>, nodata: Option<f64>) -> Vec<f32> {
+    values
+        .map(|v| {
+            if v.is_nan() || Some(v) == nodata {
+                f32::NAN
+            } else {
+                v as f32
+            }
+        })
+        .collect()
+}
+
+fn read_geo_keys<R: std::io::Read + std::io::Seek>(decoder: &mut Decoder<R>) -> HashMap<u16, u16> {
+    match decoder.get_tag_u16_vec(Tag::GeoKeyDirectoryTag) {
+        Ok(directory) => parse_geo_key_directory(&directory),
+        Err(_) => HashMap::new(),
+    }
+}
+
+// Только ключи со значением прямо в директории (TIFFTagLocation = 0).
+// Тот же формат лежит в VLR LASF_Projection/34735 у файлов LAS.
+pub fn parse_geo_key_directory(directory: &[u16]) -> HashMap<u16, u16> {
+    let mut keys = HashMap::new();
+    for entry in directory.chunks_exact(4).skip(1) {
+        if entry[1] == 0 {
+            keys.insert(entry[0], entry[3]);
+        }
+    }
+    keys
+}
+
+pub fn crs_from_geo_keys(keys: &HashMap<u16, u16>) -> Result<Crs, String> {
+    if let Some(&code) = keys.get(&PROJECTED_CS_TYPE_KEY) {
+        if code != USER_DEFINED {
+            return Crs::from_epsg(code as u32);
+        }
+    }
+    if let Some(&code) = keys.get(&GEOGRAPHIC_TYPE_KEY) {
+        if code != USER_DEFINED {
+            return Crs::from_epsg(code as u32);
+        }
+    }
+    Err("GeoTIFF has no EPSG code in its GeoKeys, set it explicitly".to_string())
+}
+
+fn read_transform<R: std::io::Read + std::io::Seek>(decoder: &mut Decoder<R>) -> Result<GeoTransform, String> {
+    // Полная матрица 4x4 по строкам
+    if let Ok(m) = decoder.get_tag_f64_vec(Tag::ModelTransformationTag) {
+        if m.len() == 16 {
+            return Ok(GeoTransform([m[3], m[0], m[1], m[7], m[4], m[5]]));
+        }
+    }
+
+    let scale = decoder
+        .get_tag_f64_vec(Tag::ModelPixelScaleTag)
+        .map_err(|_| "GeoTIFF has no ModelPixelScale or ModelTransformation tag".to_string())?;
+    let tiepoint = decoder
+        .get_tag_f64_vec(Tag::ModelTiepointTag)
+        .map_err(|_| "GeoTIFF has no ModelTiepoint tag".to_string())?;
+    if scale.len() < 2 || tiepoint.len() < 6 {
+        return Err("GeoTIFF has malformed georeferencing tags".to_string());
+    }
+
+    // Тайпоинт (I, J, K) -> (X, Y, Z); ось Y растра направлена вниз
+    let (i, j, x, y) = (tiepoint[0], tiepoint[1], tiepoint[3], tiepoint[4]);
+    Ok(GeoTransform([
+        x - i * scale[0],
+        scale[0],
+        0.0,
+        y + j * scale[1],
+        0.0,
+        -scale[1],
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float32_nodata_matches_after_rounding() {
+        let nodata = Some(-9999.9f64 as f32 as f64);
+        let samples = [-9999.9f32, 12.5, f32::NAN];
+        let heights = to_heights(samples.iter().map(|&x| x as f64), nodata);
+        assert!(heights[0].is_nan());
+        assert_eq!(heights[1], 12.5);
+        assert!(heights[2].is_nan());
+    }
+
+    #[test]
+    fn geo_key_directory_keeps_inline_values() {
+        // Заголовок, затем ключи (id, location, count, value)
+        let directory = [1, 1, 0, 3, 1025, 0, 1, 2, 3072, 0, 1, 32633, 3073, 34737, 10, 0];
+        let keys = parse_geo_key_directory(&directory);
+        assert_eq!(keys.get(&GT_RASTER_TYPE_KEY), Some(&RASTER_PIXEL_IS_POINT));
+        assert_eq!(keys.get(&PROJECTED_CS_TYPE_KEY), Some(&32633));
+        assert!(!keys.contains_key(&3073));
+    }
+
+    // Одноканальный GeoTIFF с привязкой через тайпоинт и масштаб пикселя
+    fn write_geotiff<C: tiff::encoder::colortype::ColorType>(
+        path: &Path,
+        size: (u32, u32),
+        data: &[C::Inner],
+        origin: (f64, f64),
+        pixel: f64,
+        keys: &[u16],
+        nodata: &str,
+    ) where
+        [C::Inner]: tiff::encoder::TiffValue,
+    {
+        let file = File::create(path).unwrap();
+        let mut tiff = tiff::encoder::TiffEncoder::new(file).unwrap();
+        let mut image = tiff.new_image::<C>(size.0, size.1).unwrap();
+        let mut directory = vec![1, 1, 0, (keys.len() / 2) as u16];
+        for pair in keys.chunks_exact(2) {
+            directory.extend_from_slice(&[pair[0], 0, 1, pair[1]]);
+        }
+        let encoder = image.encoder();
+        encoder.write_tag(Tag::GeoKeyDirectoryTag, &directory[..]).unwrap();
+        encoder.write_tag(Tag::ModelPixelScaleTag, &[pixel, pixel, 0.0][..]).unwrap();
+        encoder.write_tag(Tag::ModelTiepointTag, &[0.0, 0.0, 0.0, origin.0, origin.1, 0.0][..]).unwrap();
+        encoder.write_tag(Tag::GdalNodata, nodata).unwrap();
+        image.write_data(data).unwrap();
+    }
+
+    #[test]
+    fn int16_geotiff_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dem.tif");
+        write_geotiff::<tiff::encoder::colortype::GrayI16>(
+            &path,
+            (3, 2),
+            &[1, 2, -32768, 4, 5, 6],
+            (500000.0, 5000000.0),
+            10.0,
+            &[PROJECTED_CS_TYPE_KEY, 32633],
+            "-32768",
+        );
+
+        let raster = read_geotiff(&path, None).unwrap();
+        assert_eq!((raster.width, raster.height), (3, 2));
+        assert_eq!(raster.crs, Crs::Utm { zone: 33, north: true });
+        assert_eq!(raster.transform.0, [500000.0, 10.0, 0.0, 5000000.0, 0.0, -10.0]);
+        assert_eq!(raster.data[0], 1.0);
+        assert_eq!(raster.data[4], 5.0);
+        assert!(raster.data[2].is_nan());
+        assert_eq!(raster.data.iter().filter(|v| v.is_nan()).count(), 1);
+
+        let overridden = read_geotiff(&path, Some(3857)).unwrap();
+        assert_eq!(overridden.crs, Crs::WebMercator);
+    }
+
+    #[test]
+    fn float32_pixel_is_point_geotiff_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dem.tif");
+        write_geotiff::<tiff::encoder::colortype::Gray32Float>(
+            &path,
+            (2, 2),
+            &[100.5, -9999.9, 0.0, 7.25],
+            (10.0, 50.0),
+            0.5,
+            &[GT_RASTER_TYPE_KEY, RASTER_PIXEL_IS_POINT, GEOGRAPHIC_TYPE_KEY, 4326],
+            "-9999.9",
+        );
+
+        let raster = read_geotiff(&path, None).unwrap();
+        assert_eq!(raster.crs, Crs::Geographic);
+        // Тайпоинт указывает на центр пикселя (0, 0) - угол на полпикселя левее и выше
+        assert_eq!(raster.transform.0, [9.75, 0.5, 0.0, 50.25, 0.0, -0.5]);
+        assert_eq!(raster.data[0], 100.5);
+        assert!(raster.data[1].is_nan());
+        assert_eq!(raster.data[2], 0.0);
+        assert_eq!(raster.data[3], 7.25);
+    }
+
+    #[test]
+    fn user_defined_crs_is_rejected() {
+        let keys = HashMap::from([(PROJECTED_CS_TYPE_KEY, USER_DEFINED)]);
+        assert!(crs_from_geo_keys(&keys).is_err());
+    }
+}
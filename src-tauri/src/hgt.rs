@@ -0,0 +1,180 @@
+// src-tauri/src/hgt.rs - Тайлы SRTM .hgt (1" и 3") из локальной папки
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use walkdir::WalkDir;
+
+use crate::elevation::{ElevationData, ElevationSource, FetchContext};
+use crate::raster::{self, Crs, GeoRaster, GeoTransform};
+use crate::BoundingBox;
+
+// Значение "нет данных" в SRTM
+const HGT_VOID: i16 = -32768;
+
+pub struct HgtSource {
+    pub directory: PathBuf,
+}
+
+#[async_trait]
+impl ElevationSource for HgtSource {
+    fn name(&self) -> String {
+        format!("SRTM .hgt ({})", self.directory.display())
+    }
+
+    async fn fetch(&self, bbox: &BoundingBox, ctx: &FetchContext) -> Result<ElevationData, String> {
+        let index = index_hgt_files(&self.directory);
+        let mut rasters = Vec::new();
+        let mut missing_tiles = Vec::new();
+
+        for (lat, lng) in hgt_tiles_for_bbox(bbox) {
+            let name = hgt_tile_name(lat, lng);
+            match index.get(&name.to_lowercase()) {
+                Some(path) => {
+                    println!("Loading SRTM tile {}", path.display());
+                    rasters.push(read_hgt(path, lat, lng)?);
+                }
+                None => {
+                    eprintln!("Warning: SRTM tile {} not found in {}", name, self.directory.display());
+                    missing_tiles.push(name);
+                }
+            }
+        }
+
+        let grid = raster::rasters_to_height_grid(&rasters, bbox, ctx.meters_per_pixel, &self.name())?;
+        Ok(ElevationData { grid, missing_tiles })
+    }
+}
+
+// Имя тайла по юго-западному углу: N47E008.hgt, S12W077.hgt
+pub fn hgt_tile_name(lat: i32, lng: i32) -> String {
+    format!(
+        "{}{:02}{}{:03}.hgt",
+        if lat >= 0 { 'N' } else { 'S' },
+        lat.abs(),
+        if lng >= 0 { 'E' } else { 'W' },
+        lng.abs()
+    )
+}
+
+// Юго-западные углы всех градусных тайлов, пересекающих bbox
+fn hgt_tiles_for_bbox(bbox: &BoundingBox) -> Vec<(i32, i32)> {
+    let lat0 = bbox.min_lat.floor() as i32;
+    let lat1 = (bbox.max_lat.ceil() as i32 - 1).max(lat0);
+    // За антимеридианом считаем долготу дальше 180° и потом возвращаем в [-180, 180)
+    let max_lng = bbox.min_lng + bbox.lng_span();
+    let lng0 = bbox.min_lng.floor() as i32;
+    let lng1 = (max_lng.ceil() as i32 - 1).max(lng0);
+
+    let mut tiles = Vec::new();
+    for lat in lat0..=lat1 {
+        for lng in lng0..=lng1 {
+            tiles.push((lat, if lng >= 180 { lng - 360 } else { lng }));
+        }
+    }
+    tiles
+}
+
+// Все .hgt в папке (рекурсивно), ключ - имя файла в нижнем регистре
+fn index_hgt_files(directory: &Path) -> HashMap<String, PathBuf> {
+    WalkDir::new(directory)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_lowercase();
+            if name.ends_with(".hgt") {
+                Some((name, e.path().to_path_buf()))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// Квадратная сетка big-endian i16, строка 0 - северный край тайла.
+// Отсчёты лежат на узлах (включая оба края), отсюда сдвиг на полпикселя.
+pub fn read_hgt(path: &Path, lat: i32, lng: i32) -> Result<GeoRaster, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let size = match bytes.len() {
+        25_934_402 => 3601, // 1 arc-second
+        2_884_802 => 1201,  // 3 arc-seconds
+        n => return Err(format!("{} has unexpected size {} bytes", path.display(), n)),
+    };
+
+    let data = bytes
+        .chunks_exact(2)
+        .map(|b| {
+            let v = i16::from_be_bytes([b[0], b[1]]);
+            if v == HGT_VOID { f32::NAN } else { v as f32 }
+        })
+        .collect();
+
+    let step = 1.0 / (size - 1) as f64;
+    Ok(GeoRaster {
+        width: size,
+        height: size,
+        data,
+        transform: GeoTransform([
+            lng as f64 - step / 2.0,
+            step,
+            0.0,
+            (lat + 1) as f64 + step / 2.0,
+            0.0,
+            -step,
+        ]),
+        crs: Crs::Geographic,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_names_use_the_south_west_corner() {
+        assert_eq!(hgt_tile_name(47, 8), "N47E008.hgt");
+        assert_eq!(hgt_tile_name(-12, -77), "S12W077.hgt");
+        assert_eq!(hgt_tile_name(0, -1), "N00W001.hgt");
+    }
+
+    #[test]
+    fn tiles_cover_the_bbox() {
+        let bbox = BoundingBox { min_lat: 46.5, min_lng: 7.2, max_lat: 47.0, max_lng: 8.1 };
+        assert_eq!(hgt_tiles_for_bbox(&bbox), vec![(46, 7), (46, 8)]);
+
+        // Через антимеридиан: 179° в.д. и 180° з.д.
+        let bbox = BoundingBox { min_lat: -17.5, min_lng: 179.5, max_lat: -17.2, max_lng: -179.5 };
+        assert_eq!(hgt_tiles_for_bbox(&bbox), vec![(-18, 179), (-18, -180)]);
+    }
+
+    #[test]
+    fn hgt_nodes_sit_on_whole_degrees() {
+        let size = 1201;
+        let mut bytes = vec![0u8; size * size * 2];
+        bytes[..2].copy_from_slice(&1234i16.to_be_bytes());
+        bytes[2..4].copy_from_slice(&HGT_VOID.to_be_bytes());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("N47E008.hgt");
+        std::fs::write(&path, &bytes).unwrap();
+        let raster = read_hgt(&path, 47, 8).unwrap();
+
+        assert_eq!((raster.width, raster.height), (size, size));
+        assert_eq!(raster.data[0], 1234.0);
+        assert!(raster.data[1].is_nan());
+        // Первый отсчёт - ровно северо-западный угол тайла
+        assert_eq!(raster.sample(8.0, 48.0), 1234.0);
+        let (col, row) = raster.transform.to_pixel(9.0, 47.0).unwrap();
+        assert!((col - size as f64 + 0.5).abs() < 1e-9 && (row - size as f64 + 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn hgt_of_unknown_size_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("N00E000.hgt");
+        std::fs::write(&path, [0u8; 100]).unwrap();
+        assert!(read_hgt(&path, 0, 0).is_err());
+    }
+}
@@ -0,0 +1,594 @@
+// src-tauri/src/lidar.rs - Облака точек LAS/LAZ: точки земли (класс 2) -> DEM
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use las::point::Classification;
+use las::{Read, Reader};
+use serde::{Deserialize, Serialize};
+use spade::{DelaunayTriangulation, FloatTriangulation, HasPosition, Point2, Triangulation};
+use walkdir::WalkDir;
+
+use crate::blend;
+use crate::elevation::{ElevationData, ElevationSource, FetchContext};
+use crate::geotiff;
+use crate::raster::{self, Crs, GeoRaster, GeoTransform, MAX_SOURCE_GRID};
+use crate::BoundingBox;
+
+// VLR с GeoKeyDirectory (LAS 1.4, раздел 2.5)
+const LASF_PROJECTION: &str = "LASF_Projection";
+const GEO_KEY_DIRECTORY_RECORD: u16 = 34735;
+// Единицы в GeoKeys: ProjLinearUnitsGeoKey, VerticalUnitsGeoKey и коды единиц EPSG
+const PROJ_LINEAR_UNITS_KEY: u16 = 3076;
+const VERTICAL_UNITS_KEY: u16 = 4099;
+const UNIT_METRE: u16 = 9001;
+const UNIT_FOOT: u16 = 9002;
+const UNIT_US_SURVEY_FOOT: u16 = 9003;
+// Больше точек земли в выбранной области не читаем: сетка от них уже не станет точнее
+const MAX_GROUND_POINTS: u64 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LidarInterpolation {
+    // Обратные расстояния по точкам в соседних ячейках
+    #[default]
+    Idw,
+    // Триангуляция Делоне и линейная интерполяция внутри треугольников
+    Tin,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LidarSettings {
+    // Файлы .las/.laz или папки с ними
+    pub paths: Vec<String>,
+    // Переопределяет CRS из файлов
+    pub epsg: Option<u32>,
+    // Шаг сетки DEM в метрах (в CRS облака точек)
+    pub resolution_m: f64,
+    // Ячейки дальше этого от ближайшей точки земли остаются без данных
+    pub max_gap_m: f64,
+    pub interpolation: LidarInterpolation,
+}
+
+impl Default for LidarSettings {
+    fn default() -> Self {
+        LidarSettings {
+            paths: Vec::new(),
+            epsg: None,
+            resolution_m: 1.0,
+            max_gap_m: 5.0,
+            interpolation: LidarInterpolation::default(),
+        }
+    }
+}
+
+pub struct LidarSource {
+    pub settings: LidarSettings,
+}
+
+// Прямоугольник в координатах CRS облака точек
+#[derive(Debug, Clone, Copy)]
+struct Extent {
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+}
+
+impl Extent {
+    fn intersects(&self, other: &Extent) -> bool {
+        self.min_x <= other.max_x && self.max_x >= other.min_x && self.min_y <= other.max_y && self.max_y >= other.min_y
+    }
+}
+
+#[async_trait]
+impl ElevationSource for LidarSource {
+    fn name(&self) -> String {
+        match self.settings.paths.as_slice() {
+            [path] => format!(
+                "LiDAR {}",
+                Path::new(path).file_name().map(|n| n.to_string_lossy()).unwrap_or_default()
+            ),
+            paths => format!("LiDAR ({} paths)", paths.len()),
+        }
+    }
+
+    async fn fetch(&self, bbox: &BoundingBox, ctx: &FetchContext) -> Result<ElevationData, String> {
+        let settings = &self.settings;
+        if !settings.resolution_m.is_finite()
+            || settings.resolution_m <= 0.0
+            || settings.max_gap_m.is_nan()
+            || settings.max_gap_m < 0.0
+        {
+            return Err(format!(
+                "Invalid LiDAR grid settings: resolution {} m, max gap {} m",
+                settings.resolution_m, settings.max_gap_m
+            ));
+        }
+
+        let files = collect_point_cloud_files(&settings.paths);
+        if files.is_empty() {
+            return Err("No .las or .laz files found in the selected paths".to_string());
+        }
+
+        let crs_override = settings.epsg.map(Crs::from_epsg).transpose()?;
+        let mut ground: Option<GroundGrid> = None;
+        for path in &files {
+            read_ground_points(path, crs_override, bbox, settings, &mut ground)?;
+        }
+
+        let ground = match ground {
+            Some(ground) if ground.points > 0 => ground,
+            _ => return Err(format!("{}: no ground points inside the selected area", self.name())),
+        };
+        println!(
+            "Gridding {} ground points into {}x{} cells ({:?})",
+            ground.points, ground.width, ground.height, settings.interpolation
+        );
+
+        let raster = grid_points(&ground, settings);
+        raster::rasters_to_height_grid(&[raster], bbox, ctx.meters_per_pixel, &self.name()).map(ElevationData::from)
+    }
+}
+
+// Файлы облаков точек: сами пути и всё .las/.laz внутри папок
+fn collect_point_cloud_files(paths: &[String]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let ext = entry
+                .path()
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            if ext == "las" || ext == "laz" {
+                files.push(entry.into_path());
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+// Сводит точки класса 2 (земля) из файла в ячейки сетки. Сетка создаётся по CRS первого
+// файла (область bbox с запасом max_gap_m), остальные файлы должны быть в той же CRS.
+fn read_ground_points(
+    path: &Path,
+    crs_override: Option<Crs>,
+    bbox: &BoundingBox,
+    settings: &LidarSettings,
+    ground: &mut Option<GroundGrid>,
+) -> Result<(), String> {
+    let mut reader =
+        Reader::from_path(path).map_err(|e| format!("Failed to open point cloud {}: {}", path.display(), e))?;
+
+    let keys = geo_keys(reader.header());
+    let crs = match (crs_override, &keys) {
+        (Some(crs), _) => crs,
+        (None, Some(keys)) => geotiff::crs_from_geo_keys(keys)
+            .map_err(|_| format!("{}: point cloud CRS has no EPSG code, set it explicitly", path.display()))?,
+        (None, None) => {
+            return Err(format!(
+                "{}: point cloud has no GeoKey CRS record, set EPSG explicitly",
+                path.display()
+            ))
+        }
+    };
+    let z_to_meters = vertical_scale(&keys.unwrap_or_default(), crs).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let ground = ground.get_or_insert_with(|| GroundGrid::new(projected_extent(bbox, crs, settings.max_gap_m), crs, settings));
+    if ground.crs != crs {
+        return Err(format!(
+            "{} uses {:?}, but other point clouds use {:?}",
+            path.display(),
+            crs,
+            ground.crs
+        ));
+    }
+
+    let bounds = reader.header().bounds();
+    let file_extent = Extent {
+        min_x: bounds.min.x,
+        min_y: bounds.min.y,
+        max_x: bounds.max.x,
+        max_y: bounds.max.y,
+    };
+    if !ground.extent.intersects(&file_extent) {
+        println!("Skipping {}: outside the selected area", path.display());
+        return Ok(());
+    }
+
+    let before = ground.points;
+    for point in reader.points() {
+        let point = point.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if point.classification == Classification::Ground {
+            ground.add(point.x, point.y, point.z * z_to_meters);
+            if ground.points > MAX_GROUND_POINTS {
+                return Err(format!(
+                    "More than {} ground points in the selected area, select a smaller area or thin the point clouds",
+                    MAX_GROUND_POINTS
+                ));
+            }
+        }
+    }
+    println!("Loaded {} ground points from {}", ground.points - before, path.display());
+
+    Ok(())
+}
+
+// GeoKeys из VLR LASF_Projection/34735; None, если записи нет
+fn geo_keys(header: &las::Header) -> Option<HashMap<u16, u16>> {
+    let vlr = header
+        .vlrs()
+        .iter()
+        .chain(header.evlrs())
+        .find(|v| v.user_id.trim_end_matches('\0') == LASF_PROJECTION && v.record_id == GEO_KEY_DIRECTORY_RECORD)?;
+
+    let directory: Vec<u16> = vlr
+        .data
+        .chunks_exact(2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    Some(geotiff::parse_geo_key_directory(&directory))
+}
+
+fn unit_to_meters(code: u16) -> Option<f64> {
+    match code {
+        UNIT_METRE => Some(1.0),
+        UNIT_FOOT => Some(0.3048),
+        UNIT_US_SURVEY_FOOT => Some(1200.0 / 3937.0),
+        _ => None,
+    }
+}
+
+// Множитель высот в метры. Поддерживаемые проекции - в метрах, поэтому облако
+// с другими горизонтальными единицами не принимаем. Без VerticalUnitsGeoKey высоты
+// в тех же единицах, что и координаты.
+fn vertical_scale(keys: &HashMap<u16, u16>, crs: Crs) -> Result<f64, String> {
+    if crs != Crs::Geographic {
+        if let Some(&code) = keys.get(&PROJ_LINEAR_UNITS_KEY) {
+            if code != UNIT_METRE {
+                return Err(format!(
+                    "horizontal units (EPSG unit {}) are not metres, reproject the point cloud to metres",
+                    code
+                ));
+            }
+        }
+    }
+    match keys.get(&VERTICAL_UNITS_KEY) {
+        None => Ok(1.0),
+        Some(&code) => unit_to_meters(code).ok_or_else(|| format!("unsupported vertical units (EPSG unit {})", code)),
+    }
+}
+
+// Описывающий прямоугольник bbox в CRS облака (по точкам вдоль границы, т.к.
+// в UTM стороны bbox не прямые)
+fn projected_extent(bbox: &BoundingBox, crs: Crs, margin: f64) -> Extent {
+    const STEPS: usize = 8;
+    let mut extent = Extent {
+        min_x: f64::INFINITY,
+        min_y: f64::INFINITY,
+        max_x: f64::NEG_INFINITY,
+        max_y: f64::NEG_INFINITY,
+    };
+    for i in 0..=STEPS {
+        for j in 0..=STEPS {
+            if i != 0 && i != STEPS && j != 0 && j != STEPS {
+                continue;
+            }
+            let lat = bbox.min_lat + (bbox.max_lat - bbox.min_lat) * i as f64 / STEPS as f64;
+            // За антимеридианом долгота идёт дальше 180° и возвращается в [-180, 180)
+            let lng = bbox.min_lng + bbox.lng_span() * j as f64 / STEPS as f64;
+            let lng = if lng >= 180.0 { lng - 360.0 } else { lng };
+            let (x, y) = crs.project(lat, lng);
+            extent.min_x = extent.min_x.min(x);
+            extent.min_y = extent.min_y.min(y);
+            extent.max_x = extent.max_x.max(x);
+            extent.max_y = extent.max_y.max(y);
+        }
+    }
+
+    let margin = meters_to_crs_units(crs, margin);
+    extent.min_x -= margin;
+    extent.min_y -= margin;
+    extent.max_x += margin;
+    extent.max_y += margin;
+    extent
+}
+
+// У географической CRS единицы - градусы (примерно, по меридиану)
+fn meters_to_crs_units(crs: Crs, meters: f64) -> f64 {
+    match crs {
+        Crs::Geographic => meters / 111_320.0,
+        _ => meters,
+    }
+}
+
+// Сетка DEM в CRS облака. Точки земли не хранятся: при чтении каждая сразу уходит
+// в среднюю высоту своей ячейки, так память не зависит от плотности облака.
+struct GroundGrid {
+    extent: Extent,
+    crs: Crs,
+    cell: f64,
+    width: usize,
+    height: usize,
+    mean_z: Vec<f32>,
+    count: Vec<u32>,
+    // Точки внутри сетки
+    points: u64,
+}
+
+impl GroundGrid {
+    fn new(extent: Extent, crs: Crs, settings: &LidarSettings) -> Self {
+        let span = (extent.max_x - extent.min_x).max(extent.max_y - extent.min_y);
+        let mut cell = meters_to_crs_units(crs, settings.resolution_m);
+        if span / cell > MAX_SOURCE_GRID as f64 {
+            cell = span / MAX_SOURCE_GRID as f64;
+            eprintln!(
+                "Warning: LiDAR grid limited to {} px, resolution lowered to {:.2}",
+                MAX_SOURCE_GRID, cell
+            );
+        }
+
+        let width = (((extent.max_x - extent.min_x) / cell).ceil() as usize).max(2);
+        let height = (((extent.max_y - extent.min_y) / cell).ceil() as usize).max(2);
+        GroundGrid {
+            extent,
+            crs,
+            cell,
+            width,
+            height,
+            mean_z: vec![0.0; width * height],
+            count: vec![0; width * height],
+            points: 0,
+        }
+    }
+
+    // Точки вне сетки пропускаются
+    fn add(&mut self, x: f64, y: f64, z: f64) {
+        let col = ((x - self.extent.min_x) / self.cell).floor();
+        let row = ((self.extent.max_y - y) / self.cell).floor();
+        if !(col >= 0.0 && row >= 0.0 && col < self.width as f64 && row < self.height as f64) {
+            return;
+        }
+        let i = row as usize * self.width + col as usize;
+        self.count[i] = self.count[i].saturating_add(1);
+        self.mean_z[i] += (z as f32 - self.mean_z[i]) / self.count[i] as f32;
+        self.points += 1;
+    }
+
+    fn center(&self, i: usize) -> (f64, f64) {
+        (
+            self.extent.min_x + ((i % self.width) as f64 + 0.5) * self.cell,
+            self.extent.max_y - ((i / self.width) as f64 + 0.5) * self.cell,
+        )
+    }
+}
+
+// Строит DEM: в ячейках с точками - средняя высота, пропуски не шире max_gap_m
+// интерполируются по соседним ячейкам, дальше - NaN
+fn grid_points(ground: &GroundGrid, settings: &LidarSettings) -> GeoRaster {
+    let (width, height) = (ground.width, ground.height);
+
+    // Расстояние (в ячейках) до ближайшей ячейки с точками
+    let empty: Vec<bool> = ground.count.iter().map(|&c| c == 0).collect();
+    let gap = blend::distance_to_invalid(&empty, width, height);
+    let max_gap = (meters_to_crs_units(ground.crs, settings.max_gap_m) / ground.cell) as f32;
+
+    let data: Vec<f32> = match settings.interpolation {
+        LidarInterpolation::Idw => (0..width * height)
+            .map(|i| {
+                if ground.count[i] > 0 {
+                    ground.mean_z[i]
+                } else if gap[i] > max_gap {
+                    f32::NAN
+                } else {
+                    idw_at(ground, i, gap[i].ceil() as usize + 1)
+                }
+            })
+            .collect(),
+        LidarInterpolation::Tin => {
+            let tin = build_tin(ground);
+            let interpolation = tin.barycentric();
+            (0..width * height)
+                .map(|i| {
+                    if ground.count[i] > 0 {
+                        return ground.mean_z[i];
+                    }
+                    if gap[i] > max_gap {
+                        return f32::NAN;
+                    }
+                    let (x, y) = ground.center(i);
+                    interpolation
+                        .interpolate(|v| v.data().z, Point2::new(x, y))
+                        .map(|z| z as f32)
+                        .unwrap_or(f32::NAN)
+                })
+                .collect()
+        }
+    };
+
+    let (extent, cell) = (ground.extent, ground.cell);
+    GeoRaster {
+        width,
+        height,
+        data,
+        transform: GeoTransform([extent.min_x, cell, 0.0, extent.max_y, 0.0, -cell]),
+        crs: ground.crs,
+    }
+}
+
+// Среднее с весами 1/d² по ячейкам с точками в окне radius ячеек вокруг ячейки i
+fn idw_at(ground: &GroundGrid, i: usize, radius: usize) -> f32 {
+    let (width, height) = (ground.width, ground.height);
+    let (col, row) = (i % width, i / width);
+    let mut sum = 0.0;
+    let mut weight = 0.0;
+    for r in row.saturating_sub(radius)..(row + radius + 1).min(height) {
+        for c in col.saturating_sub(radius)..(col + radius + 1).min(width) {
+            let j = r * width + c;
+            if ground.count[j] == 0 {
+                continue;
+            }
+            let d2 = ((r as f64 - row as f64).powi(2) + (c as f64 - col as f64).powi(2)) as f32;
+            sum += ground.mean_z[j] / d2;
+            weight += 1.0 / d2;
+        }
+    }
+    if weight > 0.0 {
+        sum / weight
+    } else {
+        f32::NAN
+    }
+}
+
+struct GroundVertex {
+    position: Point2<f64>,
+    z: f64,
+}
+
+impl HasPosition for GroundVertex {
+    type Scalar = f64;
+
+    fn position(&self) -> Point2<f64> {
+        self.position
+    }
+}
+
+// Триангуляция по центрам ячеек с точками: плотные облака иначе дают десятки
+// миллионов треугольников без выигрыша в точности сетки
+fn build_tin(ground: &GroundGrid) -> DelaunayTriangulation<GroundVertex> {
+    let vertices: Vec<GroundVertex> = (0..ground.width * ground.height)
+        .filter(|&i| ground.count[i] > 0)
+        .map(|i| {
+            let (x, y) = ground.center(i);
+            GroundVertex {
+                position: Point2::new(x, y),
+                z: ground.mean_z[i] as f64,
+            }
+        })
+        .collect();
+
+    DelaunayTriangulation::bulk_load(vertices).unwrap_or_else(|e| {
+        eprintln!("Failed to triangulate LiDAR points: {:?}", e);
+        DelaunayTriangulation::new()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utm() -> Crs {
+        Crs::Utm { zone: 32, north: true }
+    }
+
+    #[test]
+    fn feet_heights_are_converted() {
+        let keys = HashMap::from([(PROJ_LINEAR_UNITS_KEY, UNIT_METRE), (VERTICAL_UNITS_KEY, UNIT_US_SURVEY_FOOT)]);
+        assert!((vertical_scale(&keys, utm()).unwrap() - 0.3048006).abs() < 1e-6);
+        assert_eq!(vertical_scale(&HashMap::new(), utm()).unwrap(), 1.0);
+        let keys = HashMap::from([(VERTICAL_UNITS_KEY, 9036)]);
+        assert!(vertical_scale(&keys, utm()).is_err());
+    }
+
+    #[test]
+    fn feet_coordinates_are_rejected() {
+        let keys = HashMap::from([(PROJ_LINEAR_UNITS_KEY, UNIT_FOOT)]);
+        assert!(vertical_scale(&keys, utm()).is_err());
+    }
+
+    #[test]
+    fn points_are_averaged_per_cell() {
+        let extent = Extent { min_x: 0.0, min_y: 0.0, max_x: 10.0, max_y: 10.0 };
+        let settings = LidarSettings { resolution_m: 1.0, max_gap_m: 3.0, ..LidarSettings::default() };
+        let mut ground = GroundGrid::new(extent, utm(), &settings);
+        assert_eq!((ground.width, ground.height), (10, 10));
+
+        // Северо-западная ячейка - строка 0
+        ground.add(0.2, 9.8, 100.0);
+        ground.add(0.7, 9.1, 104.0);
+        ground.add(-1.0, 5.0, 999.0);
+        assert_eq!(ground.points, 2);
+        assert_eq!(ground.mean_z[0], 102.0);
+
+        // Ряд ячеек с восточным склоном и полосой пропуска посередине
+        for col in (0..10).filter(|c| !(4..6).contains(c)) {
+            for row in 0..10 {
+                ground.add(col as f64 + 0.5, row as f64 + 0.5, 200.0 + col as f64);
+            }
+        }
+        for interpolation in [LidarInterpolation::Idw, LidarInterpolation::Tin] {
+            let raster = grid_points(&ground, &LidarSettings { interpolation, ..settings.clone() });
+            let gap = raster.data[5 * 10 + 4];
+            assert!(gap > 203.0 && gap < 206.0, "{:?}: {}", interpolation, gap);
+            assert_eq!(raster.data[5 * 10 + 9], 209.0);
+        }
+
+        let raster = grid_points(&ground, &LidarSettings { max_gap_m: 0.5, ..settings });
+        assert!(raster.data[5 * 10 + 4].is_nan());
+    }
+
+    #[test]
+    fn extent_across_the_antimeridian_stays_narrow() {
+        // Зона 60 (центральный меридиан 177° в.д.), область 179.9° в.д. - 179.9° з.д.
+        let bbox = BoundingBox { min_lat: -17.0, min_lng: 179.9, max_lat: -16.9, max_lng: -179.9 };
+        let extent = projected_extent(&bbox, Crs::Utm { zone: 60, north: false }, 0.0);
+        // 0.2° по долготе на 17° ю.ш. - около 21 км
+        let width = extent.max_x - extent.min_x;
+        assert!((15_000.0..30_000.0).contains(&width), "{}", width);
+        assert!(extent.max_y - extent.min_y < 20_000.0);
+    }
+
+    #[tokio::test]
+    async fn cloud_without_ground_points_is_an_error() {
+        use las::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("no_ground.las");
+        let mut builder = las::Builder::from((1, 2));
+        builder.transforms = las::Vector {
+            x: las::Transform { scale: 0.01, offset: 0.0 },
+            y: las::Transform { scale: 0.01, offset: 0.0 },
+            z: las::Transform { scale: 0.01, offset: 0.0 },
+        };
+        let mut writer = las::Writer::from_path(&path, builder.into_header().unwrap()).unwrap();
+        // Растительность в центре области, точек земли нет
+        let (x, y) = utm().project(47.005, 9.005);
+        writer
+            .write(las::Point { x, y, z: 500.0, classification: Classification::HighVegetation, ..Default::default() })
+            .unwrap();
+        writer.close().unwrap();
+
+        let cache = crate::cache::DiskCache::open(&crate::cache::CacheSettings {
+            directory: Some(dir.path().join("cache").to_string_lossy().into_owned()),
+            offline: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let ctx = FetchContext {
+            client: reqwest::Client::new(),
+            zoom: 14,
+            meters_per_pixel: 5.0,
+            cache,
+            download: Default::default(),
+        };
+        let source = LidarSource {
+            settings: LidarSettings {
+                paths: vec![path.to_string_lossy().into_owned()],
+                epsg: Some(32632),
+                ..LidarSettings::default()
+            },
+        };
+        let bbox = BoundingBox { min_lat: 47.0, min_lng: 9.0, max_lat: 47.01, max_lng: 9.01 };
+        match source.fetch(&bbox, &ctx).await {
+            Err(err) => assert!(err.contains("no ground points"), "{}", err),
+            Ok(_) => panic!("a cloud without ground points must not produce a DEM"),
+        }
+    }
+}
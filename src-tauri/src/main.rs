@@ -1,10 +1,45 @@
 ﻿// src-tauri/src/main.rs - НОВАЯ ВЕРСИЯ С AWS TERRAIN TILES - ЧАСТЬ 1
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::path::PathBuf;
+mod analysis;
+mod ascii_grid;
+mod blend;
+mod border;
+mod buildings;
+mod cache;
+mod detail;
+mod download;
+mod elevation;
+mod erosion;
+mod filters;
+mod geotiff;
+mod hgt;
+mod lidar;
+mod opentopodata;
+mod raster;
+mod roads;
+mod ter;
+mod terrain;
+mod voids;
+mod water;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
-use reqwest;
-use tokio;
+use analysis::{AnalysisSettings, TerrainSummary};
+use border::BorderSettings;
+use buildings::{BuildingPadSettings, SteepBuilding};
+use cache::{CacheSettings, DiskCache};
+use detail::{DetailSettings, SourceResolution};
+use download::DownloadSettings;
+use elevation::{ElevationSourceConfig, FetchContext};
+use erosion::ErosionSettings;
+use filters::FilterStep;
+use roads::RoadSettings;
+use ter::{HeightScale, HeightSettings, TerrainLayers};
+use terrain::{HeightGrid, MercatorBounds, ResampleMethod};
+use voids::VoidFillMethod;
+use water::{WaterBody, WaterSettings};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct BoundingBox {
@@ -14,18 +49,204 @@ struct BoundingBox {
     max_lng: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// Предел широты Web Mercator (дальше тайлов нет)
+const MAX_MERCATOR_LAT: f64 = 85.05112878;
+// Насколько близко к ±180° должны лежать обе долготы области через антимеридиан
+const ANTIMERIDIAN_MARGIN: f64 = 10.0;
+
+impl BoundingBox {
+    // min_lng > max_lng означает область, пересекающую антимеридиан (180°)
+    fn crosses_antimeridian(&self) -> bool {
+        self.min_lng > self.max_lng
+    }
+
+    // Ширина по долготе с учётом перехода через антимеридиан (градусы)
+    fn lng_span(&self) -> f64 {
+        if self.crosses_antimeridian() {
+            self.max_lng - self.min_lng + 360.0
+        } else {
+            self.max_lng - self.min_lng
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let coords = [
+            ("min_lat", self.min_lat),
+            ("min_lng", self.min_lng),
+            ("max_lat", self.max_lat),
+            ("max_lng", self.max_lng),
+        ];
+        for (name, value) in coords {
+            if !value.is_finite() {
+                return Err(format!("Bounding box {} is not a valid number", name));
+            }
+        }
+        for (name, lat) in [("min_lat", self.min_lat), ("max_lat", self.max_lat)] {
+            if lat.abs() > MAX_MERCATOR_LAT {
+                return Err(format!(
+                    "Bounding box {} = {} is outside the Web Mercator range ±{:.2}°",
+                    name, lat, MAX_MERCATOR_LAT
+                ));
+            }
+        }
+        for (name, lng) in [("min_lng", self.min_lng), ("max_lng", self.max_lng)] {
+            if !(-180.0..=180.0).contains(&lng) {
+                return Err(format!("Bounding box {} = {} must be within ±180°", name, lng));
+            }
+        }
+        if self.min_lat >= self.max_lat {
+            return Err(format!(
+                "Bounding box min_lat ({}) must be less than max_lat ({})",
+                self.min_lat, self.max_lat
+            ));
+        }
+        if self.min_lng == self.max_lng {
+            return Err(format!("Bounding box has zero width (min_lng = max_lng = {})", self.min_lng));
+        }
+        // Через антимеридиан проходят только области у самой линии смены дат,
+        // остальные min_lng > max_lng - перепутанные запад и восток
+        if self.crosses_antimeridian()
+            && (self.min_lng < 180.0 - ANTIMERIDIAN_MARGIN || self.max_lng > ANTIMERIDIAN_MARGIN - 180.0)
+        {
+            return Err(format!(
+                "Bounding box longitudes look swapped: min_lng ({}) is east of max_lng ({}). \
+                 Only areas within {}° of the antimeridian may cross it",
+                self.min_lng, self.max_lng, ANTIMERIDIAN_MARGIN
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct GenerationProgress {
     stage: String,
     progress: f64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct GenerationSettings {
+    // Сторона рельефа BeamNG в ячейках: степень двойки от 512 до 8192
+    terrain_resolution: u32,
+    // Размер ячейки рельефа (м); None - рельеф покрывает выбранную область по длинной стороне
+    square_size: Option<f64>,
+    resample_method: ResampleMethod,
+    // Чем заполнять места без данных высот (недокачанные тайлы, пустоты DEM)
+    void_fill: VoidFillMethod,
+    // Желаемое разрешение исходных данных (м/пиксель); None - по размеру области
+    meters_per_pixel: Option<f64>,
+    // Максимальное количество тайлов высот для одной генерации
+    max_tiles: usize,
+    // Источники высот по убыванию приоритета: следующий заполняет то, где нет данных у предыдущих
+    elevation_sources: Vec<ElevationSourceConfig>,
+    // Ширина зоны плавного перехода между источниками (м)
+    blend_width_m: f64,
+    // Фильтры итоговой карты высот, применяются по порядку
+    filters: Vec<FilterStep>,
+    // Мелкий рельеф, если исходные данные грубее сетки рельефа
+    detail: DetailSettings,
+    // Обработка краёв карты и полоса без объектов
+    border: BorderSettings,
+    roads: RoadSettings,
+    building_pads: BuildingPadSettings,
+    water: WaterSettings,
+    // Эрозия после врезки дорог, площадок и воды
+    erosion: ErosionSettings,
+    // Отсчёт высот в уровне, вертикальное преувеличение и сдвиг
+    height: HeightSettings,
+    // Растры уклона, экспозиции, кривизны и шероховатости итогового рельефа
+    analysis: AnalysisSettings,
+    cache: CacheSettings,
+    download: DownloadSettings,
+    overpass: OverpassSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct OverpassSettings {
+    // Пробуются по порядку, пока один не ответит
+    endpoints: Vec<String>,
+    headers: HashMap<String, String>,
+    // Запрос к Overpass идёт намного дольше тайла (в запросе [timeout:180])
+    timeout_secs: u64,
+}
+
+impl Default for OverpassSettings {
+    fn default() -> Self {
+        OverpassSettings {
+            endpoints: vec!["https://overpass-api.de/api/interpreter".to_string()],
+            headers: HashMap::new(),
+            timeout_secs: 200,
+        }
+    }
+}
+
+impl Default for GenerationSettings {
+    fn default() -> Self {
+        GenerationSettings {
+            terrain_resolution: 2048,
+            square_size: None,
+            resample_method: ResampleMethod::Bicubic,
+            void_fill: VoidFillMethod::default(),
+            meters_per_pixel: None,
+            max_tiles: 64,
+            elevation_sources: vec![ElevationSourceConfig::default()],
+            blend_width_m: 100.0,
+            filters: Vec::new(),
+            detail: DetailSettings::default(),
+            border: BorderSettings::default(),
+            roads: RoadSettings::default(),
+            building_pads: BuildingPadSettings::default(),
+            water: WaterSettings::default(),
+            erosion: ErosionSettings::default(),
+            height: HeightSettings::default(),
+            analysis: AnalysisSettings::default(),
+            cache: CacheSettings::default(),
+            download: DownloadSettings::default(),
+            overpass: OverpassSettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ZoomChoice {
+    zoom: u32,
+    tile_count: usize,
+    // Разрешение тайлов на этом zoom в центре области (м/пиксель на местности)
+    meters_per_pixel: f64,
+    target_meters_per_pixel: f64,
+    // true, если zoom пришлось понизить из-за лимита тайлов
+    limited_by_max_tiles: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct GenerationResult {
+    message: String,
+    // Имена источников высот через " + " в порядке приоритета
+    elevation_source: String,
+    // Только для тайловых источников
+    terrain_zoom: Option<ZoomChoice>,
+    // Тайлы, которые не удалось получить; их область заполнена интерполяцией
+    missing_tiles: Vec<String>,
+    // Доля итоговой карты высот, заполненная интерполяцией (%)
+    void_filled_percent: f64,
+    // Размер ячейки рельефа (м), сторона карты = terrain_resolution * square_size
+    square_size: f64,
+    // Здания на склонах круче building_pads.max_slope_deg - площадка не выровнена
+    steep_buildings: Vec<SteepBuilding>,
+    // Уклоны и шероховатость итогового рельефа
+    terrain_summary: TerrainSummary,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct OSMElement {
     id: i64,
+    #[serde(rename = "type")]
     element_type: String,
     lat: Option<f64>,
     lon: Option<f64>,
+    #[serde(default)]
     tags: std::collections::HashMap<String, String>,
     nodes: Option<Vec<i64>>,
 }
@@ -34,278 +255,489 @@ struct OSMElement {
 async fn generate_terrain(
     bbox: BoundingBox,
     output_path: String,
+    settings: Option<GenerationSettings>,
     window: tauri::Window,
-) -> Result<String, String> {
+) -> Result<GenerationResult, String> {
+    let settings = settings.unwrap_or_default();
+    bbox.validate()?;
+    let (bbox, square_size) = terrain_extent(&bbox, &settings)?;
+    // Края проверяются сразу: object_margin_m нужен ещё до обработки рельефа
+    settings.border.validate(settings.terrain_resolution as f64 * square_size)?;
+
     let _ = window.emit("generation-progress", GenerationProgress {
         stage: "Initializing".to_string(),
         progress: 0.0,
     });
 
-    let _ = window.emit("generation-progress", GenerationProgress {
-        stage: "Downloading terrain data from AWS".to_string(),
-        progress: 10.0,
-    });
-    
-    let terrain_data = fetch_aws_terrain_tiles(&bbox).await
-        .map_err(|e| format!("Failed to fetch AWS terrain: {}", e))?;
+    let target_mpp = settings.meters_per_pixel.unwrap_or(square_size);
+    let zoom_choice = select_terrain_zoom(&bbox, target_mpp, settings.max_tiles)?;
+
+    if settings.elevation_sources.is_empty() || settings.elevation_sources.len() >= blend::NO_SOURCE as usize {
+        return Err(format!(
+            "Expected 1 to {} elevation sources, got {}",
+            blend::NO_SOURCE - 1,
+            settings.elevation_sources.len()
+        ));
+    }
+    let sources = settings
+        .elevation_sources
+        .iter()
+        .map(|config| config.build())
+        .collect::<Result<Vec<_>, _>>()?;
+    let source_names: Vec<String> = sources.iter().map(|s| s.name()).collect();
+    let any_tiled = sources.iter().any(|s| s.is_tiled());
+
+    let cache = DiskCache::open(&settings.cache)?;
+    let client = reqwest::Client::builder()
+        .user_agent("BeamNG-Terrain-Generator/1.0")
+        .build()
+        .map_err(|e| e.to_string())?;
+    let fetch_ctx = FetchContext {
+        client: client.clone(),
+        zoom: zoom_choice.zoom,
+        meters_per_pixel: target_mpp,
+        cache: cache.clone(),
+        download: settings.download.clone(),
+    };
+
+    let mut layers = Vec::with_capacity(sources.len());
+    let mut missing_tiles = Vec::new();
+    for (index, source) in sources.iter().enumerate() {
+        let stage = if source.is_tiled() {
+            format!(
+                "Downloading terrain data from {} (zoom {}, {} tiles)",
+                source.name(), zoom_choice.zoom, zoom_choice.tile_count
+            )
+        } else {
+            format!("Downloading terrain data from {}", source.name())
+        };
+        let _ = window.emit("generation-progress", GenerationProgress {
+            stage,
+            progress: 10.0 + 20.0 * index as f64 / sources.len() as f64,
+        });
+        
+        let data = source.fetch(&bbox, &fetch_ctx).await?;
+        missing_tiles.extend(data.missing_tiles);
+        layers.push(data.grid);
+    }
 
     let _ = window.emit("generation-progress", GenerationProgress {
         stage: "Fetching OpenStreetMap data".to_string(),
         progress: 30.0,
     });
     
-    let osm_data = fetch_osm_data(&bbox).await
+    let osm_data = fetch_osm_data(&bbox, &cache, &client, &settings).await
         .map_err(|e| format!("Failed to fetch OSM data: {}", e))?;
+    
+    cache.prune();
 
     let _ = window.emit("generation-progress", GenerationProgress {
         stage: "Processing terrain heightmap".to_string(),
         progress: 50.0,
     });
     
-    let heightmap = process_terrain_data(&terrain_data, &bbox)?;
+    // Шаг каждого источника на местности (м)
+    let center_lat = (bbox.min_lat + bbox.max_lat) / 2.0;
+    let layer_resolutions: Vec<f64> = layers
+        .iter()
+        .map(|l| {
+            let (dx, dy) = l.pixel_size();
+            dx.max(dy) * center_lat.to_radians().cos()
+        })
+        .collect();
+    
+    let (blended, source_resolution) = if layers.len() == 1 {
+        (blend::BlendedGrid::single(layers.pop().unwrap()), SourceResolution::uniform(layer_resolutions[0]))
+    } else {
+        // Ширина перехода в метрах на местности -> метры Web Mercator
+        let feather_width = settings.blend_width_m / center_lat.to_radians().cos();
+        let blended = blend::blend_sources(
+            &layers,
+            MercatorBounds::from_bbox(&bbox),
+            settings.resample_method,
+            feather_width,
+            // Мельче, чем вдвое от итоговой сетки, для смешивания не нужно
+            settings.terrain_resolution as usize * 2,
+        );
+        drop(layers);
+        let resolution = SourceResolution::from_source_map(
+            &blended.source_map,
+            blended.grid.width,
+            blended.grid.height,
+            &layer_resolutions,
+        );
+        (blended, resolution)
+    };
+    blend::save_source_map(&blended, &source_names, &PathBuf::from(&output_path).join("debug"))?;
+    let terrain_grid = blended.grid;
+    
+    let mut heightmap = process_terrain_data(
+        terrain_grid,
+        &bbox,
+        square_size,
+        &settings,
+        &osm_data,
+        &source_resolution,
+    )?;
+    // Заполненные пустоты остаются с valid = false (см. voids::fill_voids)
+    let void_filled_percent = heightmap.invalid_count() as f64 * 100.0 / heightmap.data.len() as f64;
 
     let _ = window.emit("generation-progress", GenerationProgress {
         stage: "Converting objects to BeamNG format".to_string(),
         progress: 70.0,
     });
     
-    let (beamng_objects, road_network) = convert_osm_to_beamng(&osm_data, &bbox)?;
+    let (mut beamng_objects, mut road_network) = convert_osm_to_beamng(&osm_data, &heightmap, square_size)?;
+    let map_size_m = heightmap.width as f64 * square_size;
+    remove_objects_in_margin(&mut beamng_objects, &mut road_network, map_size_m, settings.border.object_margin_m());
+
+    let _ = window.emit("generation-progress", GenerationProgress {
+        stage: "Carving roads and water into terrain".to_string(),
+        progress: 78.0,
+    });
+    
+    let carved = roads::carve_roads(&mut heightmap, &road_network, square_size, &settings.roads)?;
+    if carved.carved_cells > 0 {
+        roads::save_road_mask(&carved, heightmap.width, heightmap.height, &PathBuf::from(&output_path).join("debug"))?;
+    }
+    let mut protected = carved.protected;
+    
+    let pads = buildings::level_building_pads(
+        &mut heightmap,
+        &osm_data,
+        square_size,
+        &settings.building_pads,
+        settings.border.object_margin_m(),
+        &mut protected,
+    )?;
+    
+    let mut water = water::carve_water(&mut heightmap, &osm_data, square_size, &settings.water, &protected)?;
+    remove_water_in_margin(&mut water.bodies, map_size_m, settings.border.object_margin_m());
+    
+    if settings.erosion.enabled {
+        let _ = window.emit("generation-progress", GenerationProgress {
+            stage: "Eroding terrain".to_string(),
+            progress: 80.0,
+        });
+        // Полотно дорог, площадки и дно водоёмов эрозия не трогает
+        for (locked, &wet) in protected.iter_mut().zip(&water.wet) {
+            *locked |= wet;
+        }
+        erosion::erode(&mut heightmap, &settings.erosion, square_size, &protected)?;
+    }
+    
+    // Края карты - последними, чтобы дороги, площадки, вода и эрозия не переписали полосу
+    border::apply_border(&mut heightmap, &settings.border, square_size)?;
+    
+    // Растры итогового рельефа для этапов после него (материалы, растительность, здания)
+    let terrain_analysis = analysis::analyze(&heightmap, square_size);
+    if settings.analysis.export_debug {
+        analysis::save_debug_pngs(&terrain_analysis, &PathBuf::from(&output_path).join("debug"))?;
+    }
+    
+    // Масштаб высот считается по рельефу после всех изменений - тот же для .ter и объектов
+    let (min_h, max_h) = heightmap.min_max();
+    let height_scale = HeightScale::from_range(min_h, max_h, &settings.height)?;
+    roads::place_road_nodes(&mut road_network.nodes, &heightmap, square_size, height_scale);
+    place_objects_on_terrain(&mut beamng_objects, &heightmap, square_size, height_scale);
+    let mut water_bodies = water.bodies;
+    place_water_levels(&mut water_bodies, height_scale);
 
     let _ = window.emit("generation-progress", GenerationProgress {
         stage: "Generating BeamNG map files".to_string(),
         progress: 85.0,
     });
     
-    generate_beamng_files(&output_path, &heightmap, &beamng_objects, &road_network)?;
+    generate_beamng_files(
+        &output_path,
+        &heightmap,
+        square_size,
+        height_scale,
+        &beamng_objects,
+        &road_network,
+        &water_bodies,
+    )?;
 
     let _ = window.emit("generation-progress", GenerationProgress {
         stage: "Complete".to_string(),
         progress: 100.0,
     });
 
-    Ok(format!("Map generated successfully at: {}", output_path))
+    Ok(GenerationResult {
+        message: format!("Map generated successfully at: {}", output_path),
+        elevation_source: source_names.join(" + "),
+        terrain_zoom: if any_tiled { Some(zoom_choice) } else { None },
+        missing_tiles,
+        void_filled_percent,
+        square_size,
+        steep_buildings: pads.steep,
+        terrain_summary: terrain_analysis.summary(),
+    })
 }
 
-// НОВАЯ ФУНКЦИЯ: Загрузка напрямую из AWS Terrain Tiles
-async fn fetch_aws_terrain_tiles(bbox: &BoundingBox) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    // AWS Terrain Tiles доступны через несколько источников:
-    // 1. Mapzen Terrarium format (открытый источник)
-    // 2. Terrain-RGB от AWS
+const MIN_TERRAIN_SIZE: u32 = 512;
+const MAX_TERRAIN_SIZE: u32 = 8192;
+
+// Рельеф BeamNG квадратный: terrain_resolution * square_size метров на местности
+// с центром в центре выбранной области. Возвращает область рельефа и square_size.
+fn terrain_extent(bbox: &BoundingBox, settings: &GenerationSettings) -> Result<(BoundingBox, f64), String> {
+    let size = settings.terrain_resolution;
+    if !size.is_power_of_two() || !(MIN_TERRAIN_SIZE..=MAX_TERRAIN_SIZE).contains(&size) {
+        return Err(format!(
+            "Terrain size must be a power of two from {} to {}, got {}",
+            MIN_TERRAIN_SIZE, MAX_TERRAIN_SIZE, size
+        ));
+    }
     
-    // Используем Terrain Tiles из открытых источников
-    // Zoom level: чем выше, тем детальнее (рекомендуется 10-14)
-    let zoom = 12;
-    let tiles = calculate_tiles(bbox, zoom);
+    let bounds = MercatorBounds::from_bbox(bbox);
+    let center_lat = (bbox.min_lat + bbox.max_lat) / 2.0;
+    // Метров Web Mercator в метре на местности
+    let mercator_scale = 1.0 / center_lat.to_radians().cos();
     
-    let mut all_terrain_data = Vec::new();
-    let client = reqwest::Client::builder()
-        .user_agent("BeamNG-Terrain-Generator/1.0")
-        .build()?;
-
-    println!("Downloading {} terrain tiles from AWS...", tiles.len());
-
-    for (tile_x, tile_y) in tiles {
-        // Используем открытый источник AWS Terrain Tiles
-        // Terrarium format: https://registry.opendata.aws/terrain-tiles/
-        let url = format!(
-            "https://s3.amazonaws.com/elevation-tiles-prod/terrarium/{}/{}/{}.png",
-            zoom, tile_x, tile_y
-        );
-        
-        println!("Fetching tile: {}/{}/{}", zoom, tile_x, tile_y);
-        
-        match client.get(&url).send().await {
-            Ok(response) => {
-                if response.status().is_success() {
-                    let bytes = response.bytes().await?;
-                    all_terrain_data.extend_from_slice(&bytes);
-                    println!("✓ Downloaded tile {}/{}", tile_x, tile_y);
-                } else {
-                    eprintln!("Failed to download tile {}/{}: {}", tile_x, tile_y, response.status());
-                    // Создаём пустой тайл если не удалось загрузить
-                    all_terrain_data.extend_from_slice(&create_empty_tile());
-                }
-            }
-            Err(e) => {
-                eprintln!("Error downloading tile {}/{}: {}", tile_x, tile_y, e);
-                all_terrain_data.extend_from_slice(&create_empty_tile());
-            }
+    let square_size = match settings.square_size {
+        Some(square_size) if !square_size.is_finite() || square_size <= 0.0 => {
+            return Err(format!("Invalid terrain square size: {} m", square_size));
         }
+        Some(square_size) => square_size,
+        None => bounds.width().max(bounds.height()) / mercator_scale / size as f64,
+    };
+    
+    let half = size as f64 * square_size * mercator_scale / 2.0;
+    if half >= terrain::MERCATOR_HALF_EXTENT {
+        return Err(format!(
+            "Terrain of {} cells x {} m does not fit on the globe",
+            size, square_size
+        ));
     }
-
-    if all_terrain_data.is_empty() {
-        return Err("No terrain data downloaded".into());
-    }
-
-    Ok(all_terrain_data)
+    let cx = (bounds.min_x + bounds.max_x) / 2.0;
+    let cy = (bounds.min_y + bounds.max_y) / 2.0;
+    let (max_lat, min_lng) = terrain::mercator_to_lat_lng(cx - half, cy + half);
+    let (min_lat, max_lng) = terrain::mercator_to_lat_lng(cx + half, cy - half);
+    let extent = BoundingBox { min_lat, min_lng, max_lat, max_lng };
+    extent.validate()?;
+    
+    println!(
+        "Terrain {}x{} cells, {:.2} m/cell ({:.0} m across): {:.6}..{:.6} lat, {:.6}..{:.6} lng",
+        size, size, square_size, size as f64 * square_size,
+        extent.min_lat, extent.max_lat, extent.min_lng, extent.max_lng
+    );
+    
+    Ok((extent, square_size))
 }
 
-// Альтернативная функция: Использование Mapzen Terrarium (бесплатно)
-async fn fetch_mapzen_terrarium(bbox: &BoundingBox) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let zoom = 12;
-    let tiles = calculate_tiles(bbox, zoom);
+async fn fetch_osm_data(
+    bbox: &BoundingBox,
+    cache: &DiskCache,
+    client: &reqwest::Client,
+    settings: &GenerationSettings,
+) -> Result<Vec<OSMElement>, Box<dyn std::error::Error>> {
+    let filters = [
+        r#"way["building"]"#,
+        // Входы нужны с тегами, а узлы контуров ниже выдаются без них
+        r#"node["entrance"]"#,
+        r#"way["highway"]"#,
+        r#"node["natural"="tree"]"#,
+        r#"way["natural"="tree_row"]"#,
+        r#"node["highway"="bus_stop"]"#,
+        r#"way["amenity"]"#,
+        r#"way["natural"="water"]"#,
+        r#"way["waterway"~"^(river|stream|canal|riverbank)$"]"#,
+        r#"way["landuse"]"#,
+        r#"way["natural"~"^(bare_rock|scree|rock|heath|scrub|wood|grassland|sand|beach|wetland)$"]"#,
+    ];
     
-    let mut all_terrain_data = Vec::new();
-    let client = reqwest::Client::builder()
-        .user_agent("BeamNG-Terrain-Generator/1.0")
-        .build()?;
-
-    for (tile_x, tile_y) in tiles {
-        // Mapzen Terrarium - открытый источник elevation data
-        let url = format!(
-            "https://s3.amazonaws.com/elevation-tiles-prod/terrarium/{}/{}/{}.png",
-            zoom, tile_x, tile_y
-        );
-        
-        let response = client.get(&url).send().await?;
-        let bytes = response.bytes().await?;
-        all_terrain_data.extend_from_slice(&bytes);
-    }
-
-    Ok(all_terrain_data)
-}
-
-// Альтернативная функция: Использование OpenTopoData API (бесплатно, но медленнее)
-async fn fetch_opentopo_data(bbox: &BoundingBox) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>> {
-    // OpenTopoData API - бесплатный источник elevation данных
-    // Ограничение: 100 точек за запрос
-    
-    let resolution = 100; // Точек по каждой оси
-    let mut heightmap = vec![vec![0.0; resolution]; resolution];
-    
-    let lat_step = (bbox.max_lat - bbox.min_lat) / resolution as f64;
-    let lng_step = (bbox.max_lng - bbox.min_lng) / resolution as f64;
-    
-    let client = reqwest::Client::new();
-    
-    // Собираем точки батчами по 100
-    let mut locations = Vec::new();
-    for i in 0..resolution {
-        for j in 0..resolution {
-            let lat = bbox.min_lat + (i as f64 * lat_step);
-            let lng = bbox.min_lng + (j as f64 * lng_step);
-            locations.push(format!("{},{}", lat, lng));
-            
-            // Когда набралось 100 точек или это последняя итерация
-            if locations.len() >= 100 || (i == resolution - 1 && j == resolution - 1) {
-                let locations_param = locations.join("|");
-                let url = format!(
-                    "https://api.opentopodata.org/v1/aster30m?locations={}",
-                    locations_param
-                );
-                
-                let response = client.get(&url).send().await?;
-                let json: serde_json::Value = response.json().await?;
-                
-                if let Some(results) = json["results"].as_array() {
-                    for (idx, result) in results.iter().enumerate() {
-                        if let Some(elevation) = result["elevation"].as_f64() {
-                            let original_idx = (i * resolution + j) - (locations.len() - 1 - idx);
-                            let row = original_idx / resolution;
-                            let col = original_idx % resolution;
-                            if row < resolution && col < resolution {
-                                heightmap[row][col] = elevation as f32;
-                            }
-                        }
-                    }
-                }
-                
-                locations.clear();
-                
-                // Rate limiting: подождать между запросами
-                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-            }
+    // Область через антимеридиан запрашиваем двумя частями: до 180° и от -180°
+    let boxes = if bbox.crosses_antimeridian() {
+        vec![
+            (bbox.min_lat, bbox.min_lng, bbox.max_lat, 180.0),
+            (bbox.min_lat, -180.0, bbox.max_lat, bbox.max_lng),
+        ]
+    } else {
+        vec![(bbox.min_lat, bbox.min_lng, bbox.max_lat, bbox.max_lng)]
+    };
+    
+    let mut statements = String::new();
+    for (south, west, north, east) in &boxes {
+        for filter in &filters {
+            statements.push_str(&format!("          {}({},{},{},{});\n", filter, south, west, north, east));
         }
     }
     
-    Ok(heightmap)
-}
-
-fn create_empty_tile() -> Vec<u8> {
-    // Создаём пустой PNG тайл 256x256 с нулевой высотой
-    vec![0u8; 256 * 256 * 3]
-}
-
-async fn fetch_osm_data(bbox: &BoundingBox) -> Result<Vec<OSMElement>, Box<dyn std::error::Error>> {
     let query = format!(
-        r#"[out:json][timeout:180];
-        (
-          way["building"]({},{},{},{});
-          way["highway"]({},{},{},{});
-          node["natural"="tree"]({},{},{},{});
-          way["natural"="tree_row"]({},{},{},{});
-          node["highway"="bus_stop"]({},{},{},{});
-          way["amenity"]({},{},{},{});
-        );
-        out body;
-        >;
-        out skel qt;"#,
-        bbox.min_lat, bbox.min_lng, bbox.max_lat, bbox.max_lng,
-        bbox.min_lat, bbox.min_lng, bbox.max_lat, bbox.max_lng,
-        bbox.min_lat, bbox.min_lng, bbox.max_lat, bbox.max_lng,
-        bbox.min_lat, bbox.min_lng, bbox.max_lat, bbox.max_lng,
-        bbox.min_lat, bbox.min_lng, bbox.max_lat, bbox.max_lng,
-        bbox.min_lat, bbox.min_lng, bbox.max_lat, bbox.max_lng,
+        "[out:json][timeout:180];\n        (\n{}        );\n        out body;\n        >;\n        out skel qt;",
+        statements
     );
 
-    let client = reqwest::Client::new();
-    let response = client
-        .post("https://overpass-api.de/api/interpreter")
-        .body(query)
-        .send()
-        .await?;
+    let cache_key = DiskCache::osm_key(&query, bbox);
+    let body = match cache.get(cache::OSM, &cache_key) {
+        Some(body) => {
+            println!("Using cached OpenStreetMap data");
+            body
+        }
+        None if cache.is_offline() => {
+            return Err("OpenStreetMap data for this area is not in the cache (offline mode)".into());
+        }
+        None => {
+            let headers = download::header_map(&settings.overpass.headers)?;
+            let download_settings = DownloadSettings {
+                timeout_secs: settings.overpass.timeout_secs,
+                ..settings.download.clone()
+            };
+            let body = download::fetch_from_mirrors(
+                &settings.overpass.endpoints,
+                |url| client.post(url).headers(headers.clone()).body(query.clone()),
+                &download_settings,
+            )
+            .await
+            .map_err(|e| format!("Overpass request failed: {}", e))?;
+
+            // Кэшируем только ответ, который удалось разобрать
+            serde_json::from_slice::<serde_json::Value>(&body)?;
+            if let Err(e) = cache.put(cache::OSM, &cache_key, &body) {
+                eprintln!("Failed to cache OpenStreetMap data: {}", e);
+            }
+            body
+        }
+    };
 
-    let osm_json: serde_json::Value = response.json().await?;
+    let osm_json: serde_json::Value = serde_json::from_slice(&body)?;
     let elements: Vec<OSMElement> = serde_json::from_value(osm_json["elements"].clone())?;
     
     Ok(elements)
 }
 
+// Тайлы, покрывающие bbox. У области через антимеридиан x продолжается за 2^zoom - 1
+// (следующий столбец после последнего), при загрузке берётся x mod 2^zoom.
 fn calculate_tiles(bbox: &BoundingBox, zoom: u32) -> Vec<(u32, u32)> {
-    let min_tile = lat_lng_to_tile(bbox.min_lat, bbox.min_lng, zoom);
-    let max_tile = lat_lng_to_tile(bbox.max_lat, bbox.max_lng, zoom);
+    let (x0, y0, x1, y1) = tile_span(bbox, zoom);
     
     let mut tiles = Vec::new();
-    for x in min_tile.0..=max_tile.0 {
-        for y in min_tile.1..=max_tile.1 {
+    for x in x0..=x1 {
+        for y in y0..=y1 {
             tiles.push((x, y));
         }
     }
     tiles
 }
 
+// Углы прямоугольника тайлов: северо-западный и юго-восточный (y растёт на юг)
+fn tile_span(bbox: &BoundingBox, zoom: u32) -> (u32, u32, u32, u32) {
+    let (x0, y0) = lat_lng_to_tile(bbox.max_lat, bbox.min_lng, zoom);
+    let (mut x1, y1) = lat_lng_to_tile(bbox.min_lat, bbox.max_lng, zoom);
+    if bbox.crosses_antimeridian() {
+        x1 += 1 << zoom;
+    }
+    (x0, y0, x1, y1)
+}
+
+// Terrarium тайлы есть до zoom 15 (~4.7 м/пиксель на экваторе)
+const MAX_TERRAIN_ZOOM: u32 = 15;
+const TERRAIN_TILE_SIZE: f64 = 256.0;
+
+fn tile_meters_per_pixel(lat: f64, zoom: u32) -> f64 {
+    2.0 * terrain::MERCATOR_HALF_EXTENT * lat.to_radians().cos()
+        / (TERRAIN_TILE_SIZE * 2_f64.powi(zoom as i32))
+}
+
+fn count_tiles(bbox: &BoundingBox, zoom: u32) -> usize {
+    let (x0, y0, x1, y1) = tile_span(bbox, zoom);
+    (x1 - x0 + 1) as usize * (y1 - y0 + 1) as usize
+}
+
+// Самый грубый zoom, который ещё даёт target_mpp, но не больше max_tiles тайлов
+fn select_terrain_zoom(bbox: &BoundingBox, target_mpp: f64, max_tiles: usize) -> Result<ZoomChoice, String> {
+    if !target_mpp.is_finite() || target_mpp <= 0.0 {
+        return Err(format!("Invalid target resolution: {} m/px", target_mpp));
+    }
+    if max_tiles == 0 {
+        return Err("max_tiles must be at least 1".to_string());
+    }
+    
+    let center_lat = (bbox.min_lat + bbox.max_lat) / 2.0;
+    let mut zoom = (0..=MAX_TERRAIN_ZOOM)
+        .find(|&z| tile_meters_per_pixel(center_lat, z) <= target_mpp)
+        .unwrap_or(MAX_TERRAIN_ZOOM);
+    
+    let mut limited_by_max_tiles = false;
+    while zoom > 0 && count_tiles(bbox, zoom) > max_tiles {
+        zoom -= 1;
+        limited_by_max_tiles = true;
+    }
+    
+    let choice = ZoomChoice {
+        zoom,
+        tile_count: count_tiles(bbox, zoom),
+        meters_per_pixel: tile_meters_per_pixel(center_lat, zoom),
+        target_meters_per_pixel: target_mpp,
+        limited_by_max_tiles,
+    };
+    
+    println!(
+        "Terrain zoom {}: {} tiles, {:.1} m/px (target {:.1} m/px{})",
+        choice.zoom,
+        choice.tile_count,
+        choice.meters_per_pixel,
+        target_mpp,
+        if limited_by_max_tiles { ", limited by max_tiles" } else { "" }
+    );
+    
+    Ok(choice)
+}
+
 fn lat_lng_to_tile(lat: f64, lng: f64, zoom: u32) -> (u32, u32) {
     let n = 2_f64.powi(zoom as i32);
-    let x = ((lng + 180.0) / 360.0 * n) as u32;
-    let y = ((1.0 - (lat.to_radians().tan() + 1.0 / lat.to_radians().cos()).ln() / std::f64::consts::PI) / 2.0 * n) as u32;
-    (x, y)
+    let x = (lng + 180.0) / 360.0 * n;
+    let y = (1.0 - (lat.to_radians().tan() + 1.0 / lat.to_radians().cos()).ln() / std::f64::consts::PI) / 2.0 * n;
+    // lng = 180° и широты у края проекции дают n - оставляем последний тайл
+    (x.floor().clamp(0.0, n - 1.0) as u32, y.floor().clamp(0.0, n - 1.0) as u32)
 }
 
-fn process_terrain_data(data: &[u8], bbox: &BoundingBox) -> Result<Vec<Vec<f32>>, String> {
-    // Terrarium format decoding
-    // Height = (R * 256 + G + B / 256) - 32768
-    
-    let img = image::load_from_memory(data)
-        .map_err(|e| format!("Failed to load terrain image: {}", e))?;
+fn process_terrain_data(
+    mut mosaic: HeightGrid,
+    bbox: &BoundingBox,
+    square_size: f64,
+    settings: &GenerationSettings,
+    elements: &[OSMElement],
+    source_resolution: &SourceResolution,
+) -> Result<HeightGrid, String> {
+    // Сетка от источника высот уже привязана к Web Mercator
+    println!(
+        "Terrain source grid: {}x{} px, {:.1} m/px (Web Mercator)",
+        mosaic.width,
+        mosaic.height,
+        mosaic.pixel_size().0
+    );
     
-    let rgb = img.to_rgb8();
-    let (width, height) = rgb.dimensions();
+    // Тайлы покрывают больше, чем выбранная область - обрезаем ровно по BoundingBox
+    let bounds = MercatorBounds::from_bbox(bbox);
+    if !mosaic.bounds.contains(&bounds) {
+        eprintln!("Warning: terrain tiles do not fully cover the bounding box, edges will be clamped");
+    }
     
-    let mut heightmap = vec![vec![0.0; width as usize]; height as usize];
+    let resolution = settings.terrain_resolution as usize;
     
-    for y in 0..height {
-        for x in 0..width {
-            let pixel = rgb.get_pixel(x, y);
-            let r = pixel[0] as f32;
-            let g = pixel[1] as f32;
-            let b = pixel[2] as f32;
-            
-            // Terrarium format: height = (R * 256 + G + B / 256) - 32768
-            let height_meters = (r * 256.0 + g + b / 256.0) - 32768.0;
-            heightmap[y as usize][x as usize] = height_meters;
-        }
+    // Пустоты заполняем до пересчёта, чтобы интерполяция не тянула в себя нули
+    let filled = voids::fill_voids(&mut mosaic, settings.void_fill)?;
+    if filled > 0 {
+        println!(
+            "Filled {} void cells ({:.1}% of the source grid) using {:?}",
+            filled,
+            filled as f64 * 100.0 / mosaic.data.len() as f64,
+            settings.void_fill
+        );
     }
     
+    let mut heightmap = terrain::resample_to_bounds(
+        &mosaic,
+        bounds,
+        resolution,
+        resolution,
+        settings.resample_method,
+    );
+    
+    filters::apply_filters(&mut heightmap, &settings.filters, square_size)?;
+    detail::add_detail(&mut heightmap, elements, square_size, source_resolution, &settings.detail)?;
+    
     Ok(heightmap)
 }
 
@@ -333,6 +765,9 @@ struct RoadSegment {
     lanes: u32,
     road_type: String,
     one_way: bool,
+    // Мосты и тоннели в рельеф не врезаются
+    bridge: bool,
+    tunnel: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -343,7 +778,8 @@ struct RoadNetwork {
 
 fn convert_osm_to_beamng(
     elements: &[OSMElement],
-    bbox: &BoundingBox,
+    heightmap: &HeightGrid,
+    square_size: f64,
 ) -> Result<(Vec<BeamNGObject>, RoadNetwork), String> {
     let mut objects = Vec::new();
     let mut road_nodes = Vec::new();
@@ -367,7 +803,7 @@ fn convert_osm_to_beamng(
                     if let Some(&(lat, lon)) = node_positions.get(&first_node_id) {
                         objects.push(BeamNGObject {
                             obj_type: "building".to_string(),
-                            position: latlon_to_beamng(lat, lon, bbox),
+                            position: latlon_to_beamng(lat, lon, heightmap, square_size),
                             properties: tags.clone(),
                         });
                     }
@@ -379,7 +815,7 @@ fn convert_osm_to_beamng(
             if let (Some(lat), Some(lon)) = (element.lat, element.lon) {
                 objects.push(BeamNGObject {
                     obj_type: "tree".to_string(),
-                    position: latlon_to_beamng(lat, lon, bbox),
+                    position: latlon_to_beamng(lat, lon, heightmap, square_size),
                     properties: tags.clone(),
                 });
             }
@@ -389,7 +825,7 @@ fn convert_osm_to_beamng(
             if let (Some(lat), Some(lon)) = (element.lat, element.lon) {
                 objects.push(BeamNGObject {
                     obj_type: "bus_stop".to_string(),
-                    position: latlon_to_beamng(lat, lon, bbox),
+                    position: latlon_to_beamng(lat, lon, heightmap, square_size),
                     properties: tags.clone(),
                 });
             }
@@ -401,10 +837,12 @@ fn convert_osm_to_beamng(
                 let lanes = parse_lanes(tags.get("lanes"));
                 let width = calculate_road_width(&highway_type, lanes);
                 let one_way = tags.get("oneway") == Some(&"yes".to_string());
+                let bridge = tags.get("bridge").is_some_and(|v| v != "no");
+                let tunnel = tags.get("tunnel").is_some_and(|v| v != "no");
                 
                 for (i, &node_id) in nodes.iter().enumerate() {
                     if let Some(&(lat, lon)) = node_positions.get(&node_id) {
-                        let node_pos = latlon_to_beamng(lat, lon, bbox);
+                        let node_pos = latlon_to_beamng(lat, lon, heightmap, square_size);
                         
                         road_nodes.push(RoadNode {
                             id: format!("node_{}_{}", element.id, node_id),
@@ -423,6 +861,8 @@ fn convert_osm_to_beamng(
                                 lanes,
                                 road_type: highway_type.clone(),
                                 one_way,
+                                bridge,
+                                tunnel,
                             });
                         }
                     }
@@ -459,23 +899,100 @@ fn calculate_road_width(highway_type: &str, lanes: u32) -> f32 {
     }
 }
 
-fn latlon_to_beamng(lat: f64, lon: f64, bbox: &BoundingBox) -> (f32, f32, f32) {
-    let x = ((lon - bbox.min_lng) / (bbox.max_lng - bbox.min_lng) * 2048.0) as f32;
+// Та же проекция (Web Mercator), что и у сетки высот: 1 пиксель = square_size метров уровня
+fn latlon_to_beamng(lat: f64, lon: f64, heightmap: &HeightGrid, square_size: f64) -> (f32, f32, f32) {
+    let bounds = &heightmap.bounds;
+    let (mx, my) = bounds.project(lat, lon);
+    let x = ((mx - bounds.min_x) / bounds.width() * heightmap.width as f64 * square_size) as f32;
     let y = 0.0;
-    let z = ((lat - bbox.min_lat) / (bbox.max_lat - bbox.min_lat) * 2048.0) as f32;
+    let z = ((my - bounds.min_y) / bounds.height() * heightmap.height as f64 * square_size) as f32;
     (x, y, z)
 }
 
+// Обратно к latlon_to_beamng: координаты уровня -> дробные ячейки сетки
+fn beamng_to_grid(x: f32, z: f32, heightmap: &HeightGrid, square_size: f64) -> (f64, f64) {
+    (
+        x as f64 / square_size - 0.5,
+        heightmap.height as f64 - z as f64 / square_size - 0.5,
+    )
+}
+
+// Убирает объекты и узлы дорог у края карты вместе с отрезками дорог, которые к ним идут
+fn remove_objects_in_margin(objects: &mut Vec<BeamNGObject>, network: &mut RoadNetwork, map_size_m: f64, margin_m: f64) {
+    if margin_m <= 0.0 {
+        return;
+    }
+    let before = objects.len();
+    objects.retain(|o| !border::in_margin(o.position.0, o.position.2, map_size_m, margin_m));
+    let dropped: std::collections::HashSet<String> = network
+        .nodes
+        .iter()
+        .filter(|n| border::in_margin(n.position.0, n.position.2, map_size_m, margin_m))
+        .map(|n| n.id.clone())
+        .collect();
+    network.nodes.retain(|n| !dropped.contains(&n.id));
+    network
+        .segments
+        .retain(|s| !dropped.contains(&s.start_node) && !dropped.contains(&s.end_node));
+    println!(
+        "Border margin {} m: removed {} objects and {} road nodes",
+        margin_m,
+        before - objects.len(),
+        dropped.len()
+    );
+}
+
+// Уровни воды в полосе у края взяты до обработки края - такие точки убираем,
+// водоём без точек (или линия из одной точки) убирается целиком
+fn remove_water_in_margin(bodies: &mut Vec<WaterBody>, map_size_m: f64, margin_m: f64) {
+    if margin_m <= 0.0 {
+        return;
+    }
+    let before = bodies.len();
+    let mut dropped = 0;
+    for body in bodies.iter_mut() {
+        let count = body.points.len();
+        body.points.retain(|p| !border::in_margin(p.x, p.z, map_size_m, margin_m));
+        dropped += count - body.points.len();
+    }
+    bodies.retain(|b| b.points.len() >= 2);
+    println!(
+        "Border margin {} m: removed {} water points and {} water bodies",
+        margin_m,
+        dropped,
+        before - bodies.len()
+    );
+}
+
+// Высота объектов = высота рельефа под ними в уровне (тот же масштаб, что у .ter)
+fn place_objects_on_terrain(objects: &mut [BeamNGObject], heightmap: &HeightGrid, square_size: f64, scale: HeightScale) {
+    for object in objects {
+        let (fx, fy) = beamng_to_grid(object.position.0, object.position.2, heightmap, square_size);
+        object.position.1 = scale.level_height(heightmap.sample(fx, fy, ResampleMethod::Bilinear));
+    }
+}
+
+// Уровни воды тем же масштабом, что рельеф и объекты
+fn place_water_levels(bodies: &mut [WaterBody], scale: HeightScale) {
+    for point in bodies.iter_mut().flat_map(|b| b.points.iter_mut()) {
+        point.surface_m = scale.level_height(point.surface_m);
+    }
+}
+
+// Высота точки появления и камеры превью над рельефом (м)
+const SPAWN_CLEARANCE_M: f32 = 2.0;
+const PREVIEW_HEIGHT_M: f32 = 100.0;
+
 fn generate_beamng_files(
     output_path: &str,
-    heightmap: &[Vec<f32>],
+    heightmap: &HeightGrid,
+    square_size: f64,
+    height_scale: HeightScale,
     objects: &[BeamNGObject],
     road_network: &RoadNetwork,
+    water_bodies: &[WaterBody],
 ) -> Result<(), String> {
     use std::fs;
-    use std::io::Write;
-    use zip::write::FileOptions;
-    use zip::ZipWriter;
     
     let path = PathBuf::from(output_path);
     fs::create_dir_all(&path).map_err(|e| e.to_string())?;
@@ -491,14 +1008,16 @@ fn generate_beamng_files(
     fs::create_dir_all(&art_terrains_path).map_err(|e| e.to_string())?;
     
     generate_mod_info(&mod_path, mod_name)?;
-    generate_main_level(&level_path, mod_name)?;
-    generate_items_level(&level_path, objects)?;
+    // Центр карты в координатах уровня - для превью и точки появления
+    let center = (heightmap.width as f64 * square_size / 2.0) as f32;
+    let (fx, fy) = beamng_to_grid(center, center, heightmap, square_size);
+    let ground = height_scale.level_height(heightmap.sample(fx, fy, ResampleMethod::Bilinear));
+    generate_main_level(&level_path, mod_name, center, ground)?;
+    let terrain_block =
+        generate_terrain_files(&level_path, &art_terrains_path, mod_name, heightmap, square_size, height_scale)?;
+    generate_items_level(&level_path, objects, terrain_block)?;
     generate_road_files(&level_path, road_network)?;
-    
-    let heightmap_path = art_terrains_path.join("terrain.png");
-    save_heightmap_as_png(heightmap, &heightmap_path)?;
-    
-    generate_terrain_files(&art_terrains_path, heightmap)?;
+    water::save_water_levels(water_bodies, &level_path)?;
     generate_preview_image(&level_path)?;
     
     let zip_path = path.join(format!("{}.zip", mod_name));
@@ -507,7 +1026,7 @@ fn generate_beamng_files(
     Ok(())
 }
 
-fn generate_mod_info(mod_path: &PathBuf, mod_name: &str) -> Result<(), String> {
+fn generate_mod_info(mod_path: &Path, mod_name: &str) -> Result<(), String> {
     use std::fs::File;
     use std::io::Write;
     
@@ -528,7 +1047,8 @@ fn generate_mod_info(mod_path: &PathBuf, mod_name: &str) -> Result<(), String> {
     Ok(())
 }
 
-fn generate_main_level(level_path: &PathBuf, mod_name: &str) -> Result<(), String> {
+// Высота - второй компонент, как у TerrainBlock и объектов
+fn generate_main_level(level_path: &Path, mod_name: &str, center: f32, ground: f32) -> Result<(), String> {
     use std::fs::File;
     use std::io::Write;
     
@@ -541,7 +1061,7 @@ fn generate_main_level(level_path: &PathBuf, mod_name: &str) -> Result<(), Strin
             "biome": "Urban",
             "previews": ["preview.jpg"],
             "previewPosition": {
-                "pos": [1024.0, 1024.0, 100.0],
+                "pos": [center, ground + PREVIEW_HEIGHT_M, center],
                 "rot": [0, 0, 1, 0]
             }
         },
@@ -550,7 +1070,7 @@ fn generate_main_level(level_path: &PathBuf, mod_name: &str) -> Result<(), Strin
             "spawnPoints": [
                 {
                     "objectname": "spawn_0",
-                    "pos": [1024.0, 1024.0, 105.0],
+                    "pos": [center, ground + SPAWN_CLEARANCE_M, center],
                     "rot": [0, 0, 1, 0],
                     "rotationMatrix": [[1,0,0],[0,1,0],[0,0,1]]
                 }
@@ -572,21 +1092,27 @@ fn generate_main_level(level_path: &PathBuf, mod_name: &str) -> Result<(), Strin
     Ok(())
 }
 
-fn generate_items_level(level_path: &PathBuf, objects: &[BeamNGObject]) -> Result<(), String> {
+fn generate_items_level(
+    level_path: &Path,
+    objects: &[BeamNGObject],
+    terrain_block: serde_json::Value,
+) -> Result<(), String> {
     use std::fs::File;
     use std::io::Write;
     
+    let mut level_objects = vec![terrain_block];
+    level_objects.extend(objects.iter().enumerate().map(|(i, obj)| {
+        serde_json::json!({
+            "class": get_beamng_object_class(&obj.obj_type),
+            "persistentId": format!("{}_{}", obj.obj_type, i),
+            "position": [obj.position.0, obj.position.1, obj.position.2],
+            "rotation": [0, 0, 1, 0],
+            "scale": [1, 1, 1],
+            "__gameObjectId": i + 1000
+        })
+    }));
     let items = serde_json::json!({
-        "objects": objects.iter().enumerate().map(|(i, obj)| {
-            serde_json::json!({
-                "class": get_beamng_object_class(&obj.obj_type),
-                "persistentId": format!("{}_{}", obj.obj_type, i),
-                "position": [obj.position.0, obj.position.1, obj.position.2],
-                "rotation": [0, 0, 1, 0],
-                "scale": [1, 1, 1],
-                "__gameObjectId": i + 1000
-            })
-        }).collect::<Vec<_>>()
+        "objects": level_objects
     });
     
     let items_path = level_path.join("items.level.json");
@@ -606,7 +1132,7 @@ fn get_beamng_object_class(obj_type: &str) -> &str {
     }
 }
 
-fn generate_road_files(level_path: &PathBuf, road_network: &RoadNetwork) -> Result<(), String> {
+fn generate_road_files(level_path: &Path, road_network: &RoadNetwork) -> Result<(), String> {
     use std::fs::File;
     use std::io::Write;
     
@@ -698,26 +1224,67 @@ fn get_road_material(road_type: &str) -> &str {
     }
 }
 
-fn generate_terrain_files(art_terrains_path: &PathBuf, heightmap: &[Vec<f32>]) -> Result<(), String> {
+const TERRAIN_NAME: &str = "terrain";
+const TERRAIN_MATERIAL: &str = "Grass";
+
+// terrain.ter + terrain.terrain.json в корне уровня, 16-битный PNG и материалы в art/terrains.
+// Возвращает TerrainBlock для items.level.json.
+fn generate_terrain_files(
+    level_path: &Path,
+    art_terrains_path: &Path,
+    mod_name: &str,
+    heightmap: &HeightGrid,
+    square_size: f64,
+    scale: HeightScale,
+) -> Result<serde_json::Value, String> {
     use std::fs::File;
     use std::io::Write;
     
-    let ter_json = serde_json::json!({
-        "terrainSize": 2048,
-        "squareSize": 1.0,
-        "heightScale": 256.0,
-        "heightMap": "terrain.png"
-    });
+    let layers = TerrainLayers::single(heightmap.width, TERRAIN_MATERIAL);
+    println!(
+        "Terrain heights from {:.1} m at level height {:.1} m, maxHeight {:.1} m ({:.3} m per step)",
+        scale.base,
+        scale.level_base,
+        scale.max_height,
+        scale.max_height / u16::MAX as f32
+    );
     
-    let ter_path = art_terrains_path.join("terrain.ter.json");
-    let mut file = File::create(ter_path).map_err(|e| e.to_string())?;
-    file.write_all(serde_json::to_string_pretty(&ter_json).unwrap().as_bytes())
+    let datafile = format!("/levels/{}/{}.ter", mod_name, TERRAIN_NAME);
+    let heightmap_image = format!("/levels/{}/art/terrains/{}.png", mod_name, TERRAIN_NAME);
+    
+    ter::write_ter(&level_path.join(format!("{}.ter", TERRAIN_NAME)), heightmap, scale, &layers)?;
+    ter::save_heightmap_png16(&art_terrains_path.join(format!("{}.png", TERRAIN_NAME)), heightmap, scale)?;
+    
+    let terrain_json = ter::terrain_json(&datafile, &heightmap_image, heightmap.width, &layers);
+    let json_path = level_path.join(format!("{}.terrain.json", TERRAIN_NAME));
+    let mut file = File::create(json_path).map_err(|e| e.to_string())?;
+    file.write_all(serde_json::to_string_pretty(&terrain_json).unwrap().as_bytes())
         .map_err(|e| e.to_string())?;
     
-    Ok(())
+    let materials = serde_json::json!({
+        TERRAIN_MATERIAL: {
+            "name": TERRAIN_MATERIAL,
+            "internalName": TERRAIN_MATERIAL,
+            "class": "TerrainMaterial",
+            "groundmodelName": "GRASS"
+        }
+    });
+    let materials_path = art_terrains_path.join("main.materials.json");
+    let mut file = File::create(materials_path).map_err(|e| e.to_string())?;
+    file.write_all(serde_json::to_string_pretty(&materials).unwrap().as_bytes())
+        .map_err(|e| e.to_string())?;
+    
+    Ok(serde_json::json!({
+        "class": "TerrainBlock",
+        "persistentId": TERRAIN_NAME,
+        "terrainFile": datafile,
+        "squareSize": square_size,
+        "maxHeight": scale.max_height,
+        "position": [0.0, scale.level_base, 0.0]
+    }))
 }
 
-fn generate_preview_image(level_path: &PathBuf) -> Result<(), String> {
+fn generate_preview_image(level_path: &Path) -> Result<(), String> {
     let img = image::ImageBuffer::from_fn(512, 512, |x, y| {
         let r = ((x as f32 / 512.0) * 255.0) as u8;
         let g = ((y as f32 / 512.0) * 255.0) as u8;
@@ -731,28 +1298,6 @@ fn generate_preview_image(level_path: &PathBuf) -> Result<(), String> {
     Ok(())
 }
 
-fn save_heightmap_as_png(heightmap: &[Vec<f32>], path: &PathBuf) -> Result<(), String> {
-    let height = heightmap.len() as u32;
-    let width = heightmap[0].len() as u32;
-    
-    let mut img = image::GrayImage::new(width, height);
-    
-    let min_h = heightmap.iter().flatten().fold(f32::INFINITY, |a, &b| a.min(b));
-    let max_h = heightmap.iter().flatten().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
-    let range = max_h - min_h;
-    
-    for y in 0..height {
-        for x in 0..width {
-            let h = heightmap[y as usize][x as usize];
-            let normalized = ((h - min_h) / range * 255.0) as u8;
-            img.put_pixel(x, y, image::Luma([normalized]));
-        }
-    }
-    
-    img.save(path).map_err(|e| e.to_string())?;
-    Ok(())
-}
-
 // src-tauri/src/main.rs - ЧАСТЬ 3 (финальная часть)
 // ВАЖНО: Вставьте эту часть ПОСЛЕ части 2!
 
@@ -792,4 +1337,189 @@ fn main() {
         .invoke_handler(tauri::generate_handler![generate_terrain])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bbox(min_lat: f64, min_lng: f64, max_lat: f64, max_lng: f64) -> BoundingBox {
+        BoundingBox { min_lat, min_lng, max_lat, max_lng }
+    }
+
+    #[test]
+    fn coarsest_zoom_meeting_the_target_is_chosen() {
+        let area = bbox(47.0, 8.0, 47.1, 8.2);
+        let choice = select_terrain_zoom(&area, 20.0, 1000).unwrap();
+        assert!(choice.meters_per_pixel <= 20.0);
+        assert!(tile_meters_per_pixel(47.05, choice.zoom - 1) > 20.0);
+        assert_eq!(choice.tile_count, count_tiles(&area, choice.zoom));
+        assert!(!choice.limited_by_max_tiles);
+
+        // Мельче, чем даёт MAX_TERRAIN_ZOOM, не бывает
+        let finest = select_terrain_zoom(&area, 0.01, usize::MAX).unwrap();
+        assert_eq!(finest.zoom, MAX_TERRAIN_ZOOM);
+    }
+
+    #[test]
+    fn zoom_steps_down_to_fit_max_tiles() {
+        let area = bbox(47.0, 8.0, 47.1, 8.2);
+        let unlimited = select_terrain_zoom(&area, 2.0, usize::MAX).unwrap();
+        assert!(unlimited.tile_count > 4);
+
+        let choice = select_terrain_zoom(&area, 2.0, 4).unwrap();
+        assert!(choice.limited_by_max_tiles);
+        assert!(choice.zoom < unlimited.zoom);
+        assert!(choice.tile_count <= 4);
+        assert!(count_tiles(&area, choice.zoom + 1) > 4);
+        assert_eq!(choice.target_meters_per_pixel, 2.0);
+    }
+
+    #[test]
+    fn invalid_limits_are_rejected() {
+        let area = bbox(47.0, 8.0, 47.1, 8.2);
+        assert!(select_terrain_zoom(&area, 20.0, 0).is_err());
+        for target in [0.0, -5.0, f64::NAN, f64::INFINITY] {
+            assert!(select_terrain_zoom(&area, target, 64).is_err(), "{}", target);
+        }
+    }
+
+    #[test]
+    fn swapped_longitudes_are_rejected() {
+        let err = bbox(50.0, 30.5, 50.1, 30.3).validate().unwrap_err();
+        assert!(err.contains("look swapped"), "{}", err);
+        assert!(bbox(50.0, 30.3, 50.1, 30.5).validate().is_ok());
+        // Узкая по размаху (160°), но далеко от линии смены дат
+        assert!(bbox(50.0, 100.0, 50.1, -100.0).validate().is_err());
+        assert!(bbox(50.0, 175.0, 50.1, -165.0).validate().is_err());
+    }
+
+    #[test]
+    fn narrow_antimeridian_box_is_accepted() {
+        let b = bbox(-17.0, 179.9, -16.9, -179.9);
+        assert!(b.validate().is_ok());
+        assert!(b.crosses_antimeridian());
+        assert!((b.lng_span() - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn invalid_latitudes_are_rejected() {
+        assert!(bbox(50.1, 30.3, 50.0, 30.5).validate().is_err());
+        assert!(bbox(86.0, 30.3, 86.1, 30.5).validate().is_err());
+        assert!(bbox(f64::NAN, 30.3, 50.1, 30.5).validate().is_err());
+    }
+
+    #[test]
+    fn water_levels_use_the_height_scale() {
+        let settings = HeightSettings {
+            vertical_exaggeration: 2.0,
+            offset_m: 10.0,
+            ..HeightSettings::default()
+        };
+        let scale = HeightScale::from_range(100.0, 150.0, &settings).unwrap();
+        let mut bodies = vec![WaterBody {
+            osm_id: 1,
+            kind: water::WaterKind::Lake,
+            points: vec![water::WaterPoint { x: 0.0, z: 0.0, surface_m: 120.0, width_m: 0.0 }],
+        }];
+        place_water_levels(&mut bodies, scale);
+        assert_eq!(bodies[0].points[0].surface_m, 50.0);
+    }
+
+    #[test]
+    fn water_in_the_border_margin_is_removed() {
+        let point = |x: f32, z: f32| water::WaterPoint { x, z, surface_m: 100.0, width_m: 5.0 };
+        let body = |osm_id: i64, points: Vec<water::WaterPoint>| WaterBody {
+            osm_id,
+            kind: water::WaterKind::River,
+            points,
+        };
+        let mut bodies = vec![
+            // Река через всю карту - остаётся часть вне полосы
+            body(1, vec![point(5.0, 100.0), point(50.0, 100.0), point(150.0, 100.0), point(195.0, 100.0)]),
+            // Ручей целиком у края
+            body(2, vec![point(10.0, 10.0), point(15.0, 30.0)]),
+            // Ручей, у которого вне полосы только одна точка
+            body(3, vec![point(100.0, 5.0), point(100.0, 30.0)]),
+        ];
+        remove_water_in_margin(&mut bodies, 200.0, 20.0);
+
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0].osm_id, 1);
+        let xs: Vec<f32> = bodies[0].points.iter().map(|p| p.x).collect();
+        assert_eq!(xs, vec![50.0, 150.0]);
+
+        // Без полосы ничего не меняется
+        let mut bodies = vec![body(2, vec![point(10.0, 10.0), point(15.0, 30.0)])];
+        remove_water_in_margin(&mut bodies, 200.0, 0.0);
+        assert_eq!(bodies[0].points.len(), 2);
+    }
+
+    #[test]
+    fn tiles_at_world_edges() {
+        assert_eq!(lat_lng_to_tile(0.0, 0.0, 1), (1, 1));
+        assert_eq!(lat_lng_to_tile(0.0, 180.0, 3), (7, 4));
+        assert_eq!(lat_lng_to_tile(MAX_MERCATOR_LAT, -180.0, 3), (0, 0));
+        assert_eq!(lat_lng_to_tile(-MAX_MERCATOR_LAT, 179.99, 3), (7, 7));
+    }
+
+    #[test]
+    fn tile_span_wraps_across_antimeridian() {
+        let b = bbox(-17.0, 179.9, -16.9, -179.9);
+        let (x0, _, x1, _) = tile_span(&b, 8);
+        assert_eq!((x0, x1), (255, 256));
+        assert_eq!(calculate_tiles(&b, 8).iter().map(|t| t.0).max(), Some(256));
+
+        let (x0, y0, x1, y1) = tile_span(&bbox(47.0, 8.0, 47.1, 8.2), 12);
+        assert!(x0 < x1 && y0 <= y1);
+        assert_eq!(count_tiles(&bbox(47.0, 8.0, 47.1, 8.2), 12), ((x1 - x0 + 1) * (y1 - y0 + 1)) as usize);
+    }
+
+    fn sized(terrain_resolution: u32, square_size: Option<f64>) -> GenerationSettings {
+        GenerationSettings { terrain_resolution, square_size, ..GenerationSettings::default() }
+    }
+
+    #[test]
+    fn terrain_size_must_be_a_supported_power_of_two() {
+        let area = bbox(50.0, 30.3, 50.1, 30.5);
+        for size in [0, 256, 1000, 3000, 16384] {
+            let err = terrain_extent(&area, &sized(size, None)).unwrap_err();
+            assert!(err.contains("power of two"), "{}", err);
+        }
+        for size in [512, 1024, 8192] {
+            assert!(terrain_extent(&area, &sized(size, None)).is_ok());
+        }
+    }
+
+    #[test]
+    fn square_size_defaults_to_the_longer_side() {
+        // На экваторе 0.02° долготы ~ 2226 м, широта вдвое уже
+        let area = bbox(0.0, 0.0, 0.01, 0.02);
+        let (extent, square_size) = terrain_extent(&area, &sized(1024, None)).unwrap();
+        let expected = 0.02 * terrain::MERCATOR_HALF_EXTENT / 180.0 / 1024.0;
+        assert!((square_size / expected - 1.0).abs() < 1e-6, "{}", square_size);
+        // Квадрат накрывает длинную сторону целиком и расширяет короткую
+        assert!((extent.lng_span() - 0.02).abs() < 1e-9);
+        assert!((extent.max_lat - extent.min_lat - 0.02).abs() < 1e-6);
+        assert!(((extent.min_lat + extent.max_lat) / 2.0 - 0.005).abs() < 1e-6);
+    }
+
+    #[test]
+    fn explicit_square_size_sets_the_extent() {
+        let area = bbox(0.0, 0.0, 0.01, 0.02);
+        let (extent, square_size) = terrain_extent(&area, &sized(512, Some(2.0))).unwrap();
+        assert_eq!(square_size, 2.0);
+        let across = extent.lng_span() * terrain::MERCATOR_HALF_EXTENT / 180.0;
+        assert!((across / 1024.0 - 1.0).abs() < 1e-6, "{}", across);
+
+        assert!(terrain_extent(&area, &sized(512, Some(0.0))).is_err());
+        assert!(terrain_extent(&area, &sized(512, Some(f64::NAN))).is_err());
+    }
+
+    #[test]
+    fn terrain_larger_than_the_globe_is_rejected() {
+        let area = bbox(50.0, 30.3, 50.1, 30.5);
+        let err = terrain_extent(&area, &sized(8192, Some(10_000.0))).unwrap_err();
+        assert!(err.contains("does not fit"), "{}", err);
+    }
+}
@@ -0,0 +1,302 @@
+// src-tauri/src/opentopodata.rs - Высоты по точкам через OpenTopoData API
+// (публичный api.opentopodata.org или свой сервер)
+
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::cache;
+use crate::download;
+use crate::elevation::{ElevationData, ElevationSource, FetchContext};
+use crate::terrain::{self, HeightGrid, MercatorBounds};
+use crate::BoundingBox;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OpenTopoDataSettings {
+    // Без /<dataset> на конце
+    pub endpoint: String,
+    // Имя набора данных на сервере: srtm30m, aster30m, eudem25m, ...
+    pub dataset: String,
+    // Количество точек по длинной стороне области
+    pub grid_size: usize,
+    // Точек в одном запросе (у публичного API не больше 100)
+    pub batch_size: usize,
+    // Минимальный интервал между запросами (у публичного API 1 запрос в секунду)
+    pub min_interval_ms: u64,
+}
+
+impl Default for OpenTopoDataSettings {
+    fn default() -> Self {
+        OpenTopoDataSettings {
+            endpoint: "https://api.opentopodata.org/v1".to_string(),
+            dataset: "aster30m".to_string(),
+            grid_size: 100,
+            batch_size: 100,
+            min_interval_ms: 1000,
+        }
+    }
+}
+
+// Больше точек по длинной стороне - слишком много запросов (1000x1000 - 10000 запросов по 100 точек)
+const MAX_GRID_SIZE: usize = 1000;
+
+pub struct OpenTopoDataSource {
+    pub settings: OpenTopoDataSettings,
+}
+
+#[derive(Deserialize)]
+struct OpenTopoDataResponse {
+    status: String,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    results: Vec<OpenTopoDataResult>,
+}
+
+#[derive(Deserialize)]
+struct OpenTopoDataResult {
+    elevation: Option<f64>,
+}
+
+impl OpenTopoDataSource {
+    fn batch_url(&self, locations: &[(f64, f64)]) -> String {
+        let locations: Vec<String> = locations
+            .iter()
+            .map(|(lat, lng)| format!("{:.6},{:.6}", lat, lng))
+            .collect();
+        format!(
+            "{}/{}?locations={}",
+            self.settings.endpoint.trim_end_matches('/'),
+            self.settings.dataset,
+            locations.join("|")
+        )
+    }
+
+    // Один запрос; результаты идут в том же порядке, что и точки в запросе
+    async fn fetch_batch(
+        &self,
+        locations: &[(f64, f64)],
+        ctx: &FetchContext,
+        last_request: &mut Option<Instant>,
+    ) -> Result<Vec<Option<f64>>, String> {
+        let url = self.batch_url(locations);
+        let body = match ctx.cache.get(cache::POINTS, &url) {
+            Some(body) => body,
+            None if ctx.cache.is_offline() => {
+                return Err("OpenTopoData points are not in the cache (offline mode)".to_string());
+            }
+            None => {
+                // Держим интервал между запросами, 429 и Retry-After обрабатывает fetch_with_retry
+                if let Some(last) = last_request {
+                    let interval = Duration::from_millis(self.settings.min_interval_ms);
+                    let elapsed = last.elapsed();
+                    if elapsed < interval {
+                        tokio::time::sleep(interval - elapsed).await;
+                    }
+                }
+                let body = download::fetch_with_retry(|| ctx.client.get(&url), &ctx.download).await;
+                *last_request = Some(Instant::now());
+                body?
+            }
+        };
+
+        let response: OpenTopoDataResponse =
+            serde_json::from_slice(&body).map_err(|e| format!("Invalid OpenTopoData response: {}", e))?;
+        if response.status != "OK" {
+            return Err(format!(
+                "OpenTopoData error: {}",
+                response.error.unwrap_or(response.status)
+            ));
+        }
+        if response.results.len() != locations.len() {
+            return Err(format!(
+                "OpenTopoData returned {} results for {} locations",
+                response.results.len(),
+                locations.len()
+            ));
+        }
+
+        if let Err(e) = ctx.cache.put(cache::POINTS, &url, &body) {
+            eprintln!("Failed to cache OpenTopoData response: {}", e);
+        }
+
+        Ok(response.results.into_iter().map(|r| r.elevation).collect())
+    }
+}
+
+#[async_trait]
+impl ElevationSource for OpenTopoDataSource {
+    fn name(&self) -> String {
+        format!("OpenTopoData {}", self.settings.dataset)
+    }
+
+    async fn fetch(&self, bbox: &BoundingBox, ctx: &FetchContext) -> Result<ElevationData, String> {
+        let settings = &self.settings;
+        if settings.grid_size < 2 {
+            return Err(format!("OpenTopoData grid size must be at least 2, got {}", settings.grid_size));
+        }
+        if settings.batch_size == 0 {
+            return Err("OpenTopoData batch size must be positive".to_string());
+        }
+
+        // Точки в центрах пикселей регулярной сетки Web Mercator, как у остальных источников
+        let bounds = MercatorBounds::from_bbox(bbox);
+        let long_side = bounds.width().max(bounds.height());
+        let width = ((settings.grid_size as f64 * bounds.width() / long_side).round() as usize).max(2);
+        let height = ((settings.grid_size as f64 * bounds.height() / long_side).round() as usize).max(2);
+        let batches = width.saturating_mul(height).div_ceil(settings.batch_size);
+        if settings.grid_size > MAX_GRID_SIZE {
+            return Err(format!(
+                "OpenTopoData grid of {}x{} points would take {} requests; grid size is limited to {}",
+                width, height, batches, MAX_GRID_SIZE
+            ));
+        }
+        let mut grid = HeightGrid::with_nodata(width, height, bounds);
+        let (dx, dy) = grid.pixel_size();
+
+        let mut locations = Vec::with_capacity(width * height);
+        for row in 0..height {
+            let my = bounds.max_y - (row as f64 + 0.5) * dy;
+            for col in 0..width {
+                let mx = bounds.min_x + (col as f64 + 0.5) * dx;
+                locations.push(terrain::mercator_to_lat_lng(mx, my));
+            }
+        }
+
+        println!(
+            "Requesting {}x{} points from OpenTopoData ({}, {} requests)",
+            width, height, settings.dataset, batches
+        );
+
+        let mut last_request = None;
+        for (batch_index, batch) in locations.chunks(settings.batch_size).enumerate() {
+            let elevations = self
+                .fetch_batch(batch, ctx, &mut last_request)
+                .await
+                .map_err(|e| format!("Failed to fetch OpenTopoData elevations: {}", e))?;
+
+            // Индекс точки в сетке = начало батча + позиция в ответе
+            let start = batch_index * settings.batch_size;
+            for (offset, elevation) in elevations.into_iter().enumerate() {
+                if let Some(elevation) = elevation {
+                    let index = start + offset;
+                    grid.set(index % width, index / width, elevation as f32);
+                }
+            }
+        }
+
+        if grid.invalid_count() == grid.data.len() {
+            return Err(format!("OpenTopoData {} has no data for the selected area", settings.dataset));
+        }
+
+        Ok(grid.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CacheSettings, DiskCache};
+
+    // Ответы кладутся в кэш заранее, офлайн-режим не ходит в сеть
+    #[tokio::test]
+    async fn batches_map_back_to_grid_cells() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::open(&CacheSettings {
+            directory: Some(dir.path().to_string_lossy().into_owned()),
+            offline: true,
+            ..CacheSettings::default()
+        })
+        .unwrap();
+
+        let source = OpenTopoDataSource {
+            settings: OpenTopoDataSettings {
+                grid_size: 3,
+                batch_size: 4,
+                ..OpenTopoDataSettings::default()
+            },
+        };
+        let bbox = BoundingBox { min_lat: 47.0, min_lng: 8.0, max_lat: 47.01, max_lng: 8.0147 };
+        let bounds = MercatorBounds::from_bbox(&bbox);
+        let (dx, dy) = (bounds.width() / 3.0, bounds.height() / 3.0);
+        let locations: Vec<(f64, f64)> = (0..9)
+            .map(|i| {
+                let mx = bounds.min_x + ((i % 3) as f64 + 0.5) * dx;
+                let my = bounds.max_y - ((i / 3) as f64 + 0.5) * dy;
+                terrain::mercator_to_lat_lng(mx, my)
+            })
+            .collect();
+        // Высота точки = её номер; у последней точки данных нет
+        for (batch_index, batch) in locations.chunks(4).enumerate() {
+            let results: Vec<serde_json::Value> = (0..batch.len())
+                .map(|k| {
+                    let index = batch_index * 4 + k;
+                    let elevation = if index == 8 { None } else { Some(index as f64) };
+                    serde_json::json!({ "elevation": elevation })
+                })
+                .collect();
+            let body = serde_json::json!({ "status": "OK", "results": results });
+            cache.put(cache::POINTS, &source.batch_url(batch), body.to_string().as_bytes()).unwrap();
+        }
+
+        let ctx = FetchContext {
+            client: reqwest::Client::new(),
+            zoom: 0,
+            meters_per_pixel: 1.0,
+            cache,
+            download: Default::default(),
+        };
+        let grid = source.fetch(&bbox, &ctx).await.unwrap().grid;
+        assert_eq!((grid.width, grid.height), (3, 3));
+        for index in 0..8 {
+            assert_eq!(grid.get(index % 3, index / 3), index as f32);
+        }
+        assert!(!grid.is_valid(2, 2));
+    }
+
+    #[tokio::test]
+    async fn oversized_grid_is_rejected_with_request_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::open(&CacheSettings {
+            directory: Some(dir.path().to_string_lossy().into_owned()),
+            offline: true,
+            ..CacheSettings::default()
+        })
+        .unwrap();
+        let source = OpenTopoDataSource {
+            settings: OpenTopoDataSettings {
+                grid_size: 2000,
+                ..OpenTopoDataSettings::default()
+            },
+        };
+        let ctx = FetchContext {
+            client: reqwest::Client::new(),
+            zoom: 0,
+            meters_per_pixel: 1.0,
+            cache,
+            download: Default::default(),
+        };
+        // Квадратная область у экватора: 2000x2000 точек по 100 в запросе
+        let bbox = BoundingBox { min_lat: 0.0, min_lng: 0.0, max_lat: 0.01, max_lng: 0.01 };
+        match source.fetch(&bbox, &ctx).await {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert!(e.contains("40000 requests"), "{}", e),
+        }
+    }
+
+    #[test]
+    fn batch_url_joins_locations() {
+        let source = OpenTopoDataSource {
+            settings: OpenTopoDataSettings {
+                endpoint: "https://example.org/v1/".to_string(),
+                ..OpenTopoDataSettings::default()
+            },
+        };
+        assert_eq!(
+            source.batch_url(&[(47.5, 8.25), (-1.0, 2.0)]),
+            "https://example.org/v1/aster30m?locations=47.500000,8.250000|-1.000000,2.000000"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+// src-tauri/src/raster.rs - Локальные растры высот (DEM) и их привязка к Web Mercator
+
+use crate::terrain::{self, HeightGrid, MercatorBounds};
+use crate::BoundingBox;
+
+// Больше этого размера исходную сетку не строим - дальше она всё равно
+// пересчитывается в terrain_resolution
+pub const MAX_SOURCE_GRID: usize = 8192;
+
+// Система координат растра. Датумы (WGS84, ETRS89, NAD83) не различаем -
+// разница в пределах метра и для карты BeamNG не важна.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Crs {
+    Geographic,
+    WebMercator,
+    Utm { zone: u8, north: bool },
+}
+
+impl Crs {
+    pub fn from_epsg(code: u32) -> Result<Crs, String> {
+        match code {
+            4326 | 4258 | 4269 | 4283 => Ok(Crs::Geographic),
+            3857 | 900913 => Ok(Crs::WebMercator),
+            32601..=32660 => Ok(Crs::Utm { zone: (code - 32600) as u8, north: true }),
+            32701..=32760 => Ok(Crs::Utm { zone: (code - 32700) as u8, north: false }),
+            25828..=25838 => Ok(Crs::Utm { zone: (code - 25800) as u8, north: true }),
+            26901..=26923 => Ok(Crs::Utm { zone: (code - 26900) as u8, north: true }),
+            _ => Err(format!(
+                "Unsupported CRS EPSG:{} (supported: WGS84 lat/lon, Web Mercator, UTM)",
+                code
+            )),
+        }
+    }
+
+    // Из широты/долготы в координаты растра
+    pub fn project(&self, lat: f64, lng: f64) -> (f64, f64) {
+        match *self {
+            Crs::Geographic => (lng, lat),
+            Crs::WebMercator => terrain::lat_lng_to_mercator(lat, lng),
+            Crs::Utm { zone, north } => utm_forward(lat, lng, zone, north),
+        }
+    }
+}
+
+// Transverse Mercator на эллипсоиде WGS84 (Snyder, "Map Projections", стр. 61)
+fn utm_forward(lat: f64, lng: f64, zone: u8, north: bool) -> (f64, f64) {
+    let a = 6378137.0;
+    let f = 1.0 / 298.257223563;
+    let k0 = 0.9996;
+    let e2: f64 = f * (2.0 - f);
+    let e4 = e2 * e2;
+    let e6 = e4 * e2;
+    let ep2 = e2 / (1.0 - e2);
+
+    let phi = lat.to_radians();
+    let lng0 = (zone as f64 - 1.0) * 6.0 - 180.0 + 3.0;
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let tan_phi = phi.tan();
+
+    let n = a / (1.0 - e2 * sin_phi * sin_phi).sqrt();
+    let t = tan_phi * tan_phi;
+    let c = ep2 * cos_phi * cos_phi;
+    // Разница долгот в (-180, 180], чтобы зона у антимеридиана принимала обе его стороны
+    let dlng = (lng - lng0 + 540.0).rem_euclid(360.0) - 180.0;
+    let big_a = cos_phi * dlng.to_radians();
+
+    let m = a
+        * ((1.0 - e2 / 4.0 - 3.0 * e4 / 64.0 - 5.0 * e6 / 256.0) * phi
+            - (3.0 * e2 / 8.0 + 3.0 * e4 / 32.0 + 45.0 * e6 / 1024.0) * (2.0 * phi).sin()
+            + (15.0 * e4 / 256.0 + 45.0 * e6 / 1024.0) * (4.0 * phi).sin()
+            - (35.0 * e6 / 3072.0) * (6.0 * phi).sin());
+
+    let easting = k0
+        * n
+        * (big_a
+            + (1.0 - t + c) * big_a.powi(3) / 6.0
+            + (5.0 - 18.0 * t + t * t + 72.0 * c - 58.0 * ep2) * big_a.powi(5) / 120.0)
+        + 500000.0;
+
+    let mut northing = k0
+        * (m + n
+            * tan_phi
+            * (big_a * big_a / 2.0
+                + (5.0 - t + 9.0 * c + 4.0 * c * c) * big_a.powi(4) / 24.0
+                + (61.0 - 58.0 * t + t * t + 600.0 * c - 330.0 * ep2) * big_a.powi(6) / 720.0));
+    if !north {
+        northing += 10000000.0;
+    }
+
+    (easting, northing)
+}
+
+// Аффинная привязка в стиле GDAL:
+// x = t[0] + col * t[1] + row * t[2], y = t[3] + col * t[4] + row * t[5]
+// (col, row) - угол пикселя, центр первого пикселя это (0.5, 0.5)
+#[derive(Debug, Clone, Copy)]
+pub struct GeoTransform(pub [f64; 6]);
+
+impl GeoTransform {
+    pub fn to_pixel(self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let t = &self.0;
+        let det = t[1] * t[5] - t[2] * t[4];
+        if det == 0.0 {
+            return None;
+        }
+        let dx = x - t[0];
+        let dy = y - t[3];
+        let col = (t[5] * dx - t[2] * dy) / det;
+        let row = (t[1] * dy - t[4] * dx) / det;
+        Some((col, row))
+    }
+}
+
+// Растр высот в своей системе координат. NaN - нет данных (nodata).
+pub struct GeoRaster {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f32>,
+    pub transform: GeoTransform,
+    pub crs: Crs,
+}
+
+impl GeoRaster {
+    // Размер пикселя на местности в метрах (приблизительно, в точке lat)
+    pub fn ground_resolution(&self, lat: f64) -> f64 {
+        let t = &self.transform.0;
+        let px = t[1].hypot(t[4]);
+        let py = t[2].hypot(t[5]);
+        match self.crs {
+            Crs::Geographic => {
+                let meters_per_degree = 2.0 * terrain::MERCATOR_HALF_EXTENT / 360.0;
+                (px * meters_per_degree * lat.to_radians().cos()).min(py * meters_per_degree)
+            }
+            Crs::WebMercator => px.min(py) * lat.to_radians().cos(),
+            Crs::Utm { .. } => px.min(py),
+        }
+    }
+
+    fn value(&self, col: isize, row: isize) -> f32 {
+        if col < 0 || row < 0 || col >= self.width as isize || row >= self.height as isize {
+            return f32::NAN;
+        }
+        self.data[row as usize * self.width + col as usize]
+    }
+
+    // Билинейная интерполяция с пропуском ячеек без данных
+    pub fn sample(&self, x: f64, y: f64) -> f32 {
+        let (col, row) = match self.transform.to_pixel(x, y) {
+            Some(p) => p,
+            None => return f32::NAN,
+        };
+        let fx = col - 0.5;
+        let fy = row - 0.5;
+        if fx < -0.5 || fy < -0.5 || fx > self.width as f64 - 0.5 || fy > self.height as f64 - 0.5 {
+            return f32::NAN;
+        }
+
+        let x0 = fx.floor();
+        let y0 = fy.floor();
+        let tx = fx - x0;
+        let ty = fy - y0;
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let taps = [
+            (x0, y0, (1.0 - tx) * (1.0 - ty)),
+            (x0 + 1, y0, tx * (1.0 - ty)),
+            (x0, y0 + 1, (1.0 - tx) * ty),
+            (x0 + 1, y0 + 1, tx * ty),
+        ];
+
+        let mut sum = 0.0;
+        let mut weight = 0.0;
+        for (cx, cy, w) in taps {
+            let v = self.value(cx, cy);
+            if !v.is_nan() && w > 0.0 {
+                sum += v as f64 * w;
+                weight += w;
+            }
+        }
+
+        if weight > 0.0 {
+            (sum / weight) as f32
+        } else {
+            // Точка ровно на краю области с данными
+            let v = self.value(fx.round() as isize, fy.round() as isize);
+            if v.is_nan() { f32::NAN } else { v }
+        }
+    }
+}
+
+// Перепроецирует растры в регулярную сетку Web Mercator, покрывающую bbox.
+// Шаг - самое мелкое родное разрешение, но не мельче meters_per_pixel.
+// Где растры перекрываются, берётся первый, у которого есть данные.
+pub fn to_mercator_grid(rasters: &[GeoRaster], bbox: &BoundingBox, meters_per_pixel: f64) -> HeightGrid {
+    let bounds = MercatorBounds::from_bbox(bbox);
+    let center_lat = (bbox.min_lat + bbox.max_lat) / 2.0;
+    let native_mpp = rasters
+        .iter()
+        .map(|r| r.ground_resolution(center_lat))
+        .fold(f64::INFINITY, f64::min);
+    let ground_mpp = native_mpp.max(meters_per_pixel);
+    let mercator_mpp = ground_mpp / center_lat.to_radians().cos();
+
+    let width = ((bounds.width() / mercator_mpp).ceil() as usize).clamp(2, MAX_SOURCE_GRID);
+    let height = ((bounds.height() / mercator_mpp).ceil() as usize).clamp(2, MAX_SOURCE_GRID);
+
+    let mut grid = HeightGrid::with_nodata(width, height, bounds);
+    let (dx, dy) = grid.pixel_size();
+
+    for row in 0..height {
+        let my = bounds.max_y - (row as f64 + 0.5) * dy;
+        for col in 0..width {
+            let mx = bounds.min_x + (col as f64 + 0.5) * dx;
+            let (lat, lng) = terrain::mercator_to_lat_lng(mx, my);
+            let value = rasters
+                .iter()
+                .map(|r| {
+                    let (x, y) = r.crs.project(lat, lng);
+                    r.sample(x, y)
+                })
+                .find(|v| !v.is_nan());
+            if let Some(value) = value {
+                grid.set(col, row, value);
+            }
+        }
+    }
+
+    grid
+}
+
+// Общий шаг для локальных DEM: сетка по bbox + проверка покрытия
+pub fn rasters_to_height_grid(
+    rasters: &[GeoRaster],
+    bbox: &BoundingBox,
+    meters_per_pixel: f64,
+    source_name: &str,
+) -> Result<HeightGrid, String> {
+    if rasters.is_empty() {
+        return Err(format!("{}: no elevation data found", source_name));
+    }
+
+    let grid = to_mercator_grid(rasters, bbox, meters_per_pixel);
+    if grid.invalid_count() == grid.data.len() {
+        return Err(format!("{} does not cover the selected area", source_name));
+    }
+
+    Ok(grid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utm_on_central_meridian() {
+        // Зона 32 - центральный меридиан 9° в.д.
+        let (e, n) = Crs::Utm { zone: 32, north: true }.project(0.0, 9.0);
+        assert!((e - 500000.0).abs() < 1e-6 && n.abs() < 1e-6);
+
+        // Длина дуги меридиана WGS84 до 45° - 4984944.378 м, масштаб 0.9996
+        let (e, n) = Crs::Utm { zone: 32, north: true }.project(45.0, 9.0);
+        assert!((e - 500000.0).abs() < 1e-6);
+        assert!((n - 4982950.40).abs() < 1.0, "{}", n);
+    }
+
+    #[test]
+    fn utm_is_symmetric_about_the_central_meridian() {
+        let (east, n_east) = utm_forward(47.0, 11.0, 32, true);
+        let (west, n_west) = utm_forward(47.0, 7.0, 32, true);
+        assert!((east - 500000.0 - (500000.0 - west)).abs() < 1e-6);
+        assert!((n_east - n_west).abs() < 1e-6);
+
+        // Южное полушарие - со сдвигом 10 000 км
+        let (_, n_south) = utm_forward(-47.0, 11.0, 32, false);
+        assert!((n_south - (10000000.0 - n_east)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn epsg_codes_map_to_crs() {
+        assert_eq!(Crs::from_epsg(4326), Ok(Crs::Geographic));
+        assert_eq!(Crs::from_epsg(32633), Ok(Crs::Utm { zone: 33, north: true }));
+        assert_eq!(Crs::from_epsg(32718), Ok(Crs::Utm { zone: 18, north: false }));
+        assert_eq!(Crs::from_epsg(25832), Ok(Crs::Utm { zone: 32, north: true }));
+        assert!(Crs::from_epsg(2056).is_err());
+    }
+
+    fn raster(data: Vec<f32>) -> GeoRaster {
+        // 2x2 пикселя по 10 единиц, левый верхний угол в (100, 200)
+        GeoRaster {
+            width: 2,
+            height: 2,
+            data,
+            transform: GeoTransform([100.0, 10.0, 0.0, 200.0, 0.0, -10.0]),
+            crs: Crs::Utm { zone: 32, north: true },
+        }
+    }
+
+    #[test]
+    fn transform_maps_corners_to_pixels() {
+        let t = raster(vec![0.0; 4]).transform;
+        assert_eq!(t.to_pixel(100.0, 200.0), Some((0.0, 0.0)));
+        assert_eq!(t.to_pixel(115.0, 185.0), Some((1.5, 1.5)));
+        assert_eq!(GeoTransform([0.0, 1.0, 0.0, 0.0, 0.0, 0.0]).to_pixel(1.0, 1.0), None);
+    }
+
+    #[test]
+    fn sampling_skips_nodata() {
+        let r = raster(vec![10.0, 20.0, f32::NAN, 40.0]);
+        // Центры пикселей
+        assert_eq!(r.sample(105.0, 195.0), 10.0);
+        assert_eq!(r.sample(115.0, 185.0), 40.0);
+        // Середина растра: три соседа с данными из четырёх
+        assert!((r.sample(110.0, 190.0) - 70.0 / 3.0).abs() < 1e-4);
+        assert!(r.sample(95.0, 190.0).is_nan());
+        assert!(r.sample(105.0, 185.0).is_nan());
+    }
+}
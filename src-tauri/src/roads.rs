@@ -0,0 +1,401 @@
+// src-tauri/src/roads.rs - Врезка дорожной сети в рельеф
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ter::HeightScale;
+use crate::terrain::{HeightGrid, ResampleMethod};
+use crate::{beamng_to_grid, RoadNetwork, RoadNode};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoadSettings {
+    pub enabled: bool,
+    // Обочина с каждой стороны сверх ширины дороги (м)
+    pub shoulder_m: f64,
+    // Поперечный уклон от оси к краю (%)
+    pub camber_percent: f64,
+    // Ширина плавного перехода от обочины к рельефу (м)
+    pub falloff_m: f64,
+    // Сглаживание продольного профиля дороги (м)
+    pub profile_smoothing_m: f64,
+}
+
+impl Default for RoadSettings {
+    fn default() -> Self {
+        RoadSettings {
+            enabled: true,
+            shoulder_m: 1.0,
+            camber_percent: 2.0,
+            falloff_m: 8.0,
+            profile_smoothing_m: 25.0,
+        }
+    }
+}
+
+pub struct CarvedRoads {
+    // Полотно и обочины: дальше по пайплайну эти ячейки не трогаем
+    pub protected: Vec<bool>,
+    pub carved_cells: usize,
+}
+
+// Цепочка узлов одной дороги в координатах сетки (ячейки, дробные)
+struct Polyline {
+    points: Vec<(f64, f64)>,
+    half_width: f64,
+}
+
+// Лучший кандидат для ячейки: вес врезки, расстояние до объекта и целевая высота (дороги, площадки зданий)
+#[derive(Clone, Copy)]
+pub struct Candidate {
+    pub weight: f32,
+    pub distance: f64,
+    pub height: f32,
+}
+
+impl Candidate {
+    // Оставляет в ячейке кандидата с большим весом, при равном весе - ближайшего
+    pub fn offer(slot: &mut Option<Candidate>, candidate: Candidate) {
+        let better = match slot {
+            None => true,
+            Some(c) => candidate.weight > c.weight || (candidate.weight == c.weight && candidate.distance < c.distance),
+        };
+        if better {
+            *slot = Some(candidate);
+        }
+    }
+}
+
+// Выравнивает рельеф вдоль дорог. Высоты полотна берутся из сглаженного продольного
+// профиля, поперёк - двускатный профиль с уклоном camber, затем переход к рельефу.
+pub fn carve_roads(
+    heightmap: &mut HeightGrid,
+    network: &RoadNetwork,
+    square_size: f64,
+    settings: &RoadSettings,
+) -> Result<CarvedRoads, String> {
+    let (w, h) = (heightmap.width, heightmap.height);
+    let mut carved = CarvedRoads {
+        protected: vec![false; w * h],
+        carved_cells: 0,
+    };
+    if !settings.enabled {
+        return Ok(carved);
+    }
+    check_non_negative(
+        "Road",
+        &[
+            ("shoulder_m", settings.shoulder_m),
+            ("camber_percent", settings.camber_percent),
+            ("falloff_m", settings.falloff_m),
+            ("profile_smoothing_m", settings.profile_smoothing_m),
+        ],
+    )?;
+
+    let shoulder = settings.shoulder_m / square_size;
+    let falloff = settings.falloff_m / square_size;
+    // Перепад высоты на ячейку поперёк дороги
+    let camber = (settings.camber_percent / 100.0 * square_size) as f32;
+    let smoothing = settings.profile_smoothing_m / square_size;
+
+    let polylines = build_polylines(network, heightmap, square_size);
+    let mut best: Vec<Option<Candidate>> = vec![None; w * h];
+
+    for line in &polylines {
+        let (cumulative, profile) = centerline_profile(heightmap, &line.points, smoothing);
+        let core = line.half_width + shoulder;
+        let reach = core + falloff;
+
+        for k in 0..line.points.len() - 1 {
+            let (a, b) = (line.points[k], line.points[k + 1]);
+            let x0 = ((a.0.min(b.0) - reach).floor().max(0.0)) as usize;
+            let x1 = ((a.0.max(b.0) + reach).ceil().min(w as f64 - 1.0)).max(0.0) as usize;
+            let y0 = ((a.1.min(b.1) - reach).floor().max(0.0)) as usize;
+            let y1 = ((a.1.max(b.1) + reach).ceil().min(h as f64 - 1.0)).max(0.0) as usize;
+            let length = cumulative[k + 1] - cumulative[k];
+
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    let (distance, t) = point_segment_distance((x as f64, y as f64), a, b);
+                    if distance > reach {
+                        continue;
+                    }
+                    let center = sample_profile(&profile, cumulative[k] + t * length);
+                    let (weight, height) = if distance <= core {
+                        (1.0, center - camber * distance as f32)
+                    } else {
+                        let s = ((distance - core) / falloff.max(f64::EPSILON)) as f32;
+                        (1.0 - smoothstep(s), center - camber * core as f32)
+                    };
+                    if weight <= 0.0 {
+                        continue;
+                    }
+
+                    Candidate::offer(&mut best[y * w + x], Candidate { weight, distance, height });
+                }
+            }
+        }
+    }
+
+    for (i, candidate) in best.into_iter().enumerate() {
+        if let Some(c) = candidate {
+            heightmap.data[i] += (c.height - heightmap.data[i]) * c.weight;
+            carved.carved_cells += 1;
+            if c.weight >= 1.0 {
+                carved.protected[i] = true;
+            }
+        }
+    }
+
+    println!(
+        "Carved {} roads into the terrain: {} cells changed, {} protected",
+        polylines.len(),
+        carved.carved_cells,
+        carved.protected.iter().filter(|&&p| p).count()
+    );
+    Ok(carved)
+}
+
+// Общая проверка настроек: все поля конечные и не отрицательные
+pub fn check_non_negative(what: &str, fields: &[(&str, f64)]) -> Result<(), String> {
+    for &(name, value) in fields {
+        if !value.is_finite() || value < 0.0 {
+            return Err(format!("{} setting {} must be non-negative, got {}", what, name, value));
+        }
+    }
+    Ok(())
+}
+
+// Высота узлов дороги = высота врезанного полотна в уровне
+pub fn place_road_nodes(nodes: &mut [RoadNode], heightmap: &HeightGrid, square_size: f64, scale: HeightScale) {
+    for node in nodes {
+        let (fx, fy) = beamng_to_grid(node.position.0, node.position.2, heightmap, square_size);
+        node.position.1 = scale.level_height(heightmap.sample(fx, fy, ResampleMethod::Bilinear));
+    }
+}
+
+// Отладочная карта защищённых ячеек (белые - полотно дорог)
+pub fn save_road_mask(carved: &CarvedRoads, width: usize, height: usize, dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let img = image::GrayImage::from_fn(width as u32, height as u32, |x, y| {
+        image::Luma([if carved.protected[y as usize * width + x as usize] { 255 } else { 0 }])
+    });
+    img.save(dir.join("road_mask.png")).map_err(|e| e.to_string())
+}
+
+// Сегменты одной OSM линии идут подряд и продолжают друг друга - собираем их в цепочки.
+// Мосты и тоннели рельеф не трогают.
+fn build_polylines(network: &RoadNetwork, heightmap: &HeightGrid, square_size: f64) -> Vec<Polyline> {
+    let positions: std::collections::HashMap<&str, (f32, f32)> = network
+        .nodes
+        .iter()
+        .map(|n| (n.id.as_str(), (n.position.0, n.position.2)))
+        .collect();
+
+    let mut polylines: Vec<Polyline> = Vec::new();
+    let mut last_end: Option<&str> = None;
+    for segment in &network.segments {
+        if segment.bridge || segment.tunnel {
+            last_end = None;
+            continue;
+        }
+        let (Some(&start), Some(&end)) = (
+            positions.get(segment.start_node.as_str()),
+            positions.get(segment.end_node.as_str()),
+        ) else {
+            last_end = None;
+            continue;
+        };
+        let start = beamng_to_grid(start.0, start.1, heightmap, square_size);
+        let end = beamng_to_grid(end.0, end.1, heightmap, square_size);
+        let half_width = segment.width as f64 / 2.0 / square_size;
+
+        match polylines.last_mut() {
+            Some(line) if last_end == Some(segment.start_node.as_str()) && line.half_width == half_width => {
+                line.points.push(end);
+            }
+            _ => polylines.push(Polyline {
+                points: vec![start, end],
+                half_width,
+            }),
+        }
+        last_end = Some(segment.end_node.as_str());
+    }
+    polylines
+}
+
+// Продольный профиль: высоты рельефа через каждую ячейку вдоль оси, сглаженные
+// по Гауссу (дороги, реки). Возвращает накопленную длину в узлах и профиль с шагом 1 ячейка.
+pub fn centerline_profile(heightmap: &HeightGrid, points: &[(f64, f64)], smoothing: f64) -> (Vec<f64>, Vec<f32>) {
+    let mut cumulative = vec![0.0];
+    for pair in points.windows(2) {
+        let length = ((pair[1].0 - pair[0].0).powi(2) + (pair[1].1 - pair[0].1).powi(2)).sqrt();
+        cumulative.push(cumulative.last().unwrap() + length);
+    }
+
+    let total = *cumulative.last().unwrap();
+    let samples = total.ceil() as usize + 1;
+    let mut raw = Vec::with_capacity(samples);
+    let mut k = 0;
+    for s in 0..samples {
+        let s = (s as f64).min(total);
+        while k + 2 < cumulative.len() && cumulative[k + 1] < s {
+            k += 1;
+        }
+        let length = cumulative[k + 1] - cumulative[k];
+        let t = if length > 0.0 { (s - cumulative[k]) / length } else { 0.0 };
+        let (a, b) = (points[k], points[k + 1]);
+        raw.push(heightmap.sample(a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t, ResampleMethod::Bilinear));
+    }
+
+    if smoothing <= 0.0 {
+        return (cumulative, raw);
+    }
+    let radius = (2.0 * smoothing).ceil() as isize;
+    let profile = (0..raw.len() as isize)
+        .map(|i| {
+            let mut sum = 0.0;
+            let mut weight = 0.0;
+            for j in (i - radius).max(0)..=(i + radius).min(raw.len() as isize - 1) {
+                let wgt = (-((j - i) as f64).powi(2) / (2.0 * smoothing * smoothing)).exp();
+                sum += raw[j as usize] as f64 * wgt;
+                weight += wgt;
+            }
+            (sum / weight) as f32
+        })
+        .collect();
+    (cumulative, profile)
+}
+
+pub fn sample_profile(profile: &[f32], s: f64) -> f32 {
+    let s = s.clamp(0.0, (profile.len() - 1) as f64);
+    let i = (s.floor() as usize).min(profile.len() - 1);
+    let j = (i + 1).min(profile.len() - 1);
+    let t = (s - i as f64) as f32;
+    profile[i] * (1.0 - t) + profile[j] * t
+}
+
+// Расстояние от точки до отрезка и параметр ближайшей точки на нём (0..1)
+pub fn point_segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length2 = dx * dx + dy * dy;
+    let t = if length2 > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (cx, cy) = (a.0 + dx * t, a.1 + dy * t);
+    (((p.0 - cx).powi(2) + (p.1 - cy).powi(2)).sqrt(), t)
+}
+
+pub fn smoothstep(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ter::HeightSettings;
+    use crate::RoadSegment;
+
+    // Склон поперёк дороги: высота равна номеру строки
+    fn slope(size: usize) -> HeightGrid {
+        HeightGrid::from_fn(size, size, |_, y| y as f32)
+    }
+
+    fn node(id: &str, x: f32, z: f32) -> RoadNode {
+        RoadNode { id: id.to_string(), position: (x, 0.0, z), width: 4.0, road_type: "residential".to_string() }
+    }
+
+    fn segment(start: &str, end: &str, bridge: bool) -> RoadSegment {
+        RoadSegment {
+            id: format!("{}-{}", start, end),
+            start_node: start.to_string(),
+            end_node: end.to_string(),
+            width: 4.0,
+            lanes: 2,
+            road_type: "residential".to_string(),
+            one_way: false,
+            bridge,
+            tunnel: false,
+        }
+    }
+
+    // Дорога вдоль строки 10 сетки 21x21 (z = 21 - 10 - 0.5)
+    fn straight_road(bridge: bool) -> RoadNetwork {
+        RoadNetwork {
+            nodes: vec![node("a", 2.5, 10.5), node("b", 10.5, 10.5), node("c", 18.5, 10.5)],
+            segments: vec![segment("a", "b", bridge), segment("b", "c", bridge)],
+        }
+    }
+
+    fn flat_settings() -> RoadSettings {
+        RoadSettings {
+            shoulder_m: 1.0,
+            camber_percent: 0.0,
+            falloff_m: 2.0,
+            profile_smoothing_m: 0.0,
+            ..RoadSettings::default()
+        }
+    }
+
+    #[test]
+    fn road_is_levelled_across_its_width() {
+        let mut grid = slope(21);
+        let carved = carve_roads(&mut grid, &straight_road(false), 1.0, &flat_settings()).unwrap();
+
+        // Полотно 4 м и обочины по 1 м - по 3 ячейки от оси
+        for y in 7..=13 {
+            assert_eq!(grid.get(10, y), 10.0, "row {}", y);
+            assert!(carved.protected[y * 21 + 10]);
+        }
+        // Переход к рельефу и нетронутый склон дальше
+        assert!(grid.get(10, 14) > 10.0 && grid.get(10, 14) < 14.0);
+        assert!(!carved.protected[14 * 21 + 10]);
+        assert_eq!(grid.get(10, 16), 16.0);
+        assert_eq!(grid.get(10, 4), 4.0);
+    }
+
+    #[test]
+    fn camber_lowers_the_edges() {
+        let mut grid = slope(21);
+        let settings = RoadSettings { camber_percent: 10.0, ..flat_settings() };
+        carve_roads(&mut grid, &straight_road(false), 1.0, &settings).unwrap();
+        assert!((grid.get(10, 8) - 9.8).abs() < 1e-4);
+        assert!((grid.get(10, 12) - 9.8).abs() < 1e-4);
+    }
+
+    #[test]
+    fn bridges_are_not_carved() {
+        let mut grid = slope(21);
+        let carved = carve_roads(&mut grid, &straight_road(true), 1.0, &flat_settings()).unwrap();
+        assert_eq!(carved.carved_cells, 0);
+        assert_eq!(grid.data, slope(21).data);
+    }
+
+    #[test]
+    fn negative_settings_are_rejected() {
+        let settings = RoadSettings { falloff_m: -1.0, ..RoadSettings::default() };
+        assert!(carve_roads(&mut slope(4), &straight_road(false), 1.0, &settings).is_err());
+    }
+
+    #[test]
+    fn nodes_follow_the_carved_surface() {
+        let grid = slope(21);
+        let scale = HeightScale::from_range(0.0, 20.0, &HeightSettings::default()).unwrap();
+        let mut nodes = vec![node("a", 5.5, 10.5), node("b", 5.5, 20.5)];
+        place_road_nodes(&mut nodes, &grid, 1.0, scale);
+        assert_eq!(nodes[0].position.1, 10.0);
+        assert_eq!(nodes[1].position.1, 0.0);
+    }
+
+    #[test]
+    fn distance_to_segment_clamps_to_ends() {
+        assert_eq!(point_segment_distance((1.0, 2.0), (0.0, 0.0), (4.0, 0.0)), (2.0, 0.25));
+        assert_eq!(point_segment_distance((7.0, 4.0), (0.0, 0.0), (4.0, 0.0)), (5.0, 1.0));
+        assert_eq!(smoothstep(0.5), 0.5);
+        assert_eq!(smoothstep(2.0), 1.0);
+    }
+}
@@ -0,0 +1,293 @@
+// src-tauri/src/ter.rs - Рельеф в формате BeamNG: бинарный .ter, terrain.json и 16-битный PNG
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::HeightGrid;
+
+// Версия формата .ter, которую понимает текущий BeamNG
+pub const TER_VERSION: u8 = 9;
+// В карте слоёв: дыра в рельефе
+pub const LAYER_HOLE: u8 = u8::MAX;
+// Для плоской карты maxHeight не может быть нулевым
+const MIN_MAX_HEIGHT: f32 = 1.0;
+
+// Что в уровне находится на высоте 0
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeightDatum {
+    // Самая низкая точка рельефа
+    #[default]
+    Lowest,
+    // Уровень моря: высоты в уровне совпадают с реальными
+    SeaLevel,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeightSettings {
+    pub datum: HeightDatum,
+    // Множитель перепадов высот относительно datum (1.0 - реальные метры)
+    pub vertical_exaggeration: f64,
+    // Сдвиг рельефа и объектов по вертикали после масштабирования (м)
+    pub offset_m: f64,
+}
+
+impl Default for HeightSettings {
+    fn default() -> Self {
+        HeightSettings {
+            datum: HeightDatum::default(),
+            vertical_exaggeration: 1.0,
+            offset_m: 0.0,
+        }
+    }
+}
+
+// Перевод исходных высот (м) в уровень BeamNG. В .ter: 0 -> base, 65535 -> самая высокая
+// точка, на местности это level_base и level_base + max_height (position и maxHeight TerrainBlock).
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct HeightScale {
+    pub base: f32,
+    pub max_height: f32,
+    pub exaggeration: f32,
+    pub level_base: f32,
+}
+
+impl HeightScale {
+    pub fn from_range(min: f32, max: f32, settings: &HeightSettings) -> Result<Self, String> {
+        let exaggeration = settings.vertical_exaggeration;
+        if !exaggeration.is_finite() || exaggeration <= 0.0 {
+            return Err(format!("Vertical exaggeration must be positive, got {}", exaggeration));
+        }
+        if !settings.offset_m.is_finite() {
+            return Err(format!("Invalid height offset: {} m", settings.offset_m));
+        }
+        let exaggeration = exaggeration as f32;
+        let datum = match settings.datum {
+            HeightDatum::Lowest => min,
+            HeightDatum::SeaLevel => 0.0,
+        };
+        Ok(HeightScale {
+            base: min,
+            max_height: ((max - min) * exaggeration).max(MIN_MAX_HEIGHT),
+            exaggeration,
+            level_base: (min - datum) * exaggeration + settings.offset_m as f32,
+        })
+    }
+
+    pub fn quantize(&self, height: f32) -> u16 {
+        let normalized = (height - self.base) * self.exaggeration / self.max_height;
+        (normalized * u16::MAX as f32).round().clamp(0.0, u16::MAX as f32) as u16
+    }
+
+    // Высота в уровне для исходной высоты (м) - для объектов и узлов дорог
+    pub fn level_height(&self, height: f32) -> f32 {
+        self.level_base + (height - self.base) * self.exaggeration
+    }
+}
+
+// Материалы рельефа: индекс в карте слоёв -> имя TerrainMaterial
+pub struct TerrainLayers {
+    pub layer_map: Vec<u8>,
+    pub materials: Vec<String>,
+}
+
+impl TerrainLayers {
+    // Весь рельеф одним материалом
+    pub fn single(size: usize, material: &str) -> Self {
+        TerrainLayers {
+            layer_map: vec![0; size * size],
+            materials: vec![material.to_string()],
+        }
+    }
+}
+
+// .ter: u8 версия, u32 размер стороны, u16 высоты, u8 карта слоёв,
+// u32 число материалов и имена (u8 длина + байты). Всё little-endian.
+// Строки в .ter идут с юга на север, а в HeightGrid строка 0 - северная.
+pub fn write_ter(path: &Path, heightmap: &HeightGrid, scale: HeightScale, layers: &TerrainLayers) -> Result<(), String> {
+    let size = heightmap.width;
+    if heightmap.height != size {
+        return Err(format!(
+            "BeamNG terrain must be square, got {}x{}",
+            heightmap.width, heightmap.height
+        ));
+    }
+    if layers.layer_map.len() != size * size {
+        return Err(format!(
+            "Layer map has {} cells, terrain has {}",
+            layers.layer_map.len(),
+            size * size
+        ));
+    }
+    if layers.materials.is_empty() || layers.materials.len() >= LAYER_HOLE as usize {
+        return Err(format!("Expected 1 to {} terrain materials, got {}", LAYER_HOLE - 1, layers.materials.len()));
+    }
+    if let Some(name) = layers.materials.iter().find(|name| name.len() > u8::MAX as usize) {
+        return Err(format!("Terrain material name is too long: {}", name));
+    }
+
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut out = BufWriter::new(file);
+    let io_err = |e: std::io::Error| format!("Failed to write {}: {}", path.display(), e);
+
+    out.write_all(&[TER_VERSION]).map_err(io_err)?;
+    out.write_all(&(size as u32).to_le_bytes()).map_err(io_err)?;
+
+    for row in (0..size).rev() {
+        for col in 0..size {
+            let value = scale.quantize(heightmap.get(col, row));
+            out.write_all(&value.to_le_bytes()).map_err(io_err)?;
+        }
+    }
+
+    for row in (0..size).rev() {
+        out.write_all(&layers.layer_map[row * size..(row + 1) * size]).map_err(io_err)?;
+    }
+
+    out.write_all(&(layers.materials.len() as u32).to_le_bytes()).map_err(io_err)?;
+    for name in &layers.materials {
+        let bytes = name.as_bytes();
+        out.write_all(&[bytes.len() as u8]).map_err(io_err)?;
+        out.write_all(bytes).map_err(io_err)?;
+    }
+
+    out.flush().map_err(io_err)
+}
+
+// 16-битный PNG в том же масштабе, что и .ter (север сверху)
+pub fn save_heightmap_png16(path: &Path, heightmap: &HeightGrid, scale: HeightScale) -> Result<(), String> {
+    let (width, height) = (heightmap.width as u32, heightmap.height as u32);
+    let img = image::ImageBuffer::<image::Luma<u16>, Vec<u16>>::from_fn(width, height, |x, y| {
+        image::Luma([scale.quantize(heightmap.get(x as usize, y as usize))])
+    });
+    img.save(path).map_err(|e| e.to_string())
+}
+
+// <имя>.terrain.json рядом с .ter - описание данных для редактора BeamNG
+pub fn terrain_json(datafile: &str, heightmap_image: &str, size: usize, layers: &TerrainLayers) -> serde_json::Value {
+    serde_json::json!({
+        "datafile": datafile,
+        "heightmapImage": heightmap_image,
+        "heightMapItemSize": 2,
+        "heightMapSize": size * size,
+        "layerMapItemSize": 1,
+        "layerMapSize": size * size,
+        "materials": layers.materials,
+        "size": size,
+        "version": TER_VERSION,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Высота в уровне по значению из .ter - так её восстанавливает BeamNG
+    fn decode(scale: &HeightScale, value: u16) -> f32 {
+        scale.level_base + value as f32 / u16::MAX as f32 * scale.max_height
+    }
+
+    #[test]
+    fn quantize_matches_level_height() {
+        let settings = HeightSettings {
+            datum: HeightDatum::SeaLevel,
+            vertical_exaggeration: 1.5,
+            offset_m: -20.0,
+        };
+        let scale = HeightScale::from_range(120.0, 320.0, &settings).unwrap();
+        assert_eq!(scale.max_height, 300.0);
+        assert_eq!(scale.level_base, 160.0);
+        for h in [120.0, 187.3, 250.0, 320.0] {
+            let level = scale.level_height(h);
+            assert!((decode(&scale, scale.quantize(h)) - level).abs() < 0.01, "{}", h);
+        }
+        assert_eq!(scale.quantize(100.0), 0);
+        assert_eq!(scale.quantize(400.0), u16::MAX);
+    }
+
+    #[test]
+    fn lowest_datum_starts_at_zero() {
+        let scale = HeightScale::from_range(500.0, 500.0, &HeightSettings::default()).unwrap();
+        assert_eq!(scale.level_height(500.0), 0.0);
+        assert_eq!(scale.max_height, MIN_MAX_HEIGHT);
+    }
+
+    #[test]
+    fn invalid_exaggeration_is_rejected() {
+        let settings = HeightSettings {
+            vertical_exaggeration: 0.0,
+            ..HeightSettings::default()
+        };
+        assert!(HeightScale::from_range(0.0, 10.0, &settings).is_err());
+    }
+
+    fn test_bounds() -> crate::terrain::MercatorBounds {
+        crate::terrain::MercatorBounds {
+            min_x: 0.0,
+            min_y: 0.0,
+            max_x: 2.0,
+            max_y: 2.0,
+        }
+    }
+
+    #[test]
+    fn ter_byte_layout() {
+        // Строка 0 - северная: 100 и 0, южная строка 50 и 25
+        let mut heightmap = HeightGrid::new(2, 2, test_bounds());
+        heightmap.data = vec![100.0, 0.0, 50.0, 25.0];
+        let scale = HeightScale::from_range(0.0, 100.0, &HeightSettings::default()).unwrap();
+        let layers = TerrainLayers {
+            layer_map: vec![0, 1, 1, LAYER_HOLE],
+            materials: vec!["Grass".to_string(), "Rock".to_string()],
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("terrain.ter");
+        write_ter(&path, &heightmap, scale, &layers).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+
+        let mut expected = vec![TER_VERSION, 2, 0, 0, 0];
+        for value in [scale.quantize(50.0), scale.quantize(25.0), u16::MAX, 0] {
+            expected.extend_from_slice(&value.to_le_bytes());
+        }
+        expected.extend_from_slice(&[1, LAYER_HOLE, 0, 1]);
+        expected.extend_from_slice(&[2, 0, 0, 0]);
+        expected.extend_from_slice(b"\x05Grass\x04Rock");
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn ter_rejects_bad_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("terrain.ter");
+        let scale = HeightScale::from_range(0.0, 1.0, &HeightSettings::default()).unwrap();
+        let layers = TerrainLayers::single(2, "Grass");
+
+        let wide = HeightGrid::new(3, 2, test_bounds());
+        assert!(write_ter(&path, &wide, scale, &layers).is_err());
+
+        let square = HeightGrid::new(2, 2, test_bounds());
+        let no_materials = TerrainLayers {
+            layer_map: vec![0; 4],
+            materials: Vec::new(),
+        };
+        assert!(write_ter(&path, &square, scale, &no_materials).is_err());
+        assert!(write_ter(&path, &square, scale, &TerrainLayers::single(3, "Grass")).is_err());
+        let long_name = TerrainLayers::single(2, &"x".repeat(256));
+        assert!(write_ter(&path, &square, scale, &long_name).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn terrain_json_describes_the_ter() {
+        let json = terrain_json("/levels/map/map.ter", "/levels/map/heightmap.png", 4, &TerrainLayers::single(4, "Grass"));
+        assert_eq!(json["size"], 4);
+        assert_eq!(json["heightMapSize"], 16);
+        assert_eq!(json["version"], TER_VERSION);
+        assert_eq!(json["materials"][0], "Grass");
+    }
+}
@@ -0,0 +1,761 @@
+// src-tauri/src/terrain.rs - Сетка высот с привязкой к Web Mercator
+
+use serde::{Deserialize, Serialize};
+
+use crate::BoundingBox;
+
+// Радиус сферы Web Mercator (EPSG:3857) и половина ширины мира в метрах
+pub const EARTH_RADIUS: f64 = 6378137.0;
+pub const MERCATOR_HALF_EXTENT: f64 = std::f64::consts::PI * EARTH_RADIUS;
+
+pub fn lat_lng_to_mercator(lat: f64, lng: f64) -> (f64, f64) {
+    let x = lng.to_radians() * EARTH_RADIUS;
+    let y = (std::f64::consts::FRAC_PI_4 + lat.to_radians() / 2.0).tan().ln() * EARTH_RADIUS;
+    (x, y)
+}
+
+// Долгота приводится к [-180, 180), так что x за антимеридианом тоже допустим
+pub fn mercator_to_lat_lng(x: f64, y: f64) -> (f64, f64) {
+    let lng = ((x / EARTH_RADIUS).to_degrees() + 180.0).rem_euclid(360.0) - 180.0;
+    let lat = (2.0 * (y / EARTH_RADIUS).exp().atan() - std::f64::consts::FRAC_PI_2).to_degrees();
+    (lat, lng)
+}
+
+// Границы сетки в метрах Web Mercator (края пикселей, а не центры)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MercatorBounds {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl MercatorBounds {
+    // Для области через антимеридиан восточный край сдвигается на ширину мира,
+    // и max_x выходит за MERCATOR_HALF_EXTENT
+    pub fn from_bbox(bbox: &BoundingBox) -> Self {
+        let (min_x, min_y) = lat_lng_to_mercator(bbox.min_lat, bbox.min_lng);
+        let (mut max_x, max_y) = lat_lng_to_mercator(bbox.max_lat, bbox.max_lng);
+        if bbox.crosses_antimeridian() {
+            max_x += 2.0 * MERCATOR_HALF_EXTENT;
+        }
+        MercatorBounds { min_x, min_y, max_x, max_y }
+    }
+
+    // Границы прямоугольника тайлов x0..=x1, y0..=y1 (y растёт на юг)
+    pub fn from_tile_range(zoom: u32, x0: u32, y0: u32, x1: u32, y1: u32) -> Self {
+        let tile_size = 2.0 * MERCATOR_HALF_EXTENT / 2_f64.powi(zoom as i32);
+        MercatorBounds {
+            min_x: -MERCATOR_HALF_EXTENT + x0 as f64 * tile_size,
+            max_x: -MERCATOR_HALF_EXTENT + (x1 + 1) as f64 * tile_size,
+            min_y: MERCATOR_HALF_EXTENT - (y1 + 1) as f64 * tile_size,
+            max_y: MERCATOR_HALF_EXTENT - y0 as f64 * tile_size,
+        }
+    }
+
+    // Точка в координатах этих границ (с учётом сдвига за антимеридианом)
+    pub fn project(&self, lat: f64, lng: f64) -> (f64, f64) {
+        let (mut x, y) = lat_lng_to_mercator(lat, lng);
+        if self.max_x > MERCATOR_HALF_EXTENT && x < self.min_x {
+            x += 2.0 * MERCATOR_HALF_EXTENT;
+        }
+        (x, y)
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max_y - self.min_y
+    }
+
+    pub fn contains(&self, other: &MercatorBounds) -> bool {
+        other.min_x >= self.min_x
+            && other.max_x <= self.max_x
+            && other.min_y >= self.min_y
+            && other.max_y <= self.max_y
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResampleMethod {
+    Bilinear,
+    Bicubic,
+    // Lanczos-3, при уменьшении ядро растягивается (без алиасинга)
+    Lanczos,
+}
+
+const LANCZOS_A: f64 = 3.0;
+
+// Сетка высот в метрах. Строка 0 - северный край, столбец 0 - западный.
+// valid - маска измеренных данных: в ячейках с false высота неизвестна
+// (0 до заполнения пустот, после - интерполированное значение).
+#[derive(Debug, Clone)]
+pub struct HeightGrid {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f32>,
+    pub valid: Vec<bool>,
+    pub bounds: MercatorBounds,
+}
+
+impl HeightGrid {
+    pub fn new(width: usize, height: usize, bounds: MercatorBounds) -> Self {
+        HeightGrid {
+            width,
+            height,
+            data: vec![0.0; width * height],
+            valid: vec![true; width * height],
+            bounds,
+        }
+    }
+
+    // Сетка без данных: ячейки становятся валидными по мере записи через set
+    pub fn with_nodata(width: usize, height: usize, bounds: MercatorBounds) -> Self {
+        HeightGrid {
+            valid: vec![false; width * height],
+            ..HeightGrid::new(width, height, bounds)
+        }
+    }
+
+    // Тестовая сетка w x h с пикселем 1 м, северо-западный угол в (0, h); значение = f(x, y)
+    #[cfg(test)]
+    pub fn from_fn(width: usize, height: usize, f: impl Fn(usize, usize) -> f32) -> Self {
+        let bounds = MercatorBounds { min_x: 0.0, min_y: 0.0, max_x: width as f64, max_y: height as f64 };
+        let mut grid = HeightGrid::new(width, height, bounds);
+        for y in 0..height {
+            for x in 0..width {
+                grid.set(x, y, f(x, y));
+            }
+        }
+        grid
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.data[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: f32) {
+        let i = y * self.width + x;
+        self.data[i] = value;
+        self.valid[i] = true;
+    }
+
+    pub fn is_valid(&self, x: usize, y: usize) -> bool {
+        self.valid[y * self.width + x]
+    }
+
+    pub fn invalid_count(&self) -> usize {
+        self.valid.iter().filter(|v| !**v).count()
+    }
+
+    // Размер пикселя в метрах Web Mercator по x и y
+    pub fn pixel_size(&self) -> (f64, f64) {
+        (
+            self.bounds.width() / self.width as f64,
+            self.bounds.height() / self.height as f64,
+        )
+    }
+
+    pub fn min_max(&self) -> (f32, f32) {
+        let min_h = self.data.iter().fold(f32::INFINITY, |a, &b| a.min(b));
+        let max_h = self.data.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+        (min_h, max_h)
+    }
+
+    // Дробные координаты пикселя (относительно центров) для точки в Web Mercator
+    pub fn mercator_to_pixel(&self, x: f64, y: f64) -> (f64, f64) {
+        let (dx, dy) = self.pixel_size();
+        (
+            (x - self.bounds.min_x) / dx - 0.5,
+            (self.bounds.max_y - y) / dy - 0.5,
+        )
+    }
+
+    // Значение с ограничением координат краями сетки
+    fn get_clamped(&self, x: isize, y: isize) -> f32 {
+        let cx = x.clamp(0, self.width as isize - 1) as usize;
+        let cy = y.clamp(0, self.height as isize - 1) as usize;
+        self.get(cx, cy)
+    }
+
+    pub fn sample(&self, fx: f64, fy: f64, method: ResampleMethod) -> f32 {
+        match method {
+            ResampleMethod::Bilinear => self.sample_bilinear(fx, fy),
+            ResampleMethod::Bicubic => self.sample_bicubic(fx, fy),
+            ResampleMethod::Lanczos => self.sample_lanczos(fx, fy),
+        }
+    }
+
+    fn sample_bilinear(&self, fx: f64, fy: f64) -> f32 {
+        let x0 = fx.floor();
+        let y0 = fy.floor();
+        let tx = (fx - x0) as f32;
+        let ty = (fy - y0) as f32;
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let top = self.get_clamped(x0, y0) * (1.0 - tx) + self.get_clamped(x0 + 1, y0) * tx;
+        let bottom = self.get_clamped(x0, y0 + 1) * (1.0 - tx) + self.get_clamped(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    // Интерполяция только по ячейкам с данными; None, если рядом данных нет
+    // или точка за пределами сетки
+    pub fn sample_valid(&self, fx: f64, fy: f64, method: ResampleMethod) -> Option<f32> {
+        if fx < -0.5 || fy < -0.5 || fx > self.width as f64 - 0.5 || fy > self.height as f64 - 0.5 {
+            return None;
+        }
+
+        let x0 = fx.floor() as isize;
+        let y0 = fy.floor() as isize;
+        let valid_at = |x: isize, y: isize| {
+            let cx = x.clamp(0, self.width as isize - 1) as usize;
+            let cy = y.clamp(0, self.height as isize - 1) as usize;
+            self.is_valid(cx, cy)
+        };
+
+        // Бикубическая и Lanczos только если всё их окно с данными
+        let window = match method {
+            ResampleMethod::Bilinear => None,
+            ResampleMethod::Bicubic => Some(-1..=2),
+            ResampleMethod::Lanczos => Some(1 - LANCZOS_A as isize..=LANCZOS_A as isize),
+        };
+        if let Some(window) = window {
+            if window.clone().all(|j| window.clone().all(|i| valid_at(x0 + i, y0 + j))) {
+                return Some(self.sample(fx, fy, method));
+            }
+        }
+
+        let tx = fx - x0 as f64;
+        let ty = fy - y0 as f64;
+        let taps = [
+            (x0, y0, (1.0 - tx) * (1.0 - ty)),
+            (x0 + 1, y0, tx * (1.0 - ty)),
+            (x0, y0 + 1, (1.0 - tx) * ty),
+            (x0 + 1, y0 + 1, tx * ty),
+        ];
+        let mut sum = 0.0;
+        let mut weight = 0.0;
+        for (x, y, w) in taps {
+            if w > 0.0 && valid_at(x, y) {
+                sum += self.get_clamped(x, y) as f64 * w;
+                weight += w;
+            }
+        }
+        if weight > 0.0 {
+            Some((sum / weight) as f32)
+        } else {
+            None
+        }
+    }
+
+    // Catmull-Rom по окну 4x4
+    fn sample_bicubic(&self, fx: f64, fy: f64) -> f32 {
+        let x0 = fx.floor();
+        let y0 = fy.floor();
+        let tx = (fx - x0) as f32;
+        let ty = (fy - y0) as f32;
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let mut rows = [0.0f32; 4];
+        for (j, row) in rows.iter_mut().enumerate() {
+            let y = y0 - 1 + j as isize;
+            *row = cubic(
+                self.get_clamped(x0 - 1, y),
+                self.get_clamped(x0, y),
+                self.get_clamped(x0 + 1, y),
+                self.get_clamped(x0 + 2, y),
+                tx,
+            );
+        }
+        cubic(rows[0], rows[1], rows[2], rows[3], ty)
+    }
+
+    // Lanczos по окну 6x6 без масштабирования ядра
+    fn sample_lanczos(&self, fx: f64, fy: f64) -> f32 {
+        let columns = lanczos_taps(fx, 1.0, self.width);
+        let rows = lanczos_taps(fy, 1.0, self.height);
+        clamped_sum(rows.iter().map(|&(y, wy)| {
+            (clamped_sum(columns.iter().map(|&(x, wx)| (self.get(x, y), wx))), wy)
+        }))
+    }
+}
+
+fn lanczos(x: f64) -> f64 {
+    if x == 0.0 {
+        return 1.0;
+    }
+    if x.abs() >= LANCZOS_A {
+        return 0.0;
+    }
+    let px = std::f64::consts::PI * x;
+    LANCZOS_A * px.sin() * (px / LANCZOS_A).sin() / (px * px)
+}
+
+// Индексы (с ограничением краями) и нормированные веса Lanczos вдоль одной оси.
+// scale - сколько исходных пикселей приходится на один пиксель результата.
+fn lanczos_taps(f: f64, scale: f64, len: usize) -> Vec<(usize, f32)> {
+    let scale = scale.max(1.0);
+    let support = LANCZOS_A * scale;
+    let first = (f - support).floor() as isize;
+    let last = (f + support).ceil() as isize;
+
+    let mut taps = Vec::with_capacity((last - first + 1) as usize);
+    let mut total = 0.0;
+    for i in first..=last {
+        let w = lanczos((i as f64 - f) / scale);
+        if w != 0.0 {
+            taps.push((i.clamp(0, len as isize - 1) as usize, w));
+            total += w;
+        }
+    }
+    taps.into_iter().map(|(i, w)| (i, (w / total) as f32)).collect()
+}
+
+// Взвешенная сумма пар (значение, вес), ограниченная диапазоном значений:
+// отрицательные лепестки ядра не дают звона за пределы исходных высот на обрывах
+fn clamped_sum(samples: impl Iterator<Item = (f32, f32)>) -> f32 {
+    let (mut sum, mut low, mut high) = (0.0f32, f32::INFINITY, f32::NEG_INFINITY);
+    for (value, weight) in samples {
+        sum += weight * value;
+        low = low.min(value);
+        high = high.max(value);
+    }
+    sum.max(low).min(high)
+}
+
+fn cubic(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let a = -0.5 * p0 + 1.5 * p1 - 1.5 * p2 + 0.5 * p3;
+    let b = p0 - 2.5 * p1 + 2.0 * p2 - 0.5 * p3;
+    let c = -0.5 * p0 + 0.5 * p2;
+    ((a * t + b) * t + c) * t + p1
+}
+
+// Вырезает из сетки область bounds и пересчитывает её в сетку width x height
+pub fn resample_to_bounds(
+    grid: &HeightGrid,
+    bounds: MercatorBounds,
+    width: usize,
+    height: usize,
+    method: ResampleMethod,
+) -> HeightGrid {
+    let mut out = HeightGrid::new(width, height, bounds);
+    let (dx, dy) = out.pixel_size();
+
+    for y in 0..height {
+        let my = bounds.max_y - (y as f64 + 0.5) * dy;
+        for x in 0..width {
+            let mx = bounds.min_x + (x as f64 + 0.5) * dx;
+            let (fx, fy) = grid.mercator_to_pixel(mx, my);
+            let i = y * width + x;
+            if method != ResampleMethod::Lanczos {
+                out.data[i] = grid.sample(fx, fy, method);
+            }
+            // Маску переносим по ближайшей ячейке
+            let nx = (fx.round() as isize).clamp(0, grid.width as isize - 1) as usize;
+            let ny = (fy.round() as isize).clamp(0, grid.height as isize - 1) as usize;
+            out.valid[i] = grid.is_valid(nx, ny);
+        }
+    }
+
+    if method == ResampleMethod::Lanczos {
+        out.data = resample_lanczos(grid, bounds, width, height);
+    }
+
+    out
+}
+
+// Раздельный Lanczos: сначала по строкам, затем по столбцам
+fn resample_lanczos(grid: &HeightGrid, bounds: MercatorBounds, width: usize, height: usize) -> Vec<f32> {
+    let dx = bounds.width() / width as f64;
+    let dy = bounds.height() / height as f64;
+    let (src_dx, src_dy) = grid.pixel_size();
+
+    let columns: Vec<Vec<(usize, f32)>> = (0..width)
+        .map(|x| {
+            let (fx, _) = grid.mercator_to_pixel(bounds.min_x + (x as f64 + 0.5) * dx, bounds.max_y);
+            lanczos_taps(fx, dx / src_dx, grid.width)
+        })
+        .collect();
+    let rows: Vec<Vec<(usize, f32)>> = (0..height)
+        .map(|y| {
+            let (_, fy) = grid.mercator_to_pixel(bounds.min_x, bounds.max_y - (y as f64 + 0.5) * dy);
+            lanczos_taps(fy, dy / src_dy, grid.height)
+        })
+        .collect();
+
+    // Проход по x только для строк исходной сетки, которые вообще нужны
+    let first_row = rows.iter().flatten().map(|&(r, _)| r).min().unwrap_or(0);
+    let last_row = rows.iter().flatten().map(|&(r, _)| r).max().unwrap_or(0);
+    let mut horizontal = vec![0.0f32; (last_row - first_row + 1) * width];
+    for r in first_row..=last_row {
+        let line = &mut horizontal[(r - first_row) * width..(r - first_row + 1) * width];
+        for (value, taps) in line.iter_mut().zip(&columns) {
+            *value = clamped_sum(taps.iter().map(|&(c, w)| (grid.get(c, r), w)));
+        }
+    }
+
+    let mut data = vec![0.0f32; width * height];
+    for (y, taps) in rows.iter().enumerate() {
+        for x in 0..width {
+            data[y * width + x] =
+                clamped_sum(taps.iter().map(|&(r, w)| (horizontal[(r - first_row) * width + x], w)));
+        }
+    }
+    data
+}
+
+// То же, что resample_to_bounds, но ячейки без данных не участвуют в интерполяции
+// и остаются без данных в результате
+pub fn resample_valid_to_bounds(
+    grid: &HeightGrid,
+    bounds: MercatorBounds,
+    width: usize,
+    height: usize,
+    method: ResampleMethod,
+) -> HeightGrid {
+    let mut out = HeightGrid::with_nodata(width, height, bounds);
+    let (dx, dy) = out.pixel_size();
+
+    for y in 0..height {
+        let my = bounds.max_y - (y as f64 + 0.5) * dy;
+        for x in 0..width {
+            let mx = bounds.min_x + (x as f64 + 0.5) * dx;
+            let (fx, fy) = grid.mercator_to_pixel(mx, my);
+            if let Some(value) = grid.sample_valid(fx, fy, method) {
+                out.set(x, y, value);
+            }
+        }
+    }
+
+    out
+}
+
+// Один скачанный тайл высот в исходном виде (PNG)
+pub struct TerrainTile {
+    pub zoom: u32,
+    pub x: u32,
+    pub y: u32,
+    pub data: Vec<u8>,
+}
+
+// Способ кодирования высоты в RGB пикселе тайла
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TileEncoding {
+    Terrarium,
+    TerrainRgb,
+}
+
+impl TileEncoding {
+    pub fn decode(&self, r: u8, g: u8, b: u8) -> f32 {
+        match self {
+            TileEncoding::Terrarium => decode_terrarium(r, g, b),
+            TileEncoding::TerrainRgb => decode_terrain_rgb(r, g, b),
+        }
+    }
+
+    // None для пикселей без данных: чёрный пиксель (-32768 м в Terrarium)
+    // или высота за пределами того, что бывает на Земле
+    pub fn decode_checked(&self, r: u8, g: u8, b: u8) -> Option<f32> {
+        if r == 0 && g == 0 && b == 0 {
+            return None;
+        }
+        let height = self.decode(r, g, b);
+        if (MIN_PLAUSIBLE_HEIGHT..=MAX_PLAUSIBLE_HEIGHT).contains(&height) {
+            Some(height)
+        } else {
+            None
+        }
+    }
+}
+
+// Марианская впадина и Эверест с запасом
+const MIN_PLAUSIBLE_HEIGHT: f32 = -11500.0;
+const MAX_PLAUSIBLE_HEIGHT: f32 = 9000.0;
+
+// Terrarium format: height = (R * 256 + G + B / 256) - 32768
+pub fn decode_terrarium(r: u8, g: u8, b: u8) -> f32 {
+    (r as f32 * 256.0 + g as f32 + b as f32 / 256.0) - 32768.0
+}
+
+// Mapbox Terrain-RGB: height = -10000 + (R * 65536 + G * 256 + B) * 0.1
+pub fn decode_terrain_rgb(r: u8, g: u8, b: u8) -> f32 {
+    let value = r as f64 * 65536.0 + g as f64 * 256.0 + b as f64;
+    (-10000.0 + value * 0.1) as f32
+}
+
+// Прямоугольник тайлов, который покрывает мозаика
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRange {
+    pub zoom: u32,
+    pub min_x: u32,
+    pub min_y: u32,
+    pub max_x: u32,
+    pub max_y: u32,
+}
+
+impl TileRange {
+    pub fn from_tiles(zoom: u32, tiles: &[(u32, u32)]) -> Option<Self> {
+        Some(TileRange {
+            zoom,
+            min_x: tiles.iter().map(|t| t.0).min()?,
+            min_y: tiles.iter().map(|t| t.1).min()?,
+            max_x: tiles.iter().map(|t| t.0).max()?,
+            max_y: tiles.iter().map(|t| t.1).max()?,
+        })
+    }
+
+    pub fn columns(&self) -> usize {
+        (self.max_x - self.min_x + 1) as usize
+    }
+
+    pub fn rows(&self) -> usize {
+        (self.max_y - self.min_y + 1) as usize
+    }
+}
+
+// Декодирует каждый тайл отдельно и кладёт его в свою ячейку общей мозаики.
+// Ячейки недостающих тайлов и битые пиксели остаются без данных (valid = false).
+pub fn mosaic_tiles(tiles: &[TerrainTile], range: TileRange, encoding: TileEncoding) -> Result<HeightGrid, String> {
+    let zoom = range.zoom;
+
+    let mut decoded = Vec::with_capacity(tiles.len());
+    for tile in tiles {
+        if tile.zoom != zoom
+            || tile.x < range.min_x
+            || tile.x > range.max_x
+            || tile.y < range.min_y
+            || tile.y > range.max_y
+        {
+            return Err(format!(
+                "Tile {}/{}/{} is outside of the mosaic range",
+                tile.zoom, tile.x, tile.y
+            ));
+        }
+        match image::load_from_memory(&tile.data) {
+            Ok(img) => decoded.push((tile, img.to_rgb8())),
+            Err(e) => eprintln!("Failed to decode tile {}/{}/{}: {}", tile.zoom, tile.x, tile.y, e),
+        }
+    }
+
+    let tile_size = match decoded.first() {
+        Some((_, img)) => img.width() as usize,
+        None => return Err("None of the terrain tiles could be decoded".to_string()),
+    };
+
+    let bounds = MercatorBounds::from_tile_range(zoom, range.min_x, range.min_y, range.max_x, range.max_y);
+    let mut grid = HeightGrid::with_nodata(range.columns() * tile_size, range.rows() * tile_size, bounds);
+
+    for (tile, img) in decoded {
+        if img.width() as usize != tile_size || img.height() as usize != tile_size {
+            eprintln!(
+                "Skipping tile {}/{}/{}: unexpected size {}x{}",
+                tile.zoom, tile.x, tile.y, img.width(), img.height()
+            );
+            continue;
+        }
+
+        let offset_x = (tile.x - range.min_x) as usize * tile_size;
+        let offset_y = (tile.y - range.min_y) as usize * tile_size;
+
+        for (px, py, pixel) in img.enumerate_pixels() {
+            if let Some(height) = encoding.decode_checked(pixel[0], pixel[1], pixel[2]) {
+                grid.set(offset_x + px as usize, offset_y + py as usize, height);
+            }
+        }
+    }
+
+    Ok(grid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terrarium_decodes_sea_level_and_fractions() {
+        // 128 * 256 = 32768 -> 0 м; B / 256 даёт дробную часть
+        assert_eq!(decode_terrarium(128, 0, 0), 0.0);
+        assert_eq!(decode_terrarium(128, 100, 128), 100.5);
+        assert_eq!(decode_terrarium(127, 255, 0), -1.0);
+    }
+
+    #[test]
+    fn terrain_rgb_decodes_with_tenth_metre_step() {
+        // 100000 * 0.1 - 10000 = 0 м
+        assert_eq!(decode_terrain_rgb(1, 134, 160), 0.0);
+        assert!((decode_terrain_rgb(1, 134, 165) - 0.5).abs() < 1e-3);
+        assert_eq!(decode_terrain_rgb(0, 0, 0), -10000.0);
+    }
+
+    #[test]
+    fn implausible_pixels_are_nodata() {
+        assert_eq!(TileEncoding::Terrarium.decode_checked(0, 0, 0), None);
+        assert_eq!(TileEncoding::TerrainRgb.decode_checked(0, 0, 0), None);
+        // 255 * 256 - 32768 = 32512 м
+        assert_eq!(TileEncoding::Terrarium.decode_checked(255, 0, 0), None);
+        assert_eq!(TileEncoding::Terrarium.decode_checked(128, 10, 0), Some(10.0));
+    }
+
+
+    #[test]
+    fn resample_crops_to_exact_bounds() {
+        let grid = HeightGrid::from_fn(8, 8, |x, y| (x * 10 + y) as f32);
+        // Столбцы 2..6 и строки 1..4 сверху при том же размере пикселя
+        let bounds = MercatorBounds { min_x: 2.0, min_y: 4.0, max_x: 6.0, max_y: 7.0 };
+
+        for method in [ResampleMethod::Bilinear, ResampleMethod::Bicubic] {
+            let out = resample_to_bounds(&grid, bounds, 4, 3, method);
+            assert_eq!(out.bounds, bounds);
+            assert_eq!((out.width, out.height), (4, 3));
+            for y in 0..3 {
+                for x in 0..4 {
+                    assert!((out.get(x, y) - grid.get(x + 2, y + 1)).abs() < 1e-4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn resample_clamps_edge_pixels() {
+        let grid = HeightGrid::from_fn(4, 4, |x, _| x as f32 * 10.0);
+        // Вдвое мельче и с запасом за краями: центры крайних пикселей лежат вне центров исходных
+        let bounds = MercatorBounds { min_x: -1.0, min_y: 0.0, max_x: 5.0, max_y: 4.0 };
+        let out = resample_to_bounds(&grid, bounds, 12, 8, ResampleMethod::Bilinear);
+
+        for y in 0..8 {
+            assert_eq!(out.get(0, y), 0.0);
+            assert_eq!(out.get(1, y), 0.0);
+            assert_eq!(out.get(11, y), 30.0);
+        }
+        assert!((out.get(5, 0) - 12.5).abs() < 1e-4);
+
+        let cubic = resample_to_bounds(&grid, bounds, 12, 8, ResampleMethod::Bicubic);
+        assert!(cubic.data.iter().all(|v| v.is_finite()));
+        assert_eq!(cubic.get(0, 0), 0.0);
+        assert_eq!(cubic.get(11, 7), 30.0);
+    }
+
+    #[test]
+    fn lanczos_reproduces_a_linear_ramp() {
+        let grid = HeightGrid::from_fn(32, 32, |x, y| x as f32 * 2.0 + y as f32);
+        // Внутренняя область со смещением на полпикселя, окно ядра не касается краёв
+        let bounds = MercatorBounds { min_x: 8.5, min_y: 8.5, max_x: 24.5, max_y: 24.5 };
+        let (fine, coarse) = (
+            resample_to_bounds(&grid, bounds, 32, 32, ResampleMethod::Lanczos),
+            resample_to_bounds(&grid, bounds, 8, 8, ResampleMethod::Lanczos),
+        );
+        for out in [fine, coarse] {
+            let (dx, dy) = out.pixel_size();
+            for y in 0..out.height {
+                for x in 0..out.width {
+                    // Центр пикселя результата в координатах исходной сетки
+                    let sx = bounds.min_x + (x as f64 + 0.5) * dx - 0.5;
+                    let sy = 32.0 - (bounds.max_y - (y as f64 + 0.5) * dy) - 0.5;
+                    let expected = (sx * 2.0 + sy) as f32;
+                    // Lanczos воспроизводит наклон с точностью до долей пикселя, не бита в бит
+                    assert!((out.get(x, y) - expected).abs() < 0.1, "{} vs {}", out.get(x, y), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lanczos_does_not_ring_on_a_step() {
+        let grid = HeightGrid::from_fn(16, 16, |x, _| if x < 8 { 0.0 } else { 100.0 });
+        let bounds = grid.bounds;
+
+        let up = resample_to_bounds(&grid, bounds, 48, 48, ResampleMethod::Lanczos);
+        let down = resample_to_bounds(&grid, bounds, 6, 6, ResampleMethod::Lanczos);
+        for value in up.data.iter().chain(&down.data) {
+            assert!((0.0..=100.0).contains(value), "{}", value);
+        }
+        // Вдали от обрыва уровни не искажаются
+        assert_eq!(up.get(0, 0), 0.0);
+        assert_eq!(up.get(47, 0), 100.0);
+        // Точечная выборка ограничена так же
+        let near = grid.sample(8.7, 3.0, ResampleMethod::Lanczos);
+        assert!((0.0..=100.0).contains(&near), "{}", near);
+    }
+
+    #[test]
+    fn resample_carries_the_valid_mask() {
+        let mut grid = HeightGrid::from_fn(4, 4, |x, _| x as f32);
+        // Левая половина без данных
+        for y in 0..4 {
+            grid.valid[y * 4] = false;
+            grid.valid[y * 4 + 1] = false;
+        }
+        let out = resample_to_bounds(&grid, grid.bounds, 8, 8, ResampleMethod::Bilinear);
+
+        assert!(!out.is_valid(3, 0));
+        assert!(out.is_valid(4, 0));
+        // Доля пустот в отчёте (void_filled_percent) считается по этой маске
+        assert_eq!(out.invalid_count() as f64 * 100.0 / out.data.len() as f64, 50.0);
+    }
+
+    #[test]
+    fn valid_resample_ignores_and_keeps_voids() {
+        let mut grid = HeightGrid::from_fn(4, 4, |x, _| x as f32 * 10.0);
+        // Левый столбец без данных и с мусором вместо высоты
+        for y in 0..4 {
+            grid.data[y * 4] = 1000.0;
+            grid.valid[y * 4] = false;
+        }
+
+        let same = resample_valid_to_bounds(&grid, grid.bounds, 4, 4, ResampleMethod::Bicubic);
+        assert_eq!(same.invalid_count(), 4);
+        assert!(!same.is_valid(0, 2));
+        assert_eq!(same.get(2, 2), 20.0);
+
+        // С запасом за краями: вне исходной сетки данных нет
+        let bounds = MercatorBounds { min_x: 0.0, min_y: 0.0, max_x: 6.0, max_y: 4.0 };
+        let out = resample_valid_to_bounds(&grid, bounds, 12, 8, ResampleMethod::Bilinear);
+        assert!(!out.is_valid(0, 0) && !out.is_valid(11, 0));
+        for (i, &value) in out.data.iter().enumerate() {
+            if out.valid[i] {
+                assert!((10.0..=30.0).contains(&value), "{}", value);
+            }
+        }
+    }
+
+    fn terrarium_png(size: u32, height: u8) -> Vec<u8> {
+        let img = image::RgbImage::from_pixel(size, size, image::Rgb([128, height, 0]));
+        let mut data = Vec::new();
+        image::DynamicImage::ImageRgb8(img)
+            .write_to(&mut std::io::Cursor::new(&mut data), image::ImageOutputFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn tiles_land_in_their_mosaic_cells() {
+        let tile = |x, y, height| TerrainTile { zoom: 1, x, y, data: terrarium_png(2, height) };
+        // Тайла (1, 1) нет, а (0, 1) не раскодируется
+        let tiles = [
+            tile(0, 0, 10),
+            tile(1, 0, 20),
+            TerrainTile { zoom: 1, x: 0, y: 1, data: vec![1, 2, 3] },
+        ];
+        let range = TileRange::from_tiles(1, &[(0, 0), (1, 1)]).unwrap();
+        let grid = mosaic_tiles(&tiles, range, TileEncoding::Terrarium).unwrap();
+
+        assert_eq!((grid.width, grid.height), (4, 4));
+        assert_eq!(grid.bounds, MercatorBounds::from_tile_range(1, 0, 0, 1, 1));
+        assert_eq!(grid.get(1, 1), 10.0);
+        assert_eq!(grid.get(2, 0), 20.0);
+        assert!(!grid.is_valid(0, 2));
+        assert!(!grid.is_valid(3, 3));
+        assert_eq!(grid.invalid_count(), 8);
+    }
+
+    #[test]
+    fn tile_outside_range_is_an_error() {
+        let tiles = [TerrainTile { zoom: 1, x: 1, y: 1, data: terrarium_png(2, 0) }];
+        let range = TileRange::from_tiles(1, &[(0, 0)]).unwrap();
+        assert!(mosaic_tiles(&tiles, range, TileEncoding::Terrarium).is_err());
+    }
+}
@@ -0,0 +1,299 @@
+// src-tauri/src/voids.rs - Заполнение пустот (ячеек без данных) в сетке высот
+
+use std::collections::VecDeque;
+use std::f64::consts::{PI, TAU};
+
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::HeightGrid;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoidFillMethod {
+    // Обратные расстояния до ближайших ячеек с данными по нескольким направлениям
+    #[default]
+    InverseDistance,
+    // Гармоническая интерполяция (уравнение Лапласа) - гладко сшивает края пустоты
+    Laplacian,
+}
+
+const RAY_DIRECTIONS: usize = 16;
+const LAPLACE_MAX_ITERATIONS: usize = 1000;
+// Максимальное изменение за итерацию (м), после которого считаем, что сошлось
+const LAPLACE_TOLERANCE: f32 = 0.01;
+
+// Заполняет ячейки с valid = false и возвращает их число. Маска не меняется: пересчёт
+// сетки (resample_to_bounds) и фильтры переносят её дальше, и по ней main.rs считает
+// void_filled_percent для итоговой карты, а не для исходной сетки.
+pub fn fill_voids(grid: &mut HeightGrid, method: VoidFillMethod) -> Result<usize, String> {
+    let voids: Vec<usize> = (0..grid.data.len()).filter(|&i| !grid.valid[i]).collect();
+    if voids.is_empty() {
+        return Ok(0);
+    }
+    if voids.len() == grid.data.len() {
+        return Err("Elevation data has no valid cells to fill voids from".to_string());
+    }
+
+    fill_inverse_distance(grid, &voids);
+    if method == VoidFillMethod::Laplacian {
+        // IDW служит начальным приближением, так итерации сходятся намного быстрее
+        relax_laplacian(grid, &voids);
+    }
+
+    Ok(voids.len())
+}
+
+// Для каждой пустой ячейки идём по лучам до первой ячейки с данными
+// и берём среднее с весами 1/d²
+fn fill_inverse_distance(grid: &mut HeightGrid, voids: &[usize]) {
+    let (w, h) = (grid.width as f64, grid.height as f64);
+    let directions: Vec<(f64, f64)> = (0..RAY_DIRECTIONS)
+        .map(|k| {
+            let angle = k as f64 * TAU / RAY_DIRECTIONS as f64;
+            (angle.cos(), angle.sin())
+        })
+        .collect();
+
+    let valid_mean = {
+        let (sum, count) = grid
+            .data
+            .iter()
+            .zip(&grid.valid)
+            .filter(|(_, v)| **v)
+            .fold((0.0f64, 0usize), |(s, c), (d, _)| (s + *d as f64, c + 1));
+        (sum / count as f64) as f32
+    };
+
+    let mut filled = Vec::with_capacity(voids.len());
+    for &i in voids {
+        let x = (i % grid.width) as f64;
+        let y = (i / grid.width) as f64;
+        let mut sum = 0.0;
+        let mut weight = 0.0;
+
+        for &(dx, dy) in &directions {
+            let mut step = 1.0;
+            loop {
+                let cx = (x + dx * step).round();
+                let cy = (y + dy * step).round();
+                if cx < 0.0 || cy < 0.0 || cx >= w || cy >= h {
+                    break;
+                }
+                let j = cy as usize * grid.width + cx as usize;
+                if grid.valid[j] {
+                    let wgt = 1.0 / (step * step);
+                    sum += grid.data[j] as f64 * wgt;
+                    weight += wgt;
+                    break;
+                }
+                step += 1.0;
+            }
+        }
+
+        filled.push(if weight > 0.0 { (sum / weight) as f32 } else { valid_mean });
+    }
+
+    for (&i, value) in voids.iter().zip(filled) {
+        grid.data[i] = value;
+    }
+}
+
+// Последовательная верхняя релаксация отдельно по каждой связной пустоте: мелкие пустоты
+// сходятся за несколько проходов и больше не пересчитываются вместе с крупными
+fn relax_laplacian(grid: &mut HeightGrid, voids: &[usize]) {
+    let regions = void_regions(grid, voids);
+    let mut slowest = 0;
+    let mut unconverged = 0;
+    for region in &regions {
+        match relax_region(grid, region) {
+            Some(iterations) => slowest = slowest.max(iterations),
+            None => unconverged += 1,
+        }
+    }
+
+    if unconverged > 0 {
+        println!(
+            "Laplacian void fill: {} of {} voids stopped after {} iterations",
+            unconverged,
+            regions.len(),
+            LAPLACE_MAX_ITERATIONS
+        );
+    } else {
+        println!("Laplacian void fill: {} voids converged in at most {} iterations", regions.len(), slowest);
+    }
+}
+
+// Связные (по четырём соседям) области пустых ячеек
+fn void_regions(grid: &HeightGrid, voids: &[usize]) -> Vec<Vec<usize>> {
+    let (w, h) = (grid.width, grid.height);
+    let mut seen = vec![false; w * h];
+    let mut regions = Vec::new();
+    let mut queue = VecDeque::new();
+    for &start in voids {
+        if seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        queue.push_back(start);
+        let mut region = Vec::new();
+        while let Some(i) = queue.pop_front() {
+            region.push(i);
+            let (x, y) = (i % w, i / w);
+            let neighbours = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < w).then(|| i + 1),
+                (y > 0).then(|| i - w),
+                (y + 1 < h).then(|| i + w),
+            ];
+            for j in neighbours.into_iter().flatten() {
+                if !grid.valid[j] && !seen[j] {
+                    seen[j] = true;
+                    queue.push_back(j);
+                }
+            }
+        }
+        regions.push(region);
+    }
+    regions
+}
+
+// Ячейки с данными - граничные условия, на краях сетки учитываются только соседи внутри неё.
+// Параметр релаксации - оптимальный для квадрата размером с область. Возвращает число
+// итераций или None, если за LAPLACE_MAX_ITERATIONS не сошлось.
+fn relax_region(grid: &mut HeightGrid, region: &[usize]) -> Option<usize> {
+    let (w, h) = (grid.width, grid.height);
+    let (mut x0, mut y0, mut x1, mut y1) = (w, h, 0, 0);
+    for &i in region {
+        let (x, y) = (i % w, i / w);
+        x0 = x0.min(x);
+        y0 = y0.min(y);
+        x1 = x1.max(x);
+        y1 = y1.max(y);
+    }
+    let size = (x1 - x0).max(y1 - y0) + 2;
+    let omega = (2.0 / (1.0 + (PI / size as f64).sin())) as f32;
+
+    for iteration in 0..LAPLACE_MAX_ITERATIONS {
+        let mut max_change = 0.0f32;
+        for &i in region {
+            let (x, y) = (i % w, i / w);
+            let mut sum = 0.0;
+            let mut count = 0.0;
+            if x > 0 {
+                sum += grid.data[i - 1];
+                count += 1.0;
+            }
+            if x + 1 < w {
+                sum += grid.data[i + 1];
+                count += 1.0;
+            }
+            if y > 0 {
+                sum += grid.data[i - w];
+                count += 1.0;
+            }
+            if y + 1 < h {
+                sum += grid.data[i + w];
+                count += 1.0;
+            }
+            if count == 0.0 {
+                continue;
+            }
+
+            let change = omega * (sum / count - grid.data[i]);
+            grid.data[i] += change;
+            max_change = max_change.max(change.abs());
+        }
+
+        if max_change < LAPLACE_TOLERANCE {
+            return Some(iteration + 1);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{apply_filters, FilterMask, FilterStep, GaussianSettings, HeightFilter};
+    use crate::terrain::{resample_to_bounds, MercatorBounds, ResampleMethod};
+
+    // Наклонная плоскость с двумя дырами разного размера
+    fn plane_with_holes() -> HeightGrid {
+        let mut grid = HeightGrid::from_fn(64, 64, |x, y| 100.0 + 0.5 * x as f32 - 0.25 * y as f32);
+        for (i, valid) in grid.valid.iter_mut().enumerate() {
+            let (x, y) = (i % 64, i / 64);
+            *valid = !((10..40).contains(&x) && (5..50).contains(&y) || (x, y) == (55, 55));
+        }
+        grid
+    }
+
+    #[test]
+    fn laplacian_restores_plane() {
+        let mut grid = plane_with_holes();
+        let expected = grid.data.clone();
+        grid.data.iter_mut().zip(&grid.valid).filter(|(_, v)| !**v).for_each(|(d, _)| *d = 0.0);
+
+        let filled = fill_voids(&mut grid, VoidFillMethod::Laplacian).unwrap();
+        assert_eq!(filled, 30 * 45 + 1);
+        for (a, b) in grid.data.iter().zip(&expected) {
+            assert!((a - b).abs() < 0.05, "{} vs {}", a, b);
+        }
+        // Маска не меняется
+        assert!(!grid.is_valid(55, 55));
+    }
+
+    #[test]
+    fn inverse_distance_follows_the_plane() {
+        let mut grid = plane_with_holes();
+        let expected = grid.data.clone();
+        grid.data.iter_mut().zip(&grid.valid).filter(|(_, v)| !**v).for_each(|(d, _)| *d = 0.0);
+
+        fill_voids(&mut grid, VoidFillMethod::InverseDistance).unwrap();
+        let errors: Vec<f32> = (0..grid.data.len())
+            .filter(|&i| !grid.valid[i])
+            .map(|i| (grid.data[i] - expected[i]).abs())
+            .collect();
+        // Одиночная дыра окружена данными симметрично - плоскость восстанавливается точно
+        assert!((grid.get(55, 55) - expected[55 * 64 + 55]).abs() < 1e-4);
+        // В большой дыре (30x45 ячеек при уклоне 0.5 м/ячейку) IDW тянется к ближним краям
+        let max_error = errors.iter().copied().fold(0.0, f32::max);
+        let mean_error = errors.iter().sum::<f32>() / errors.len() as f32;
+        assert!(max_error < 2.5, "{}", max_error);
+        assert!(mean_error < 1.25, "{}", mean_error);
+    }
+
+    #[test]
+    fn filled_cells_stay_marked_through_the_pipeline() {
+        let mut grid = plane_with_holes();
+        let filled = fill_voids(&mut grid, VoidFillMethod::InverseDistance).unwrap();
+        assert_eq!(grid.invalid_count(), filled);
+
+        // Как в process_terrain_data: пересчёт в итоговую сетку и фильтры
+        let mut heightmap = resample_to_bounds(&grid, grid.bounds, 32, 32, ResampleMethod::Bicubic);
+        let smoothing = FilterStep {
+            filter: HeightFilter::Gaussian(GaussianSettings { sigma_m: 2.0 }),
+            mask: FilterMask::default(),
+        };
+        apply_filters(&mut heightmap, &[smoothing], 2.0).unwrap();
+
+        let source_percent = filled as f64 * 100.0 / grid.data.len() as f64;
+        let final_percent = heightmap.invalid_count() as f64 * 100.0 / heightmap.data.len() as f64;
+        assert!((final_percent - source_percent).abs() < 1.0, "{} vs {}", final_percent, source_percent);
+    }
+
+    #[test]
+    fn void_regions_are_split() {
+        let grid = plane_with_holes();
+        let voids: Vec<usize> = (0..grid.data.len()).filter(|&i| !grid.valid[i]).collect();
+        let mut sizes: Vec<usize> = void_regions(&grid, &voids).iter().map(Vec::len).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![1, 30 * 45]);
+    }
+
+    #[test]
+    fn all_void_grid_is_an_error() {
+        let bounds = MercatorBounds { min_x: 0.0, min_y: 0.0, max_x: 4.0, max_y: 4.0 };
+        let mut grid = HeightGrid::with_nodata(4, 4, bounds);
+        assert!(fill_voids(&mut grid, VoidFillMethod::InverseDistance).is_err());
+    }
+}
@@ -0,0 +1,568 @@
+// src-tauri/src/water.rs - Озёра и реки: врезка русел и котловин в рельеф, уровни воды
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::blend;
+use crate::buildings::footprint_cells;
+use crate::roads::{centerline_profile, check_non_negative, point_segment_distance, sample_profile, smoothstep};
+use crate::terrain::HeightGrid;
+use crate::voids::{self, VoidFillMethod};
+use crate::{latlon_to_beamng, OSMElement};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WaterSettings {
+    pub enabled: bool,
+    // Глубина дна под уровнем воды (м)
+    pub lake_depth_m: f64,
+    pub river_depth_m: f64,
+    pub stream_depth_m: f64,
+    // Ширина русла, если у линии нет тега width (м)
+    pub river_width_m: f64,
+    pub canal_width_m: f64,
+    pub stream_width_m: f64,
+    // Ширина берега: от уреза воды до полной глубины и от уреза до естественного рельефа (м)
+    pub bank_width_m: f64,
+    // Сглаживание продольного профиля реки перед тем, как сделать его невозрастающим (м)
+    pub profile_smoothing_m: f64,
+}
+
+impl Default for WaterSettings {
+    fn default() -> Self {
+        WaterSettings {
+            enabled: true,
+            lake_depth_m: 3.0,
+            river_depth_m: 2.0,
+            stream_depth_m: 0.5,
+            river_width_m: 10.0,
+            canal_width_m: 6.0,
+            stream_width_m: 2.0,
+            bank_width_m: 6.0,
+            profile_smoothing_m: 20.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WaterKind {
+    // Площадные: natural=water (уровень плоский) и русло реки полигоном (уровень по берегам)
+    Lake,
+    RiverArea,
+    // Линейные waterway=*, уровень падает по направлению линии (по течению)
+    River,
+    Canal,
+    Stream,
+}
+
+// Точка водного объекта в координатах уровня: вершина контура или узел линии.
+// surface_m - высота воды: после carve_water исходная (м), в water_levels.json - в уровне
+// (HeightScale::level_height, как у объектов).
+#[derive(Debug, Clone, Serialize)]
+pub struct WaterPoint {
+    pub x: f32,
+    pub z: f32,
+    pub surface_m: f32,
+    pub width_m: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WaterBody {
+    pub osm_id: i64,
+    pub kind: WaterKind,
+    pub points: Vec<WaterPoint>,
+}
+
+pub struct WaterReport {
+    pub bodies: Vec<WaterBody>,
+    // Ячейки под водой
+    pub wet: Vec<bool>,
+}
+
+struct WaterShape {
+    osm_id: i64,
+    kind: WaterKind,
+    // Вершины в координатах сетки (ячейки, дробные) и в координатах уровня
+    points: Vec<(f64, f64)>,
+    level_points: Vec<(f32, f32)>,
+    width_m: f64,
+}
+
+// Понижает рельеф под водой: дно = уровень воды - глубина, берега плавно сходят к урезу.
+// Уровни берутся из рельефа до врезки, рельеф только понижается. Ячейки с protected = true
+// (дороги, площадки) не меняются.
+pub fn carve_water(
+    heightmap: &mut HeightGrid,
+    elements: &[OSMElement],
+    square_size: f64,
+    settings: &WaterSettings,
+    protected: &[bool],
+) -> Result<WaterReport, String> {
+    let (w, h) = (heightmap.width, heightmap.height);
+    let mut report = WaterReport {
+        bodies: Vec::new(),
+        wet: vec![false; w * h],
+    };
+    if !settings.enabled {
+        return Ok(report);
+    }
+    check_non_negative(
+        "Water",
+        &[
+            ("lake_depth_m", settings.lake_depth_m),
+            ("river_depth_m", settings.river_depth_m),
+            ("stream_depth_m", settings.stream_depth_m),
+            ("river_width_m", settings.river_width_m),
+            ("canal_width_m", settings.canal_width_m),
+            ("stream_width_m", settings.stream_width_m),
+            ("bank_width_m", settings.bank_width_m),
+            ("profile_smoothing_m", settings.profile_smoothing_m),
+        ],
+    )?;
+
+    let shapes = collect_shapes(elements, heightmap, square_size, settings);
+    let river = river_surface(heightmap, &shapes);
+    let mut lowered = heightmap.data.clone();
+    for shape in shapes {
+        let surfaces = match shape.kind {
+            WaterKind::Lake | WaterKind::RiverArea => carve_area(
+                heightmap,
+                &shape,
+                river.as_ref(),
+                square_size,
+                settings,
+                &mut lowered,
+                &mut report.wet,
+            ),
+            _ => carve_line(heightmap, &shape, square_size, settings, &mut lowered, &mut report.wet),
+        };
+        let Some(surfaces) = surfaces else {
+            continue;
+        };
+        report.bodies.push(WaterBody {
+            osm_id: shape.osm_id,
+            kind: shape.kind,
+            points: shape
+                .level_points
+                .iter()
+                .zip(surfaces)
+                .map(|(&(x, z), surface_m)| WaterPoint {
+                    x,
+                    z,
+                    surface_m,
+                    width_m: shape.width_m as f32,
+                })
+                .collect(),
+        });
+    }
+
+    let mut changed = 0;
+    for (i, value) in lowered.into_iter().enumerate() {
+        if protected[i] {
+            report.wet[i] = false;
+        } else if value < heightmap.data[i] {
+            heightmap.data[i] = value;
+            changed += 1;
+        }
+    }
+
+    println!(
+        "Carved {} water bodies: {} cells lowered, {} under water",
+        report.bodies.len(),
+        changed,
+        report.wet.iter().filter(|&&v| v).count()
+    );
+    Ok(report)
+}
+
+// Уровни воды для водных объектов уровня: water_levels.json в папке уровня (попадает в мод)
+pub fn save_water_levels(bodies: &[WaterBody], dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(bodies).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join("water_levels.json"), json).map_err(|e| e.to_string())
+}
+
+// Замкнутые линии natural=water и waterway=riverbank, линии waterway=river|canal|stream.
+// Водотоки в трубах и тоннелях (tunnel=culvert) рельеф не трогают.
+fn collect_shapes(
+    elements: &[OSMElement],
+    heightmap: &HeightGrid,
+    square_size: f64,
+    settings: &WaterSettings,
+) -> Vec<WaterShape> {
+    let nodes: HashMap<i64, (f64, f64)> = elements
+        .iter()
+        .filter(|e| e.element_type == "node")
+        .filter_map(|e| Some((e.id, (e.lat?, e.lon?))))
+        .collect();
+
+    let mut shapes = Vec::new();
+    for element in elements {
+        if element.element_type != "way" {
+            continue;
+        }
+        let tag = |key: &str| element.tags.get(key).map(String::as_str);
+        let kind = match (tag("natural"), tag("waterway"), tag("water")) {
+            (Some("water"), _, Some("river" | "stream" | "canal")) => WaterKind::RiverArea,
+            (Some("water"), _, _) => WaterKind::Lake,
+            (_, Some("riverbank"), _) => WaterKind::RiverArea,
+            (_, Some("river"), _) => WaterKind::River,
+            (_, Some("canal"), _) => WaterKind::Canal,
+            (_, Some("stream"), _) => WaterKind::Stream,
+            _ => continue,
+        };
+        let is_line = matches!(kind, WaterKind::River | WaterKind::Canal | WaterKind::Stream);
+        if is_line && tag("tunnel").is_some_and(|t| t != "no") {
+            continue;
+        }
+
+        let Some(way_nodes) = &element.nodes else {
+            continue;
+        };
+        let coords: Vec<(f64, f64)> = way_nodes.iter().filter_map(|id| nodes.get(id)).copied().collect();
+        let closed = way_nodes.len() > 3 && way_nodes.first() == way_nodes.last();
+        if coords.len() < 2 || (!is_line && (!closed || coords.len() < 4)) {
+            continue;
+        }
+
+        let width_m = match kind {
+            WaterKind::Lake | WaterKind::RiverArea => 0.0,
+            _ => tag_width(tag("width")).unwrap_or(match kind {
+                WaterKind::River => settings.river_width_m,
+                WaterKind::Canal => settings.canal_width_m,
+                _ => settings.stream_width_m,
+            }),
+        };
+        shapes.push(WaterShape {
+            osm_id: element.id,
+            kind,
+            points: coords
+                .iter()
+                .map(|&(lat, lon)| {
+                    let (mx, my) = heightmap.bounds.project(lat, lon);
+                    heightmap.mercator_to_pixel(mx, my)
+                })
+                .collect(),
+            level_points: coords
+                .iter()
+                .map(|&(lat, lon)| {
+                    let (x, _, z) = latlon_to_beamng(lat, lon, heightmap, square_size);
+                    (x, z)
+                })
+                .collect(),
+            width_m,
+        });
+    }
+    shapes
+}
+
+// width=12, width=12 m, width=2,5
+fn tag_width(value: Option<&str>) -> Option<f64> {
+    let value = value?.trim().trim_end_matches('m').trim().replace(',', ".");
+    value.parse::<f64>().ok().filter(|w| w.is_finite() && *w > 0.0)
+}
+
+// Уровень воды в руслах-полигонах: участок карты, покрывающий их все
+struct RiverSurface {
+    x0: usize,
+    y0: usize,
+    width: usize,
+    data: Vec<f32>,
+}
+
+// Гармоническая интерполяция уровня от берегов - одна на все русла-полигоны сразу.
+// None, если таких полигонов нет.
+fn river_surface(heightmap: &HeightGrid, shapes: &[WaterShape]) -> Option<RiverSurface> {
+    let (w, h) = (heightmap.width, heightmap.height);
+    let areas: Vec<_> = shapes
+        .iter()
+        .filter(|s| s.kind == WaterKind::RiverArea)
+        .filter_map(|s| Some((s, footprint_cells(&s.points, 2.0, w, h)?)))
+        .collect();
+    let (x0, y0, x1, y1) = areas
+        .iter()
+        .map(|(_, cells)| *cells)
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))?;
+    let (sw, sh) = (x1 - x0 + 1, y1 - y0 + 1);
+
+    let mut grid = HeightGrid::new(sw, sh, heightmap.bounds);
+    for y in 0..sh {
+        for x in 0..sw {
+            grid.set(x, y, heightmap.get(x0 + x, y0 + y));
+        }
+    }
+    for (shape, (ax0, ay0, ax1, ay1)) in &areas {
+        let aw = ax1 - ax0 + 1;
+        let inside = scanline_mask(&shape.points, *ax0, *ay0, aw, ay1 - ay0 + 1);
+        for j in (0..inside.len()).filter(|&j| inside[j]) {
+            grid.valid[(ay0 - y0 + j / aw) * sw + ax0 - x0 + j % aw] = false;
+        }
+    }
+    // Без берегов (всё под водой) уровень русла берётся как у озера
+    voids::fill_voids(&mut grid, VoidFillMethod::Laplacian).ok()?;
+
+    Some(RiverSurface {
+        x0,
+        y0,
+        width: sw,
+        data: grid.data,
+    })
+}
+
+// Площадной объект. Уровень озера плоский - самая низкая ячейка берега, у русла реки
+// берётся из river_surface. Глубина растёт от уреза на ширине берега.
+// Возвращает уровень воды в вершинах контура; None, если объект вне карты или меньше ячейки.
+fn carve_area(
+    heightmap: &HeightGrid,
+    shape: &WaterShape,
+    river: Option<&RiverSurface>,
+    square_size: f64,
+    settings: &WaterSettings,
+    lowered: &mut [f32],
+    wet: &mut [bool],
+) -> Option<Vec<f32>> {
+    let (w, h) = (heightmap.width, heightmap.height);
+    let (x0, y0, x1, y1) = footprint_cells(&shape.points, 2.0, w, h)?;
+    let (sw, sh) = (x1 - x0 + 1, y1 - y0 + 1);
+    let inside = scanline_mask(&shape.points, x0, y0, sw, sh);
+    if !inside.contains(&true) {
+        return None;
+    }
+
+    let height_at = |j: usize| heightmap.get(x0 + j % sw, y0 + j / sw);
+    let shore_min = {
+        let mut min = f32::INFINITY;
+        for j in (0..sw * sh).filter(|&j| !inside[j]) {
+            let (sx, sy) = (j % sw, j / sw);
+            let touches = (sx > 0 && inside[j - 1])
+                || (sx + 1 < sw && inside[j + 1])
+                || (sy > 0 && inside[j - sw])
+                || (sy + 1 < sh && inside[j + sw]);
+            if touches {
+                min = min.min(height_at(j));
+            }
+        }
+        // Весь участок карты под водой - берега не видно
+        if min.is_finite() {
+            min
+        } else {
+            (0..sw * sh).map(height_at).fold(f32::INFINITY, f32::min)
+        }
+    };
+
+    let surface = |j: usize| match (shape.kind, river) {
+        (WaterKind::RiverArea, Some(r)) => r.data[(y0 + j / sw - r.y0) * r.width + x0 + j % sw - r.x0],
+        _ => shore_min,
+    };
+
+    let depth = match shape.kind {
+        WaterKind::RiverArea => settings.river_depth_m,
+        _ => settings.lake_depth_m,
+    } as f32;
+    let bank = settings.bank_width_m / square_size;
+    let distance = blend::distance_to_invalid(&inside, sw, sh);
+    for j in (0..sw * sh).filter(|&j| inside[j]) {
+        // Урез воды проходит между последней ячейкой воды и первой ячейкой берега
+        let from_shore = distance[j] as f64 - 0.5;
+        let s = if bank > 0.0 { smoothstep((from_shore / bank) as f32) } else { 1.0 };
+        let i = (y0 + j / sw) * w + x0 + j % sw;
+        lowered[i] = lowered[i].min(surface(j) - depth * s);
+        wet[i] = true;
+    }
+
+    Some(
+        shape
+            .points
+            .iter()
+            .map(|&(fx, fy)| {
+                let sx = (fx.round() - x0 as f64).clamp(0.0, sw as f64 - 1.0) as usize;
+                let sy = (fy.round() - y0 as f64).clamp(0.0, sh as f64 - 1.0) as usize;
+                surface(sy * sw + sx)
+            })
+            .collect(),
+    )
+}
+
+// Линейный водоток. Уровень - сглаженный профиль рельефа вдоль линии, который не растёт
+// по течению. Поперёк: параболическое дно до полуширины, затем берег до рельефа.
+// Возвращает уровень воды в узлах; None, если линия целиком вне карты.
+fn carve_line(
+    heightmap: &HeightGrid,
+    shape: &WaterShape,
+    square_size: f64,
+    settings: &WaterSettings,
+    lowered: &mut [f32],
+    wet: &mut [bool],
+) -> Option<Vec<f32>> {
+    let (w, h) = (heightmap.width, heightmap.height);
+    let inside_map = |&(fx, fy): &(f64, f64)| fx >= 0.0 && fy >= 0.0 && fx <= w as f64 - 1.0 && fy <= h as f64 - 1.0;
+    if !shape.points.iter().any(inside_map) {
+        return None;
+    }
+
+    let (cumulative, mut profile) =
+        centerline_profile(heightmap, &shape.points, settings.profile_smoothing_m / square_size);
+    for k in 1..profile.len() {
+        profile[k] = profile[k].min(profile[k - 1]);
+    }
+
+    let depth = match shape.kind {
+        WaterKind::Stream => settings.stream_depth_m,
+        _ => settings.river_depth_m,
+    } as f32;
+    // Узкий ручей всё равно занимает хотя бы одну ячейку
+    let half_width = (shape.width_m / 2.0 / square_size).max(0.5);
+    let bank = settings.bank_width_m / square_size;
+    let reach = half_width + bank;
+
+    for k in 0..shape.points.len() - 1 {
+        let (a, b) = (shape.points[k], shape.points[k + 1]);
+        let x0 = ((a.0.min(b.0) - reach).floor().max(0.0)) as usize;
+        let x1 = ((a.0.max(b.0) + reach).ceil().min(w as f64 - 1.0)).max(0.0) as usize;
+        let y0 = ((a.1.min(b.1) - reach).floor().max(0.0)) as usize;
+        let y1 = ((a.1.max(b.1) + reach).ceil().min(h as f64 - 1.0)).max(0.0) as usize;
+        let length = cumulative[k + 1] - cumulative[k];
+
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let (distance, t) = point_segment_distance((x as f64, y as f64), a, b);
+                if distance > reach {
+                    continue;
+                }
+                let i = y * w + x;
+                let surface = sample_profile(&profile, cumulative[k] + t * length);
+                let target = if distance <= half_width {
+                    wet[i] = true;
+                    surface - depth * (1.0 - (distance / half_width).powi(2) as f32)
+                } else {
+                    let terrain = heightmap.data[i];
+                    let s = ((distance - half_width) / bank.max(f64::EPSILON)) as f32;
+                    surface + (terrain - surface).max(0.0) * smoothstep(s)
+                };
+                lowered[i] = lowered[i].min(target);
+            }
+        }
+    }
+
+    Some(cumulative.iter().map(|&s| sample_profile(&profile, s)).collect())
+}
+
+// Ячейки, центры которых внутри многоугольника (чётно-нечётное правило построчно)
+pub fn scanline_mask(polygon: &[(f64, f64)], x0: usize, y0: usize, sw: usize, sh: usize) -> Vec<bool> {
+    let mut mask = vec![false; sw * sh];
+    let mut crossings = Vec::new();
+    for sy in 0..sh {
+        let py = (y0 + sy) as f64;
+        crossings.clear();
+        let mut j = polygon.len() - 1;
+        for i in 0..polygon.len() {
+            let (a, b) = (polygon[i], polygon[j]);
+            if (a.1 > py) != (b.1 > py) {
+                crossings.push(a.0 + (py - a.1) * (b.0 - a.0) / (b.1 - a.1));
+            }
+            j = i;
+        }
+        crossings.sort_unstable_by(f64::total_cmp);
+        for pair in crossings.chunks_exact(2) {
+            let start = (pair[0].floor() + 1.0 - x0 as f64).max(0.0) as usize;
+            let end = (pair[1].ceil() - 1.0 - x0 as f64).min(sw as f64 - 1.0);
+            if end < 0.0 {
+                continue;
+            }
+            for sx in start..=end as usize {
+                mask[sy * sw + sx] = true;
+            }
+        }
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn river_area(points: &[(f64, f64)]) -> WaterShape {
+        WaterShape {
+            osm_id: 1,
+            kind: WaterKind::RiverArea,
+            points: points.to_vec(),
+            level_points: Vec::new(),
+            width_m: 0.0,
+        }
+    }
+
+    #[test]
+    fn scanline_mask_takes_cell_centres() {
+        let square = [(1.5, 1.5), (4.5, 1.5), (4.5, 3.5), (1.5, 3.5), (1.5, 1.5)];
+        let mask = scanline_mask(&square, 0, 0, 6, 5);
+        let inside: Vec<(usize, usize)> = (0..30).filter(|&j| mask[j]).map(|j| (j % 6, j / 6)).collect();
+        assert_eq!(inside, vec![(2, 2), (3, 2), (4, 2), (2, 3), (3, 3), (4, 3)]);
+
+        // Смещение участка не меняет результат
+        let shifted = scanline_mask(&square, 2, 2, 3, 2);
+        assert!(shifted.iter().all(|&v| v));
+    }
+
+    #[test]
+    fn river_surface_is_filled_once_for_all_areas() {
+        let heightmap = HeightGrid::from_fn(60, 40, |x, _| 50.0 - 0.2 * x as f32);
+        let shapes = [
+            river_area(&[(5.0, 10.0), (25.0, 10.0), (25.0, 20.0), (5.0, 20.0), (5.0, 10.0)]),
+            river_area(&[(35.0, 15.0), (55.0, 15.0), (55.0, 30.0), (35.0, 30.0), (35.0, 15.0)]),
+        ];
+        let river = river_surface(&heightmap, &shapes).unwrap();
+        assert_eq!((river.x0, river.y0), (3, 8));
+        assert_eq!(river.width, 57 - 3 + 1);
+
+        // На наклонной плоскости гармонический уровень совпадает с плоскостью
+        for (x, y) in [(15, 15), (45, 22)] {
+            let level = river.data[(y - river.y0) * river.width + x - river.x0];
+            assert!((level - heightmap.get(x, y)).abs() < 0.05, "{} vs {}", level, heightmap.get(x, y));
+        }
+    }
+
+    #[test]
+    fn area_depth_depends_on_kind() {
+        let heightmap = HeightGrid::from_fn(30, 20, |x, _| 50.0 - 0.2 * x as f32);
+        let settings = WaterSettings {
+            lake_depth_m: 4.0,
+            river_depth_m: 1.5,
+            bank_width_m: 0.0,
+            ..WaterSettings::default()
+        };
+        let mut shape = river_area(&[(5.0, 5.0), (25.0, 5.0), (25.0, 15.0), (5.0, 15.0), (5.0, 5.0)]);
+        let carve = |shape: &WaterShape| {
+            let river = river_surface(&heightmap, std::slice::from_ref(shape));
+            let mut lowered = heightmap.data.clone();
+            let mut wet = vec![false; lowered.len()];
+            carve_area(&heightmap, shape, river.as_ref(), 1.0, &settings, &mut lowered, &mut wet).unwrap();
+            assert!(wet[10 * 30 + 15]);
+            lowered[10 * 30 + 15]
+        };
+
+        // Русло: дно на river_depth_m ниже наклонного уровня
+        let bed = carve(&shape);
+        assert!((heightmap.get(15, 10) - bed - 1.5).abs() < 0.05, "river bed at {}", bed);
+
+        // Озеро: дно на lake_depth_m ниже самого низкого берега (x = 25)
+        shape.kind = WaterKind::Lake;
+        assert_eq!(carve(&shape), 45.0 - 4.0);
+    }
+
+    #[test]
+    fn no_river_areas_no_surface() {
+        let heightmap = HeightGrid::from_fn(10, 10, |_, _| 0.0);
+        assert!(river_surface(&heightmap, &[]).is_none());
+    }
+
+    #[test]
+    fn width_tags() {
+        assert_eq!(tag_width(Some("12")), Some(12.0));
+        assert_eq!(tag_width(Some("2,5 m")), Some(2.5));
+        assert_eq!(tag_width(Some("wide")), None);
+        assert_eq!(tag_width(Some("-3")), None);
+    }
+}
@@ -19,6 +19,85 @@ interface GenerationProgress {
   progress: number;
 }
 
+interface ZoomChoice {
+  zoom: number;
+  tile_count: number;
+  meters_per_pixel: number;
+  target_meters_per_pixel: number;
+  limited_by_max_tiles: boolean;
+}
+
+interface GenerationResult {
+  message: string;
+  elevation_source: string;
+  terrain_zoom: ZoomChoice | null;
+  missing_tiles: string[];
+  void_filled_percent: number;
+  square_size: number;
+  steep_buildings: SteepBuilding[];
+  terrain_summary: TerrainSummary;
+}
+
+interface TerrainSummary {
+  mean_slope_deg: number;
+  max_slope_deg: number;
+  steep_percent: number;
+  mean_roughness_m: number;
+}
+
+interface SteepBuilding {
+  osm_id: number;
+  slope_deg: number;
+  lat: number;
+  lon: number;
+}
+
+const TERRAIN_SIZES = [512, 1024, 2048, 4096, 8192];
+
+// Фильтры карты высот (порядок важен: сначала выбросы, потом ступени, потом сглаживание)
+type FilterStep =
+  | { type: 'despike'; threshold_m: number }
+  | { type: 'deterrace'; step_m: number }
+  | { type: 'gaussian'; sigma_m: number; mask?: { max_slope_deg: number } };
+
+type ElevationSourceConfig =
+  | { type: 'terrarium' }
+  | { type: 'terrain_rgb'; access_token: string }
+  | {
+      type: 'tiles';
+      url_templates: string[];
+      encoding: 'terrarium' | 'terrain_rgb';
+      headers: Record<string, string>;
+    }
+  | { type: 'open_topo_data'; endpoint?: string; dataset: string; grid_size: number }
+  | { type: 'geo_tiff'; path: string; epsg: number | null }
+  | { type: 'hgt'; directory: string }
+  | { type: 'ascii_grid'; path: string; epsg: number | null }
+  | { type: 'lidar'; paths: string[]; epsg: number | null; interpolation: 'idw' | 'tin' };
+
+// Непустые строки textarea
+const parseLines = (text: string): string[] =>
+  text
+    .split('\n')
+    .map((line) => line.trim())
+    .filter((line) => line.length > 0);
+
+// Строки вида "Header-Name: value"
+const parseHeaders = (text: string): Record<string, string> => {
+  const headers: Record<string, string> = {};
+  for (const line of parseLines(text)) {
+    const colon = line.indexOf(':');
+    if (colon > 0) {
+      headers[line.slice(0, colon).trim()] = line.slice(colon + 1).trim();
+    }
+  }
+  return headers;
+};
+
+// Leaflet отдаёт долготы за пределами ±180° после прокрутки карты по кругу.
+// После нормализации min_lng > max_lng означает область через антимеридиан.
+const wrapLng = (lng: number): number => ((((lng + 180) % 360) + 360) % 360) - 180;
+
 function MapSelector({ onBoundsChange }: { onBoundsChange: (bounds: BoundingBox) => void }) {
   const [selectionStart, setSelectionStart] = useState<[number, number] | null>(null);
   const [selectionEnd, setSelectionEnd] = useState<[number, number] | null>(null);
@@ -33,9 +112,9 @@ function MapSelector({ onBoundsChange }: { onBoundsChange: (bounds: BoundingBox)
           
           const bbox: BoundingBox = {
             min_lat: Math.min(selectionStart[0], e.latlng.lat),
-            min_lng: Math.min(selectionStart[1], e.latlng.lng),
+            min_lng: wrapLng(Math.min(selectionStart[1], e.latlng.lng)),
             max_lat: Math.max(selectionStart[0], e.latlng.lat),
-            max_lng: Math.max(selectionStart[1], e.latlng.lng),
+            max_lng: wrapLng(Math.max(selectionStart[1], e.latlng.lng)),
           };
           onBoundsChange(bbox);
         }
@@ -91,6 +170,47 @@ function App() {
     progress: 0,
   });
   const [result, setResult] = useState<string>('');
+  const [terrainZoom, setTerrainZoom] = useState<ZoomChoice | null>(null);
+  const [missingTiles, setMissingTiles] = useState<string[]>([]);
+  const [voidFilledPercent, setVoidFilledPercent] = useState<number>(0);
+  const [terrainSummary, setTerrainSummary] = useState<TerrainSummary | null>(null);
+  const [elevationSource, setElevationSource] = useState<ElevationSourceConfig['type']>('terrarium');
+  const [mapboxToken, setMapboxToken] = useState<string>('');
+  const [lastSource, setLastSource] = useState<string>('');
+  const [demPath, setDemPath] = useState<string>('');
+  const [demEpsg, setDemEpsg] = useState<string>('');
+  const [fillWithTerrarium, setFillWithTerrarium] = useState<boolean>(true);
+  const [offline, setOffline] = useState<boolean>(false);
+  const [openTopoEndpoint, setOpenTopoEndpoint] = useState<string>('');
+  const [openTopoDataset, setOpenTopoDataset] = useState<string>('aster30m');
+  const [openTopoGridSize, setOpenTopoGridSize] = useState<number>(100);
+  const [voidFill, setVoidFill] = useState<'inverse_distance' | 'laplacian'>('inverse_distance');
+  const [tileUrls, setTileUrls] = useState<string>('');
+  const [tileEncoding, setTileEncoding] = useState<'terrarium' | 'terrain_rgb'>('terrarium');
+  const [tileHeaders, setTileHeaders] = useState<string>('');
+  const [overpassEndpoints, setOverpassEndpoints] = useState<string>('');
+  const [lidarInterpolation, setLidarInterpolation] = useState<'idw' | 'tin'>('idw');
+  const [terrainSize, setTerrainSize] = useState<number>(2048);
+  const [squareSize, setSquareSize] = useState<string>('');
+  const [resampleMethod, setResampleMethod] = useState<'bilinear' | 'bicubic' | 'lanczos'>('bicubic');
+  const [lastSquareSize, setLastSquareSize] = useState<number>(0);
+  const [heightDatum, setHeightDatum] = useState<'lowest' | 'sea_level'>('lowest');
+  const [exaggeration, setExaggeration] = useState<number>(1);
+  const [despike, setDespike] = useState<boolean>(true);
+  const [deterrace, setDeterrace] = useState<boolean>(false);
+  const [smoothFlats, setSmoothFlats] = useState<boolean>(false);
+  const [microDetail, setMicroDetail] = useState<boolean>(true);
+  const [demResolution, setDemResolution] = useState<string>('');
+  const [carveRoads, setCarveRoads] = useState<boolean>(true);
+  const [buildingPads, setBuildingPads] = useState<boolean>(true);
+  const [carveWater, setCarveWater] = useState<boolean>(true);
+  const [erosion, setErosion] = useState<boolean>(false);
+  const [erosionSeed, setErosionSeed] = useState<number>(1);
+  const [exportAnalysis, setExportAnalysis] = useState<boolean>(false);
+  const [border, setBorder] = useState<'none' | 'falloff' | 'mirror' | 'rim'>('none');
+  const [borderWidth, setBorderWidth] = useState<number>(200);
+  const [padBase, setPadBase] = useState<'min' | 'mean' | 'entrance'>('mean');
+  const [steepBuildings, setSteepBuildings] = useState<SteepBuilding[]>([]);
 
   useEffect(() => {
     const unlisten = listen<GenerationProgress>('generation-progress', (event) => {
@@ -114,6 +234,59 @@ function App() {
     }
   };
 
+  const selectDemFile = async () => {
+    const selected = await open({
+      directory: elevationSource === 'hgt',
+      multiple: false,
+      title: elevationSource === 'hgt' ? 'Выберите папку с тайлами SRTM' : 'Выберите файл DEM',
+      filters:
+        elevationSource === 'ascii_grid'
+          ? [{ name: 'ESRI ASCII grid', extensions: ['asc'] }]
+          : elevationSource === 'geo_tiff'
+            ? [{ name: 'GeoTIFF', extensions: ['tif', 'tiff'] }]
+            : elevationSource === 'lidar'
+              ? [{ name: 'LAS/LAZ', extensions: ['las', 'laz'] }]
+              : undefined,
+    });
+
+    if (selected && typeof selected === 'string') {
+      setDemPath(selected);
+    }
+  };
+
+  const buildElevationSource = (): ElevationSourceConfig => {
+    const epsg = demEpsg.trim() ? parseInt(demEpsg, 10) : null;
+    switch (elevationSource) {
+      case 'terrain_rgb':
+        return { type: 'terrain_rgb', access_token: mapboxToken };
+      case 'tiles':
+        return {
+          type: 'tiles',
+          url_templates: parseLines(tileUrls),
+          encoding: tileEncoding,
+          headers: parseHeaders(tileHeaders),
+        };
+      case 'open_topo_data':
+        return {
+          type: 'open_topo_data',
+          // Пустое поле - публичный api.opentopodata.org
+          ...(openTopoEndpoint.trim() && { endpoint: openTopoEndpoint.trim() }),
+          dataset: openTopoDataset.trim(),
+          grid_size: openTopoGridSize,
+        };
+      case 'geo_tiff':
+        return { type: 'geo_tiff', path: demPath, epsg };
+      case 'hgt':
+        return { type: 'hgt', directory: demPath };
+      case 'ascii_grid':
+        return { type: 'ascii_grid', path: demPath, epsg };
+      case 'lidar':
+        return { type: 'lidar', paths: [demPath], epsg, interpolation: lidarInterpolation };
+      default:
+        return { type: elevationSource };
+    }
+  };
+
   const generateTerrain = async () => {
     if (!bbox || !outputPath) {
       alert('Пожалуйста, выберите область на карте и путь для сохранения');
@@ -122,13 +295,66 @@ function App() {
 
     setIsGenerating(true);
     setResult('');
+    setTerrainZoom(null);
+    setMissingTiles([]);
+    setVoidFilledPercent(0);
+    setSteepBuildings([]);
+    setTerrainSummary(null);
+
+    // Локальный DEM первым по приоритету, Terrarium заполняет остальное
+    const isLocalDem = ['geo_tiff', 'hgt', 'ascii_grid', 'lidar'].includes(elevationSource);
+    const elevation_sources: ElevationSourceConfig[] =
+      isLocalDem && fillWithTerrarium
+        ? [buildElevationSource(), { type: 'terrarium' }]
+        : [buildElevationSource()];
+
+    const filters: FilterStep[] = [];
+    if (despike) filters.push({ type: 'despike', threshold_m: 5 });
+    if (deterrace) filters.push({ type: 'deterrace', step_m: 1 });
+    // Обрывы круче 20° не трогаем
+    if (smoothFlats) filters.push({ type: 'gaussian', sigma_m: 3, mask: { max_slope_deg: 20 } });
 
     try {
-      const response = await invoke<string>('generate_terrain', {
+      const response = await invoke<GenerationResult>('generate_terrain', {
         bbox,
         outputPath,
+        settings: {
+          elevation_sources,
+          terrain_resolution: terrainSize,
+          // Пустое поле - рельеф покрывает выбранную область
+          square_size: squareSize.trim() ? parseFloat(squareSize) : null,
+          resample_method: resampleMethod,
+          filters,
+          detail: {
+            enabled: microDetail,
+            // Пустое поле - шаг сетки источника
+            source_resolution_m: demResolution.trim() ? parseFloat(demResolution) : null,
+          },
+          border: {
+            treatment: border === 'none' ? { type: 'none' } : { type: border, width_m: borderWidth },
+          },
+          roads: { enabled: carveRoads },
+          building_pads: { enabled: buildingPads, base: padBase },
+          water: { enabled: carveWater },
+          erosion: { enabled: erosion, seed: erosionSeed },
+          height: { datum: heightDatum, vertical_exaggeration: exaggeration },
+          analysis: { export_debug: exportAnalysis },
+          cache: { offline },
+          void_fill: voidFill,
+          // Пустое поле - стандартный overpass-api.de
+          ...(parseLines(overpassEndpoints).length > 0 && {
+            overpass: { endpoints: parseLines(overpassEndpoints) },
+          }),
+        },
       });
-      setResult(response);
+      setResult(response.message);
+      setTerrainZoom(response.terrain_zoom);
+      setMissingTiles(response.missing_tiles);
+      setVoidFilledPercent(response.void_filled_percent);
+      setLastSquareSize(response.square_size);
+      setSteepBuildings(response.steep_buildings);
+      setTerrainSummary(response.terrain_summary);
+      setLastSource(response.elevation_source);
     } catch (error) {
       setResult(`Ошибка: ${error}`);
     } finally {
@@ -166,6 +392,340 @@ function App() {
           {outputPath && <p className="path-display">Путь: {outputPath}</p>}
         </section>
 
+        <section className="output-section">
+          <h2>Источник высот</h2>
+          <select
+            value={elevationSource}
+            onChange={(e) => setElevationSource(e.target.value as ElevationSourceConfig['type'])}
+            disabled={isGenerating}
+          >
+            <option value="terrarium">AWS Terrarium</option>
+            <option value="terrain_rgb">Mapbox Terrain-RGB</option>
+            <option value="tiles">Свой тайловый сервер</option>
+            <option value="open_topo_data">OpenTopoData</option>
+            <option value="geo_tiff">GeoTIFF (локальный файл)</option>
+            <option value="hgt">SRTM .hgt (локальная папка)</option>
+            <option value="ascii_grid">ESRI ASCII grid .asc</option>
+            <option value="lidar">LiDAR .las/.laz (точки земли)</option>
+          </select>
+          {elevationSource === 'terrain_rgb' && (
+            <input
+              type="text"
+              placeholder="Mapbox access token"
+              value={mapboxToken}
+              onChange={(e) => setMapboxToken(e.target.value)}
+              disabled={isGenerating}
+            />
+          )}
+          {elevationSource === 'open_topo_data' && (
+            <div>
+              <input
+                type="text"
+                placeholder="Сервер (по умолчанию https://api.opentopodata.org/v1)"
+                value={openTopoEndpoint}
+                onChange={(e) => setOpenTopoEndpoint(e.target.value)}
+                disabled={isGenerating}
+              />
+              <input
+                type="text"
+                placeholder="Набор данных (srtm30m, aster30m, eudem25m...)"
+                value={openTopoDataset}
+                onChange={(e) => setOpenTopoDataset(e.target.value)}
+                disabled={isGenerating}
+              />
+              <label>
+                Точек по стороне:{' '}
+                <input
+                  type="number"
+                  min={2}
+                  value={openTopoGridSize}
+                  onChange={(e) => setOpenTopoGridSize(parseInt(e.target.value, 10) || 2)}
+                  disabled={isGenerating}
+                />
+              </label>
+            </div>
+          )}
+          {elevationSource === 'tiles' && (
+            <div>
+              <textarea
+                placeholder={'URL шаблоны, по одному в строке (основной, затем зеркала)\nhttps://tiles.example.com/{z}/{x}/{y}.png'}
+                value={tileUrls}
+                onChange={(e) => setTileUrls(e.target.value)}
+                disabled={isGenerating}
+              />
+              <select
+                value={tileEncoding}
+                onChange={(e) => setTileEncoding(e.target.value as 'terrarium' | 'terrain_rgb')}
+                disabled={isGenerating}
+              >
+                <option value="terrarium">Кодирование Terrarium</option>
+                <option value="terrain_rgb">Кодирование Terrain-RGB</option>
+              </select>
+              <textarea
+                placeholder="Заголовки, по одному в строке (Authorization: Bearer ...)"
+                value={tileHeaders}
+                onChange={(e) => setTileHeaders(e.target.value)}
+                disabled={isGenerating}
+              />
+            </div>
+          )}
+          {['geo_tiff', 'hgt', 'ascii_grid', 'lidar'].includes(elevationSource) && (
+            <div>
+              <button onClick={selectDemFile} className="select-button" disabled={isGenerating}>
+                {elevationSource === 'hgt' ? '📁 Выбрать папку SRTM' : '📄 Выбрать файл DEM'}
+              </button>
+              {demPath && <p className="path-display">DEM: {demPath}</p>}
+              {elevationSource !== 'hgt' && (
+                <input
+                  type="text"
+                  placeholder="EPSG (если не указан в файле)"
+                  value={demEpsg}
+                  onChange={(e) => setDemEpsg(e.target.value)}
+                  disabled={isGenerating}
+                />
+              )}
+              {elevationSource === 'lidar' && (
+                <select
+                  value={lidarInterpolation}
+                  onChange={(e) => setLidarInterpolation(e.target.value as 'idw' | 'tin')}
+                  disabled={isGenerating}
+                >
+                  <option value="idw">Интерполяция: обратные расстояния (IDW)</option>
+                  <option value="tin">Интерполяция: триангуляция (TIN)</option>
+                </select>
+              )}
+              <label>
+                <input
+                  type="checkbox"
+                  checked={fillWithTerrarium}
+                  onChange={(e) => setFillWithTerrarium(e.target.checked)}
+                  disabled={isGenerating}
+                />
+                Дополнить данными AWS Terrarium за пределами DEM
+              </label>
+            </div>
+          )}
+          <label>
+            Размер рельефа:{' '}
+            <select
+              value={terrainSize}
+              onChange={(e) => setTerrainSize(parseInt(e.target.value, 10))}
+              disabled={isGenerating}
+            >
+              {TERRAIN_SIZES.map((size) => (
+                <option key={size} value={size}>
+                  {size} x {size}
+                </option>
+              ))}
+            </select>
+          </label>
+          <input
+            type="number"
+            min={0.1}
+            step={0.1}
+            placeholder="Размер ячейки, м (по умолчанию - по выбранной области)"
+            value={squareSize}
+            onChange={(e) => setSquareSize(e.target.value)}
+            disabled={isGenerating}
+          />
+          {bbox && squareSize.trim() && parseFloat(squareSize) > 0 && (
+            <p>
+              Сторона карты: {((terrainSize * parseFloat(squareSize)) / 1000).toFixed(2)} км
+            </p>
+          )}
+          <select
+            value={resampleMethod}
+            onChange={(e) => setResampleMethod(e.target.value as 'bilinear' | 'bicubic' | 'lanczos')}
+            disabled={isGenerating}
+          >
+            <option value="bilinear">Пересчёт: билинейный</option>
+            <option value="bicubic">Пересчёт: бикубический</option>
+            <option value="lanczos">Пересчёт: Lanczos</option>
+          </select>
+          <select
+            value={heightDatum}
+            onChange={(e) => setHeightDatum(e.target.value as 'lowest' | 'sea_level')}
+            disabled={isGenerating}
+          >
+            <option value="lowest">Высота 0: самая низкая точка</option>
+            <option value="sea_level">Высота 0: уровень моря</option>
+          </select>
+          <label>
+            Вертикальное преувеличение:{' '}
+            <input
+              type="number"
+              min={0.1}
+              max={10}
+              step={0.1}
+              value={exaggeration}
+              onChange={(e) => setExaggeration(parseFloat(e.target.value) || 1)}
+              disabled={isGenerating}
+            />
+          </label>
+          <label>
+            <input
+              type="checkbox"
+              checked={despike}
+              onChange={(e) => setDespike(e.target.checked)}
+              disabled={isGenerating}
+            />
+            Удалить выбросы высот
+          </label>
+          <label>
+            <input
+              type="checkbox"
+              checked={deterrace}
+              onChange={(e) => setDeterrace(e.target.checked)}
+              disabled={isGenerating}
+            />
+            Убрать ступени квантования (шаг 1 м)
+          </label>
+          <label>
+            <input
+              type="checkbox"
+              checked={smoothFlats}
+              onChange={(e) => setSmoothFlats(e.target.checked)}
+              disabled={isGenerating}
+            />
+            Сгладить пологие участки (без обрывов)
+          </label>
+          <label>
+            <input
+              type="checkbox"
+              checked={microDetail}
+              onChange={(e) => setMicroDetail(e.target.checked)}
+              disabled={isGenerating}
+            />
+            Добавить мелкий рельеф, если данные грубее сетки
+          </label>
+          {microDetail && (
+            <input
+              type="number"
+              min={0.1}
+              step={0.1}
+              placeholder="Шаг исходного DEM, м (по умолчанию - по сетке источника)"
+              value={demResolution}
+              onChange={(e) => setDemResolution(e.target.value)}
+              disabled={isGenerating}
+            />
+          )}
+          <label>
+            <input
+              type="checkbox"
+              checked={carveRoads}
+              onChange={(e) => setCarveRoads(e.target.checked)}
+              disabled={isGenerating}
+            />
+            Врезать дороги в рельеф
+          </label>
+          <label>
+            <input
+              type="checkbox"
+              checked={buildingPads}
+              onChange={(e) => setBuildingPads(e.target.checked)}
+              disabled={isGenerating}
+            />
+            Выровнять площадки под зданиями
+          </label>
+          {buildingPads && (
+            <select
+              value={padBase}
+              onChange={(e) => setPadBase(e.target.value as 'min' | 'mean' | 'entrance')}
+              disabled={isGenerating}
+            >
+              <option value="mean">Высота площадки: средняя по контуру</option>
+              <option value="min">Высота площадки: минимальная</option>
+              <option value="entrance">Высота площадки: у входа</option>
+            </select>
+          )}
+          <select
+            value={border}
+            onChange={(e) => setBorder(e.target.value as 'none' | 'falloff' | 'mirror' | 'rim')}
+            disabled={isGenerating}
+          >
+            <option value="none">Край карты: как есть</option>
+            <option value="falloff">Край карты: спуск к самой низкой точке</option>
+            <option value="mirror">Край карты: зеркальное отражение</option>
+            <option value="rim">Край карты: горный вал</option>
+          </select>
+          {border !== 'none' && (
+            <label>
+              Ширина края, м:{' '}
+              <input
+                type="number"
+                min={0}
+                step={10}
+                value={borderWidth}
+                onChange={(e) => setBorderWidth(parseFloat(e.target.value) || 0)}
+                disabled={isGenerating}
+              />
+            </label>
+          )}
+          <label>
+            <input
+              type="checkbox"
+              checked={carveWater}
+              onChange={(e) => setCarveWater(e.target.checked)}
+              disabled={isGenerating}
+            />
+            Врезать озёра и реки в рельеф
+          </label>
+          <label>
+            <input
+              type="checkbox"
+              checked={erosion}
+              onChange={(e) => setErosion(e.target.checked)}
+              disabled={isGenerating}
+            />
+            Эрозия (водная и осыпание склонов)
+          </label>
+          {erosion && (
+            <label>
+              Seed эрозии:{' '}
+              <input
+                type="number"
+                min={0}
+                step={1}
+                value={erosionSeed}
+                onChange={(e) => setErosionSeed(parseInt(e.target.value, 10) || 0)}
+                disabled={isGenerating}
+              />
+            </label>
+          )}
+          <label>
+            <input
+              type="checkbox"
+              checked={exportAnalysis}
+              onChange={(e) => setExportAnalysis(e.target.checked)}
+              disabled={isGenerating}
+            />
+            Сохранить карты уклона, экспозиции, кривизны и шероховатости (debug)
+          </label>
+          <select
+            value={voidFill}
+            onChange={(e) => setVoidFill(e.target.value as 'inverse_distance' | 'laplacian')}
+            disabled={isGenerating}
+          >
+            <option value="inverse_distance">Пустоты: обратные расстояния (IDW)</option>
+            <option value="laplacian">Пустоты: гладкая интерполяция (Лаплас)</option>
+          </select>
+          <label>
+            <input
+              type="checkbox"
+              checked={offline}
+              onChange={(e) => setOffline(e.target.checked)}
+              disabled={isGenerating}
+            />
+            Офлайн режим (только данные из кэша)
+          </label>
+          <textarea
+            placeholder="Серверы Overpass API, по одному в строке (по умолчанию overpass-api.de)"
+            value={overpassEndpoints}
+            onChange={(e) => setOverpassEndpoints(e.target.value)}
+            disabled={isGenerating}
+          />
+        </section>
+
         <section className="generate-section">
           <h2>3. Генерация карты</h2>
           <button
@@ -196,6 +756,44 @@ function App() {
               {!result.includes('Ошибка') && (
                 <div className="mod-info">
                   <h3>✅ Мод успешно создан!</h3>
+                  {terrainZoom && (
+                    <p>
+                      🗻 Рельеф ({lastSource}): zoom {terrainZoom.zoom}, тайлов: {terrainZoom.tile_count},{' '}
+                      {terrainZoom.meters_per_pixel.toFixed(1)} м/пиксель
+                      {terrainZoom.limited_by_max_tiles && ' (ограничено лимитом тайлов)'}
+                    </p>
+                  )}
+                  {missingTiles.length > 0 && (
+                    <p>
+                      ⚠️ Не удалось получить тайлов: {missingTiles.length} ({missingTiles.slice(0, 5).join(', ')}
+                      {missingTiles.length > 5 && ', ...'})
+                    </p>
+                  )}
+                  {lastSquareSize > 0 && (
+                    <p>
+                      📐 Рельеф {terrainSize} x {terrainSize}, ячейка {lastSquareSize.toFixed(2)} м (
+                      {((terrainSize * lastSquareSize) / 1000).toFixed(2)} км)
+                    </p>
+                  )}
+                  {terrainSummary && (
+                    <p>
+                      🏔️ Уклон: средний {terrainSummary.mean_slope_deg.toFixed(1)}°, максимальный{' '}
+                      {terrainSummary.max_slope_deg.toFixed(0)}°, круче 30°: {terrainSummary.steep_percent.toFixed(1)}% карты
+                    </p>
+                  )}
+                  {steepBuildings.length > 0 && (
+                    <p>
+                      ⛰️ Зданий на слишком крутом склоне (не выровнены): {steepBuildings.length} (
+                      {steepBuildings
+                        .slice(0, 5)
+                        .map((b) => `way/${b.osm_id} ${b.slope_deg.toFixed(0)}°`)
+                        .join(', ')}
+                      {steepBuildings.length > 5 && ', ...'})
+                    </p>
+                  )}
+                  {voidFilledPercent > 0 && (
+                    <p>🩹 Заполнено интерполяцией: {voidFilledPercent.toFixed(1)}% карты высот</p>
+                  )}
                   <p>📦 Файл мода: <code>generated_map.zip</code></p>
                   <h4>Как установить:</h4>
                   <ol>
@@ -235,10 +833,12 @@ generated_map.zip
 │       ├── road_nodes.json (дорожная сеть)
 │       ├── decalRoad.json (дороги BeamNG)
 │       ├── preview.jpg (превью карты)
+│       ├── terrain.ter (рельеф BeamNG, 16 бит)
+│       ├── terrain.terrain.json (описание рельефа)
 │       └── art/
 │           └── terrains/
-│               ├── terrain.png (heightmap)
-│               └── terrain.ter.json (настройки)
+│               ├── terrain.png (heightmap, 16 бит)
+│               └── main.materials.json (материалы рельефа)
             </pre>
           </div>
         </section>